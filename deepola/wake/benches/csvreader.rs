@@ -21,10 +21,8 @@ fn deepola_csvreader_lineitem(c: &mut Criterion) {
 
         // Metadata for DataBlock
         let lineitem_schema = wake::data::Schema::from_example("lineitem").unwrap();
-        let mut metadata = MetaCell::Schema(lineitem_schema.clone()).into_meta_map();
-        *metadata
-            .entry(DATABLOCK_TOTAL_RECORDS.to_string())
-            .or_insert(MetaCell::Float(0.0)) = MetaCell::Float(1_000_000.0);
+        let metadata =
+            BlockMetadata::from_schema(lineitem_schema.clone()).with_expected_total(1_000_000.0);
 
         group.bench_function("deepola_csvreader", |b| {
             b.iter(|| {
@@ -32,7 +30,10 @@ fn deepola_csvreader_lineitem(c: &mut Criterion) {
                     .delimiter('|')
                     .has_headers(false)
                     .build();
-                csvreader.write_to_self(0, DataMessage::from(input_df.clone()));
+                csvreader.write_to_self(
+                    0,
+                    DataMessage::from(DataBlock::new(input_df.clone(), metadata.clone())),
+                );
                 csvreader.write_to_self(0, DataMessage::eof());
                 csvreader.run();
             });
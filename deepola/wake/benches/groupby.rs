@@ -83,7 +83,7 @@
 //         group.bench_with_input(BenchmarkId::from_parameter(num_groups), num_groups, |b, &num_groups| {
 //             let groupby_node = get_groupby_node(NUM_GROUP_KEYS, NUM_VAL_COLS);
 //             let arrayrow_records = generate_groupby_arrayrow(num_groups, NUM_GROUP_KEYS, NUM_VAL_COLS);
-//             let metadata = MetaCell::Schema(get_schema(NUM_GROUP_KEYS, NUM_VAL_COLS)).into_dm_meta_map();
+//             let metadata = BlockMetadata::from_schema(get_schema(NUM_GROUP_KEYS, NUM_VAL_COLS)).with_block_type(BlockType::Dm);
 //             let dblock =
 //                 DataBlock::new(
 //                     arrayrow_records,
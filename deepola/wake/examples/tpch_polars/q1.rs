@@ -1,4 +1,5 @@
 use crate::prelude::*;
+use wake::data::{Column, Schema};
 
 /// This node implements the following SQL query
 // select
@@ -45,6 +46,8 @@ pub fn query(
 
     // CSVReaderNode would be created for this table.
     let lineitem_csvreader_node = build_reader_node("lineitem".into(), &tableinput, &table_columns);
+    let lineitem_schema = projected_schema("lineitem", table_columns.get("lineitem"));
+    let float_column = |name: &str| Column::from_field(name.into(), wake::data::DataType::Float);
 
     // WHERE Node
     let where_node = AppenderNode::<DataFrame, MapAppender>::new()
@@ -54,6 +57,8 @@ pub fn query(
             let mask = a.lt_eq(var_date).unwrap();
             df.filter(&mask).unwrap()
         })))
+        .required_columns(vec!["l_shipdate".into()])
+        .output_schema(lineitem_schema.clone())
         .build();
 
     // EXPRESSION Node
@@ -81,6 +86,19 @@ pub fn query(
             ];
             df.hstack(&columns).unwrap()
         })))
+        .required_columns(vec![
+            "l_extendedprice".into(),
+            "l_discount".into(),
+            "l_tax".into(),
+        ])
+        .output_schema(Schema::new(
+            "lineitem".into(),
+            [
+                lineitem_schema.columns.clone(),
+                vec![float_column("disc_price"), float_column("charge")],
+            ]
+            .concat(),
+        ))
         .build();
 
     // GROUP BY Aggregate Node
@@ -149,6 +167,39 @@ pub fn query(
                 .sort(&["l_returnflag", "l_linestatus"], vec![false, false])
                 .unwrap()
         })))
+        .required_columns(vec![
+            "l_returnflag".into(),
+            "l_linestatus".into(),
+            "l_quantity_sum".into(),
+            "l_extendedprice_sum".into(),
+            "l_discount_sum".into(),
+            "disc_price_sum".into(),
+            "charge_sum".into(),
+            "l_orderkey_count".into(),
+        ])
+        .output_schema(Schema::new(
+            "q1".into(),
+            [
+                vec![
+                    lineitem_schema.get_column("l_returnflag"),
+                    lineitem_schema.get_column("l_linestatus"),
+                ],
+                [
+                    "sum_qty",
+                    "sum_base_price",
+                    "sum_disc_price",
+                    "sum_charge",
+                    "avg_qty",
+                    "avg_price",
+                    "avg_disc",
+                    "count_order",
+                ]
+                .iter()
+                .map(|name| float_column(name))
+                .collect(),
+            ]
+            .concat(),
+        ))
         .build();
 
     // Connect nodes with subscription
@@ -138,10 +138,15 @@ pub fn run_query(
 
     let start_time = Instant::now();
     log::warn!("Query {} has {} nodes", _query_no, query_service.nodes().len());
+    let plan_path = results_dir_path.join("plan.dot");
+    std::fs::write(&plan_path, query_service.graph().to_dot())
+        .unwrap_or_else(|_| panic!("Failed to write {:?}", plan_path));
+    query_service.add_reader(output_reader);
     query_service.run();
     loop {
-        let message = output_reader.read();
-        if message.is_eof() {
+        let (message, progress) = output_reader.read_with_progress();
+        // A failed query ends with an error, which join returns below.
+        if message.is_end() {
             break;
         }
         let data = message.datablock().data();
@@ -160,12 +165,20 @@ pub fn run_query(
                 epoch + 1,
                 duration
             );
+            if let Some(progress) = progress {
+                log::warn!("Progress: {}", progress);
+            }
         }
         query_result_time_ns.push(duration.as_nanos());
         last_df = data.clone();
         epoch = epoch + 1;
     }
-    query_service.join();
+    if let Err(e) = query_service.join() {
+        log::error!("Query failed: {}", e);
+    }
+    let metrics_path = results_dir_path.join("metrics.json");
+    std::fs::write(&metrics_path, query_service.metrics().to_json())
+        .unwrap_or_else(|_| panic!("Failed to write {:?}", metrics_path));
     let end_time = Instant::now();
     if epoch != 0 {
         log::warn!("Query Result");
@@ -229,48 +242,33 @@ pub fn build_reader_node_raw(
     // Get batch size and file names from tableinput tables;
     let file_format = check_file_format(&raw_input_files);
     let schema = tpch_schema(&table).unwrap();
+    let output_schema = projected_schema(&table, columns);
 
-    let mut projected_cols_index = None;
-    let mut projected_cols_names = None;
-    match columns {
-        Some(columns) => {
-            let mut cols_index = columns
-                .iter()
-                .map(|x| schema.index(x))
-                .collect::<Vec<usize>>();
-            cols_index.sort_unstable();
-            let col_names = cols_index
-                .iter()
-                .map(|x| schema.get_column_from_index(*x).name)
-                .collect::<Vec<String>>();
-            projected_cols_index = Some(cols_index);
-            projected_cols_names = Some(col_names);
-        }
-        None => {}
-    }
+    let columns: Option<Vec<String>> =
+        columns.map(|columns| columns.iter().map(|column| column.to_string()).collect());
     let input_files = df!("col" => &raw_input_files).unwrap();
 
     let reader = match file_format {
         FILE_FORMAT_CSV => CSVReaderBuilder::new()
             .delimiter('|')
             .has_headers(false)
-            .column_names(projected_cols_names)
-            .projected_cols(projected_cols_index)
+            .schema(schema.clone())
+            .columns(columns)
             .build(),
         FILE_FORMAT_PARQUET => ParquetReaderBuilder::new()
-            .column_names(projected_cols_names)
-            .projected_cols(projected_cols_index)
+            .schema(schema.clone())
+            .columns(columns)
             .build(),
         _ => {
             panic!("Invalid file format specified. Supported formats are tbl and parquet.")
         }
     };
 
-    let mut metadata = MetaCell::Schema(schema).into_meta_map();
-    *metadata
-        .entry(DATABLOCK_TOTAL_RECORDS.to_string())
-        .or_insert(MetaCell::Float(0.0)) =
-        MetaCell::Float(total_number_of_records(&table, scale) as f64);
+    // Lets the service check the columns the query reads from the table before it runs.
+    reader.declare_output_schema(output_schema);
+
+    let metadata = BlockMetadata::from_schema(schema)
+        .with_expected_total(total_number_of_records(&table, scale) as f64);
 
     let dblock = DataBlock::new(input_files, metadata);
     reader.write_to_self(0, DataMessage::from(dblock));
@@ -278,6 +276,17 @@ pub fn build_reader_node_raw(
     reader
 }
 
+/// The schema of the columns of `table` a query reads, in the order listed, or of all of its
+/// columns.
+pub fn projected_schema(table: &str, columns: Option<&Vec<&str>>) -> wake::data::Schema {
+    let schema = tpch_schema(table).unwrap();
+    let columns = match columns {
+        Some(names) => names.iter().map(|name| schema.get_column(name)).collect(),
+        None => schema.columns.clone(),
+    };
+    wake::data::Schema::new(table.to_string(), columns)
+}
+
 pub fn tpch_schema(table: &str) -> std::result::Result<wake::data::Schema, Box<dyn Error>> {
     let columns = match table {
         "lineitem" => vec![
@@ -291,9 +300,9 @@ pub fn tpch_schema(table: &str) -> std::result::Result<wake::data::Schema, Box<d
             Column::from_field("l_tax".to_string(), wake::data::DataType::Float),
             Column::from_field("l_returnflag".to_string(), wake::data::DataType::Text),
             Column::from_field("l_linestatus".to_string(), wake::data::DataType::Text),
-            Column::from_field("l_shipdate".to_string(), wake::data::DataType::Text),
-            Column::from_field("l_commitdate".to_string(), wake::data::DataType::Text),
-            Column::from_field("l_receiptdate".to_string(), wake::data::DataType::Text),
+            Column::from_field("l_shipdate".to_string(), wake::data::DataType::Date),
+            Column::from_field("l_commitdate".to_string(), wake::data::DataType::Date),
+            Column::from_field("l_receiptdate".to_string(), wake::data::DataType::Date),
             Column::from_field("l_shipinstruct".to_string(), wake::data::DataType::Text),
             Column::from_field("l_shipmode".to_string(), wake::data::DataType::Text),
             Column::from_field("l_comment".to_string(), wake::data::DataType::Text),
@@ -303,7 +312,7 @@ pub fn tpch_schema(table: &str) -> std::result::Result<wake::data::Schema, Box<d
             Column::from_field("o_custkey".to_string(), wake::data::DataType::Integer),
             Column::from_field("o_orderstatus".to_string(), wake::data::DataType::Text),
             Column::from_field("o_totalprice".to_string(), wake::data::DataType::Float),
-            Column::from_field("o_orderdate".to_string(), wake::data::DataType::Text),
+            Column::from_field("o_orderdate".to_string(), wake::data::DataType::Date),
             Column::from_field("o_orderpriority".to_string(), wake::data::DataType::Text),
             Column::from_field("o_clerk".to_string(), wake::data::DataType::Text),
             Column::from_field("o_shippriority".to_string(), wake::data::DataType::Integer),
@@ -1,13 +1,28 @@
 use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crate::{data::DataMessage, utils::log_event};
+use crate::data::{DataMessage, Payload};
+use crate::graph::{at_step_boundary, note_progress, yield_node, BarrierTracker, MetricsProbe};
+use crate::utils::log_event;
 
 use super::single_channel::*;
 
+/// How long [MultiChannelReader::read_any] waits outside of a worker pool before polling its
+/// channels again.
+const ANY_READ_WAIT: Duration = Duration::from_micros(100);
+
 /// A group of different input channels.
 #[derive(Debug)]
 pub struct MultiChannelReader<T: Send> {
     pub readers: Vec<Rc<ChannelReader<T>>>,
+
+    /// Records the read messages into the metrics of the node owning this reader.
+    probe: Option<MetricsProbe<T>>,
+
+    /// Takes the checkpoints of the node owning this reader. Checkpoint barriers are handed
+    /// to it instead of being returned.
+    barriers: Option<Rc<BarrierTracker<T>>>,
 }
 
 impl<T: Send> Default for MultiChannelReader<T> {
@@ -18,7 +33,35 @@ impl<T: Send> Default for MultiChannelReader<T> {
 
 impl<T: Send> MultiChannelReader<T> {
     pub fn new() -> Self {
-        Self { readers: vec![] }
+        Self {
+            readers: vec![],
+            probe: None,
+            barriers: None,
+        }
+    }
+
+    pub(crate) fn set_probe(&mut self, probe: MetricsProbe<T>) {
+        self.probe = Some(probe);
+    }
+
+    pub(crate) fn set_barrier_tracker(&mut self, barriers: Rc<BarrierTracker<T>>) {
+        self.barriers = Some(barriers);
+    }
+
+    /// Hands a checkpoint barrier or EOF read from the seq_no-th reader to the barrier
+    /// tracker. Returns true if the message is a barrier, which is not returned to the caller.
+    fn is_barrier(&self, seq_no: usize, message: &DataMessage<T>) -> bool {
+        match &self.barriers {
+            Some(barriers) if message.is_checkpoint() => {
+                barriers.on_barrier(seq_no);
+                true
+            }
+            Some(barriers) if message.is_eof() => {
+                barriers.on_eof(seq_no);
+                false
+            }
+            _ => false,
+        }
     }
 
     /// Add a new reader. Individual channel readers can be created using
@@ -36,7 +79,14 @@ impl<T: Send> MultiChannelReader<T> {
     pub fn read(&self, seq_no: usize) -> DataMessage<T> {
         log_event(&format!("read-message-channel-{}",seq_no), "start");
         let reader = self.reader(seq_no);
-        let message = reader.read();
+        let read_start = Instant::now();
+        let mut message = reader.read();
+        while self.is_barrier(seq_no, &message) {
+            message = reader.read();
+        }
+        if let Some(probe) = &self.probe {
+            probe.on_read(&message, reader.len() + 1, read_start.elapsed());
+        }
         log::debug!(
             "Read from (channel: {}). {:?}.",
             reader.channel_id(),
@@ -45,12 +95,53 @@ impl<T: Send> MultiChannelReader<T> {
         log_event(&format!("read-message-channel-{}",seq_no), "end");
         message
     }
+
+    /// Read a message from the seq_no-th reader if one is available, without waiting.
+    pub fn try_read(&self, seq_no: usize) -> Option<DataMessage<T>> {
+        let reader = self.reader(seq_no);
+        let mut message = reader.try_read();
+        while matches!(&message, Some(message) if self.is_barrier(seq_no, message)) {
+            message = reader.try_read();
+        }
+        if let (Some(probe), Some(message)) = (&self.probe, &message) {
+            probe.on_read(message, reader.len() + 1, Duration::ZERO);
+        }
+        message
+    }
+
+    /// Reads a message from whichever of the `seq_nos`-th readers has one first, and returns
+    /// it along with the seq_no it was read from.
+    pub fn read_any(&self, seq_nos: &[usize]) -> (usize, DataMessage<T>) {
+        let read_start = Instant::now();
+        loop {
+            if !at_step_boundary() {
+                for &seq_no in seq_nos.iter() {
+                    let reader = self.reader(seq_no);
+                    if let Some(message) = reader.try_read() {
+                        note_progress();
+                        if self.is_barrier(seq_no, &message) {
+                            continue;
+                        }
+                        if let Some(probe) = &self.probe {
+                            probe.on_read(&message, reader.len() + 1, read_start.elapsed());
+                        }
+                        return (seq_no, message);
+                    }
+                }
+            }
+            if !yield_node() {
+                thread::sleep(ANY_READ_WAIT);
+            }
+        }
+    }
 }
 
 impl<T: Send> Clone for MultiChannelReader<T> {
     fn clone(&self) -> Self {
         Self {
             readers: self.readers.clone(),
+            probe: self.probe.clone(),
+            barriers: self.barriers.clone(),
         }
     }
 }
@@ -59,6 +150,12 @@ impl<T: Send> Clone for MultiChannelReader<T> {
 #[derive(Debug)]
 pub struct MultiChannelBroadcaster<T: Send> {
     writers: Vec<ChannelWriter<T>>,
+
+    /// Records the written messages into the metrics of the node owning this broadcaster.
+    probe: Option<MetricsProbe<T>>,
+
+    /// The node owning this broadcaster, recorded as the origin of the errors it writes.
+    node_id: Option<String>,
 }
 
 impl<T: Send> Default for MultiChannelBroadcaster<T> {
@@ -69,7 +166,30 @@ impl<T: Send> Default for MultiChannelBroadcaster<T> {
 
 impl<T: Send> MultiChannelBroadcaster<T> {
     pub fn new() -> Self {
-        Self { writers: vec![] }
+        Self {
+            writers: vec![],
+            probe: None,
+            node_id: None,
+        }
+    }
+
+    pub(crate) fn set_probe(&mut self, probe: MetricsProbe<T>) {
+        self.probe = Some(probe);
+    }
+
+    pub(crate) fn set_node_id(&mut self, node_id: &str) {
+        self.node_id = Some(node_id.to_string());
+    }
+
+    /// Sets the origin of an error created by the owning node, i.e., one that has no origin
+    /// yet. Errors forwarded from upstream keep theirs.
+    fn stamp(&self, message: DataMessage<T>) -> DataMessage<T> {
+        match (message.payload(), &self.node_id) {
+            (Payload::Error(error), Some(node_id)) if error.node_id().is_none() => {
+                DataMessage::error(error.with_origin(node_id))
+            }
+            _ => message,
+        }
     }
 
     /// Add a new writer.
@@ -84,12 +204,47 @@ impl<T: Send> MultiChannelBroadcaster<T> {
 
     /// Broadcast a message to all writers.
     pub fn write(&self, message: DataMessage<T>) {
+        let message = self.stamp(message);
         log_event("write-message", "start");
+        let write_start = Instant::now();
         for w in self.iter() {
             log::debug!("Writes to (channel: {}). {:?}.", w.channel_id(), message);
             w.write(message.clone())
         }
+        if let Some(probe) = &self.probe {
+            probe.on_write(&message, write_start.elapsed());
+        }
+        log_event("write-message", "end");
+    }
+
+    /// Write a message to the seq_no-th writer only.
+    pub fn write_to(&self, seq_no: usize, message: DataMessage<T>) {
+        let message = self.stamp(message);
+        log_event("write-message", "start");
+        let write_start = Instant::now();
+        let writer = &self.writers[seq_no];
+        log::debug!("Writes to (channel: {}). {:?}.", writer.channel_id(), message);
+        writer.write(message.clone());
+        if let Some(probe) = &self.probe {
+            probe.on_write(&message, write_start.elapsed());
+        }
+        log_event("write-message", "end");
+    }
+
+    /// Same as [Self::write_to], but returns false instead of panicking if the reader of the
+    /// seq_no-th channel is gone or has stopped reading. See [ChannelWriter::write_if_open].
+    pub fn write_to_if_open(&self, seq_no: usize, message: DataMessage<T>) -> bool {
+        let message = self.stamp(message);
+        log_event("write-message", "start");
+        let write_start = Instant::now();
+        let writer = &self.writers[seq_no];
+        log::debug!("Writes to (channel: {}). {:?}.", writer.channel_id(), message);
+        let written = writer.write_if_open(message.clone());
+        if let (true, Some(probe)) = (written, &self.probe) {
+            probe.on_write(&message, write_start.elapsed());
+        }
         log_event("write-message", "end");
+        written
     }
 
     pub fn iter(&self) -> impl Iterator<Item = &ChannelWriter<T>> + '_ {
@@ -109,6 +264,8 @@ impl<T: Send> Clone for MultiChannelBroadcaster<T> {
     fn clone(&self) -> Self {
         Self {
             writers: self.writers.clone(),
+            probe: self.probe.clone(),
+            node_id: self.node_id.clone(),
         }
     }
 }
@@ -1,5 +1,7 @@
 mod channel_group;
 mod single_channel;
+mod tcp_channel;
 
 pub use channel_group::*;
 pub use single_channel::*;
+pub use tcp_channel::*;
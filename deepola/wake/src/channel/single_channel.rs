@@ -1,24 +1,166 @@
-use getset::Getters;
+use getset::{CopyGetters, Getters};
 use nanoid::nanoid;
-use std::sync::mpsc;
+use polars::prelude::DataFrame;
+use std::any::Any;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::time::Duration;
 
-use crate::data::DataMessage;
+use crate::data::{DataMessage, Payload};
+use crate::graph::{abort_if_torn_down, at_step_boundary, note_progress, yield_node};
 
-const CHANNEL_SIZE: usize = 1000000;
+/// The number of messages a channel holds unless configured otherwise.
+pub const DEFAULT_CHANNEL_CAPACITY: usize = 1000000;
+
+/// How long a writer outside of a worker pool waits before checking a full channel again.
+const FULL_CHANNEL_WAIT: Duration = Duration::from_millis(1);
+
+/// The maximum amount of data a channel holds before its writers wait for the reader.
+///
+/// The size in bytes is approximate: it is the estimated size of a [DataFrame] payload
+/// (other payload types count their shallow size), and a message is always accepted into an
+/// empty channel regardless of its size.
+///
+/// With several writers, the number of messages is approximate too: a writer checks that the
+/// channel has space and then sends, which is not atomic, so writers that find the last free
+/// space at the same time all send. The channel then holds up to one message more than its
+/// capacity per additional writer. With a single writer, the bound is exact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct ChannelCapacity {
+    messages: usize,
+    bytes: Option<usize>,
+}
+
+impl Default for ChannelCapacity {
+    fn default() -> Self {
+        Self::new(DEFAULT_CHANNEL_CAPACITY)
+    }
+}
+
+impl ChannelCapacity {
+    pub fn new(messages: usize) -> Self {
+        assert!(messages > 0, "A channel must hold at least one message");
+        Self {
+            messages,
+            bytes: None,
+        }
+    }
+
+    pub fn with_bytes(mut self, bytes: usize) -> Self {
+        self.bytes = Some(bytes);
+        self
+    }
+}
+
+/// Shared by the writers and the reader of a channel.
+#[derive(Debug)]
+struct ChannelState {
+    /// The number of messages in the channel.
+    queued: AtomicUsize,
+
+    /// The approximate size of the messages in the channel.
+    queued_bytes: AtomicUsize,
+
+    max_messages: AtomicUsize,
+
+    /// usize::MAX if the channel is not bounded in bytes.
+    max_bytes: AtomicUsize,
+
+    /// Set once the capacity is given for this channel specifically, so that a service-wide
+    /// default does not override it.
+    configured: AtomicBool,
+
+    /// Set once the reader stops reading. Writers do not wait for it anymore.
+    released: AtomicBool,
+
+    lock: Mutex<()>,
+    space_available: Condvar,
+}
+
+impl ChannelState {
+    fn new(capacity: ChannelCapacity) -> Self {
+        Self {
+            queued: AtomicUsize::new(0),
+            queued_bytes: AtomicUsize::new(0),
+            max_messages: AtomicUsize::new(capacity.messages),
+            max_bytes: AtomicUsize::new(capacity.bytes.unwrap_or(usize::MAX)),
+            configured: AtomicBool::new(false),
+            released: AtomicBool::new(false),
+            lock: Mutex::new(()),
+            space_available: Condvar::new(),
+        }
+    }
+
+    fn set_capacity(&self, capacity: ChannelCapacity) {
+        self.max_messages.store(capacity.messages, Ordering::Relaxed);
+        self.max_bytes
+            .store(capacity.bytes.unwrap_or(usize::MAX), Ordering::Relaxed);
+        self.space_available.notify_all();
+    }
+
+    fn has_space(&self) -> bool {
+        self.released.load(Ordering::Relaxed)
+            || (self.queued.load(Ordering::Relaxed) < self.max_messages.load(Ordering::Relaxed)
+                && self.queued_bytes.load(Ordering::Relaxed)
+                    < self.max_bytes.load(Ordering::Relaxed))
+    }
+
+    fn release(&self) {
+        self.released.store(true, Ordering::Relaxed);
+        self.space_available.notify_all();
+    }
+}
+
+fn approx_bytes<T: 'static>(data: &T) -> usize {
+    match (data as &dyn Any).downcast_ref::<DataFrame>() {
+        Some(df) => df.estimated_size(),
+        None => std::mem::size_of::<T>(),
+    }
+}
+
+fn message_bytes<T>(message: &DataMessage<T>, data_bytes: fn(&T) -> usize) -> usize {
+    match message.payload() {
+        Payload::Some(dblock) => data_bytes(dblock.data()),
+        _ => 0,
+    }
+}
 
 pub struct Channel;
 
 impl Channel {
-    pub fn create<T: Send>() -> (ChannelWriter<T>, ChannelReader<T>) {
-        let (channel_tx, channel_rx) = mpsc::sync_channel::<DataMessage<T>>(CHANNEL_SIZE);
+    pub fn create<T: Send + 'static>() -> (ChannelWriter<T>, ChannelReader<T>) {
+        Self::create_with(ChannelCapacity::default())
+    }
+
+    /// Creates a channel holding at most `capacity`. Unlike [Self::create], the capacity
+    /// is not overridden by the default capacity of an [crate::graph::ExecutionService].
+    pub fn create_bounded<T: Send + 'static>(
+        capacity: ChannelCapacity,
+    ) -> (ChannelWriter<T>, ChannelReader<T>) {
+        let (writer, reader) = Self::create_with(capacity);
+        writer.state.configured.store(true, Ordering::Relaxed);
+        (writer, reader)
+    }
+
+    fn create_with<T: Send + 'static>(
+        capacity: ChannelCapacity,
+    ) -> (ChannelWriter<T>, ChannelReader<T>) {
+        // The capacity is enforced by the writers; see ChannelWriter::wait_for_space.
+        let (channel_tx, channel_rx) = mpsc::channel::<DataMessage<T>>();
         let channel_id = nanoid!(CHANNEL_ID_LEN, &CHANNEL_ID_ALPHABET);
+        let state = Arc::new(ChannelState::new(capacity));
         let writer = ChannelWriter {
             channel_id: channel_id.clone(),
             channel_tx,
+            state: state.clone(),
+            data_bytes: approx_bytes::<T>,
         };
         let reader = ChannelReader {
             channel_id,
             channel_rx,
+            state,
+            data_bytes: approx_bytes::<T>,
         };
         (writer, reader)
     }
@@ -35,16 +177,99 @@ pub struct ChannelWriter<T: Send> {
     #[getset(get = "pub")]
     channel_id: String,
 
-    channel_tx: mpsc::SyncSender<DataMessage<T>>,
+    channel_tx: mpsc::Sender<DataMessage<T>>,
+
+    state: Arc<ChannelState>,
+
+    data_bytes: fn(&T) -> usize,
 }
 
 impl<T: Send> ChannelWriter<T> {
+    /// Waits until the channel has space for the message. When called from a node scheduled
+    /// on a [crate::graph::WorkerPool], the node yields its worker thread instead of blocking.
+    /// The message is dropped if the reader has released the channel.
     pub fn write(&self, message: DataMessage<T>) {
-        match self.channel_tx.send(message) {
-            Ok(_) => (),
-            Err(e) => panic!("{}", e.to_string()),
+        if let Err(e) = self.send(message) {
+            panic!("{}", e.to_string())
         }
     }
+
+    /// Same as [Self::write], but returns false instead of panicking if the reader of this
+    /// channel is already gone. The message is dropped, and false returned, if the reader has
+    /// stopped reading (see [ChannelReader::release]).
+    pub fn write_if_open(&self, message: DataMessage<T>) -> bool {
+        if self.state.released.load(Ordering::Relaxed) {
+            return false;
+        }
+        self.send(message).is_ok()
+    }
+
+    /// Same as [Self::write_if_open], but never waits: the message goes into the channel even
+    /// if the channel is full. Only for a message that ends the stream (EOF, STOP or an
+    /// error), e.g., from a node that is being torn down and must not block anymore.
+    pub fn write_terminal_if_open(&self, message: DataMessage<T>) -> bool {
+        assert!(
+            message.is_end(),
+            "Only a message that ends the stream may ignore the capacity of a channel"
+        );
+        if self.state.released.load(Ordering::Relaxed) {
+            return false;
+        }
+        self.push(message).is_ok()
+    }
+
+    /// Sets the capacity of this channel, which is shared by all its writers.
+    pub fn set_capacity(&self, capacity: ChannelCapacity) {
+        self.state.configured.store(true, Ordering::Relaxed);
+        self.state.set_capacity(capacity);
+    }
+
+    /// Sets the capacity unless one was set for this channel specifically.
+    pub(crate) fn set_default_capacity(&self, capacity: ChannelCapacity) {
+        if !self.state.configured.load(Ordering::Relaxed) {
+            self.state.set_capacity(capacity);
+        }
+    }
+
+    fn wait_for_space(&self) {
+        while !self.state.has_space() {
+            if yield_node() {
+                abort_if_torn_down();
+                continue;
+            }
+            let guard = self.state.lock.lock().unwrap();
+            if !self.state.has_space() {
+                let _ = self
+                    .state
+                    .space_available
+                    .wait_timeout(guard, FULL_CHANNEL_WAIT)
+                    .unwrap();
+            }
+        }
+    }
+
+    fn send(&self, message: DataMessage<T>) -> Result<(), mpsc::SendError<DataMessage<T>>> {
+        self.wait_for_space();
+        self.push(message)
+    }
+
+    /// Sends the message regardless of the capacity of the channel, unless the reader has
+    /// released the channel: then nothing reads the message, and it would only take memory.
+    fn push(&self, message: DataMessage<T>) -> Result<(), mpsc::SendError<DataMessage<T>>> {
+        if self.state.released.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        // Counted before sending so that the reader never sees a message it was not told about.
+        let bytes = message_bytes(&message, self.data_bytes);
+        self.state.queued.fetch_add(1, Ordering::Relaxed);
+        self.state.queued_bytes.fetch_add(bytes, Ordering::Relaxed);
+        let result = self.channel_tx.send(message);
+        if result.is_err() {
+            self.state.queued.fetch_sub(1, Ordering::Relaxed);
+            self.state.queued_bytes.fetch_sub(bytes, Ordering::Relaxed);
+        }
+        result
+    }
 }
 
 impl<T: Send> Clone for ChannelWriter<T> {
@@ -52,6 +277,8 @@ impl<T: Send> Clone for ChannelWriter<T> {
         Self {
             channel_id: self.channel_id.clone(),
             channel_tx: self.channel_tx.clone(),
+            state: self.state.clone(),
+            data_bytes: self.data_bytes,
         }
     }
 }
@@ -65,14 +292,45 @@ pub struct ChannelReader<T: Send> {
     channel_id: String,
 
     channel_rx: mpsc::Receiver<DataMessage<T>>,
+
+    state: Arc<ChannelState>,
+
+    data_bytes: fn(&T) -> usize,
 }
 
 const EMPTY_CHANNEL_MSG: &str = "receiving on an empty channel";
 
 impl<T: Send> ChannelReader<T> {
+    /// The number of messages waiting in this channel.
+    pub fn len(&self) -> usize {
+        self.state.queued.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Tells the writers that no more messages will be read from this channel, so that they
+    /// do not wait for space anymore.
+    pub fn release(&self) {
+        self.state.release();
+    }
+
+    fn dequeued(&self, message: &DataMessage<T>) {
+        self.state.queued.fetch_sub(1, Ordering::Relaxed);
+        self.state
+            .queued_bytes
+            .fetch_sub(message_bytes(message, self.data_bytes), Ordering::Relaxed);
+        self.state.space_available.notify_all();
+    }
+
     pub fn try_read(&self) -> Option<DataMessage<T>> {
+        abort_if_torn_down();
         match self.channel_rx.try_recv() {
-            Ok(v) => Some(v),
+            Ok(v) => {
+                self.dequeued(&v);
+                Some(v)
+            }
             Err(e) => {
                 if e.to_string().eq(&EMPTY_CHANNEL_MSG.to_string()) {
                     None
@@ -83,20 +341,51 @@ impl<T: Send> ChannelReader<T> {
         }
     }
 
+    /// Blocks until a message arrives. When called from a node scheduled on a [crate::graph::WorkerPool],
+    /// the node yields its worker thread instead of blocking it.
     pub fn read(&self) -> DataMessage<T> {
+        loop {
+            if !at_step_boundary() {
+                if let Some(message) = self.try_read() {
+                    note_progress();
+                    return message;
+                }
+            }
+            if !yield_node() {
+                break;
+            }
+        }
         match self.channel_rx.recv() {
-            Ok(m) => m,
+            Ok(m) => {
+                self.dequeued(&m);
+                m
+            }
             Err(e) => {
                 panic!("{}", e.to_string())
             }
         }
     }
+
+    /// Blocks the calling thread until a message arrives. Returns None instead of panicking
+    /// once every writer of this channel is gone.
+    pub(super) fn read_if_open(&self) -> Option<DataMessage<T>> {
+        let message = self.channel_rx.recv().ok()?;
+        self.dequeued(&message);
+        Some(message)
+    }
+}
+
+impl<T: Send> Drop for ChannelReader<T> {
+    fn drop(&mut self) {
+        self.release();
+    }
 }
 
 #[cfg(test)]
 mod tests {
 
     use std::thread;
+    use std::time::Instant;
 
     use super::*;
 
@@ -111,4 +400,79 @@ mod tests {
             drop(writer);
         });
     }
+
+    /// A writer waits while the channel is full, until the reader takes a message out.
+    #[test]
+    fn writer_waits_for_space() {
+        let (writer, reader) = Channel::create_bounded::<String>(ChannelCapacity::new(2));
+        writer.write(DataMessage::from("a".to_string()));
+        writer.write(DataMessage::from("b".to_string()));
+        let wait = Duration::from_millis(20);
+        let handle = thread::spawn(move || {
+            let start = Instant::now();
+            writer.write(DataMessage::from("c".to_string()));
+            start.elapsed()
+        });
+        thread::sleep(wait);
+        assert_eq!(reader.len(), 2);
+        assert_eq!(reader.read().datablock().data(), "a");
+        assert!(handle.join().unwrap() >= wait);
+        assert_eq!(reader.len(), 2);
+    }
+
+    #[test]
+    fn bounds_bytes() {
+        let capacity = ChannelCapacity::new(100).with_bytes(std::mem::size_of::<String>());
+        let (writer, reader) = Channel::create_bounded::<String>(capacity);
+        writer.write(DataMessage::from("a".to_string()));
+        assert!(!writer.state.has_space());
+        reader.read();
+        assert!(writer.state.has_space());
+    }
+
+    #[test]
+    fn default_capacity_does_not_override() {
+        let (writer, _reader) = Channel::create_bounded::<String>(ChannelCapacity::new(1));
+        writer.set_default_capacity(ChannelCapacity::new(5));
+        assert_eq!(writer.state.max_messages.load(Ordering::Relaxed), 1);
+
+        let (writer, _reader) = Channel::create::<String>();
+        writer.set_default_capacity(ChannelCapacity::new(5));
+        assert_eq!(writer.state.max_messages.load(Ordering::Relaxed), 5);
+    }
+
+    /// Writers never wait for a reader that is gone.
+    #[test]
+    fn released_channel_does_not_block() {
+        let (writer, reader) = Channel::create_bounded::<String>(ChannelCapacity::new(1));
+        writer.write(DataMessage::from("a".to_string()));
+        reader.release();
+        writer.write(DataMessage::from("b".to_string()));
+        assert!(!writer.write_if_open(DataMessage::from("c".to_string())));
+    }
+
+    /// Messages written after the reader is gone are dropped rather than queued.
+    #[test]
+    fn released_channel_drops_messages() {
+        let (writer, reader) = Channel::create_bounded::<String>(ChannelCapacity::new(2));
+        writer.write(DataMessage::from("a".to_string()));
+        reader.release();
+        for _ in 0..10 {
+            writer.write(DataMessage::from("b".to_string()));
+        }
+        assert_eq!(reader.len(), 1);
+        assert_eq!(reader.read().datablock().data(), "a");
+        assert!(reader.try_read().is_none());
+    }
+
+    /// A message that ends the stream goes into a full channel without waiting.
+    #[test]
+    fn terminal_message_ignores_capacity() {
+        let (writer, reader) = Channel::create_bounded::<String>(ChannelCapacity::new(1));
+        writer.write(DataMessage::from("a".to_string()));
+        assert!(writer.write_terminal_if_open(DataMessage::eof()));
+        assert_eq!(reader.len(), 2);
+        reader.read();
+        assert!(reader.read().is_eof());
+    }
 }
@@ -0,0 +1,296 @@
+use polars::prelude::{DataFrame, IpcReader, IpcWriter, SerReader, SerWriter};
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufReader, BufWriter, Cursor, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::thread;
+
+use super::{Channel, ChannelReader, ChannelWriter};
+use crate::data::{BlockMetadata, DataBlock, DataMessage, Payload, Signal, StreamError};
+
+/// How the data of a [DataBlock] is encoded when sent over a [TcpChannel].
+pub trait WireFormat: Sized + Send + Sync + 'static {
+    fn encode(&self) -> io::Result<Vec<u8>>;
+
+    fn decode(bytes: &[u8]) -> io::Result<Self>;
+}
+
+/// Encoded as Arrow IPC.
+impl WireFormat for DataFrame {
+    fn encode(&self) -> io::Result<Vec<u8>> {
+        let mut bytes = vec![];
+        IpcWriter::new(&mut bytes)
+            .finish(&mut self.clone())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        Ok(bytes)
+    }
+
+    fn decode(bytes: &[u8]) -> io::Result<Self> {
+        IpcReader::new(Cursor::new(bytes))
+            .finish()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+    }
+}
+
+impl WireFormat for String {
+    fn encode(&self) -> io::Result<Vec<u8>> {
+        Ok(self.as_bytes().to_vec())
+    }
+
+    fn decode(bytes: &[u8]) -> io::Result<Self> {
+        String::from_utf8(bytes.to_vec()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// The largest header or data a peer may announce. A longer frame is taken to be corrupt
+/// rather than allocated.
+const MAX_FRAME_LEN: u64 = 1 << 30;
+
+/// Everything of a message except the data of a [DataBlock]. Sent as JSON.
+#[derive(Serialize, Deserialize)]
+enum FrameHeader {
+    /// Followed by the encoded data.
+    Data(BlockMetadata),
+    Eof,
+    Signal(Signal),
+    Error(StreamError),
+}
+
+/// A channel between two processes, e.g., from a node of a query running on a worker to a
+/// node of the same query running on the coordinator.
+///
+/// Either end is a regular [ChannelWriter] or [ChannelReader], backed by a thread that moves
+/// the messages between the local channel and the socket. Messages arrive in the order they
+/// are written, and the stream ends with the EOF, STOP or error written by the sending side.
+/// Each message is sent as the length of its [FrameHeader] (a big-endian u32) and the header,
+/// followed, for data, by the length of the encoded data (a big-endian u64) and the data.
+/// If the connection fails or a message cannot be decoded, the receiving side ends the stream
+/// with an error.
+pub struct TcpChannel;
+
+impl TcpChannel {
+    /// Connects to a [TcpChannel] listening at `addr`. The messages written to the returned
+    /// writer are sent to it; the writer can be added to a node as an output channel with
+    /// [crate::graph::Subscribable::add].
+    pub fn connect<T: WireFormat>(addr: impl ToSocketAddrs) -> io::Result<ChannelWriter<T>> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        let (writer, reader) = Channel::create::<T>();
+        thread::spawn(move || {
+            let peer = stream.peer_addr();
+            if let Err(e) = send_messages(&reader, stream) {
+                log::error!("Sending to {:?} failed: {}", peer, e);
+            }
+        });
+        Ok(writer)
+    }
+
+    /// Accepts the next connection on `listener`, and returns the reader of the messages the
+    /// other process sends.
+    pub fn accept<T: WireFormat>(listener: &TcpListener) -> io::Result<ChannelReader<T>> {
+        let (writer, reader) = Channel::create::<T>();
+        Self::accept_into(listener, writer)?;
+        Ok(reader)
+    }
+
+    /// Same as [Self::accept], but writes the messages to `writer`, e.g., an input channel of
+    /// a node obtained with [crate::graph::ExecutionNode::self_writer].
+    pub fn accept_into<T: WireFormat>(
+        listener: &TcpListener,
+        writer: ChannelWriter<T>,
+    ) -> io::Result<()> {
+        let (stream, peer) = listener.accept()?;
+        stream.set_nodelay(true)?;
+        thread::spawn(move || {
+            if let Err(e) = receive_messages(stream, &writer) {
+                log::error!("Receiving from {} failed: {}", peer, e);
+                // Ends the stream, so that the reader does not wait for the rest of it.
+                let error = StreamError::from(e).context(&peer.to_string());
+                writer.write_if_open(DataMessage::error(error));
+            }
+        });
+        Ok(())
+    }
+}
+
+/// Sends the messages read from `reader` until the end of the stream, or until every writer
+/// of `reader` is gone.
+fn send_messages<T: WireFormat>(reader: &ChannelReader<T>, stream: TcpStream) -> io::Result<()> {
+    let mut stream = BufWriter::new(stream);
+    while let Some(message) = reader.read_if_open() {
+        write_message(&mut stream, &message)?;
+        if message.is_end() {
+            break;
+        }
+        if reader.is_empty() {
+            stream.flush()?;
+        }
+    }
+    stream.flush()
+}
+
+/// Writes the messages received on `stream` to `writer` until the end of the stream.
+fn receive_messages<T: WireFormat>(stream: TcpStream, writer: &ChannelWriter<T>) -> io::Result<()> {
+    let mut stream = BufReader::new(stream);
+    loop {
+        let message = read_message(&mut stream)?;
+        let is_end = message.is_end();
+        if !writer.write_if_open(message) || is_end {
+            return Ok(());
+        }
+    }
+}
+
+fn write_message<T: WireFormat>(
+    stream: &mut impl Write,
+    message: &DataMessage<T>,
+) -> io::Result<()> {
+    let (header, data) = match message.payload() {
+        Payload::Some(dblock) => (
+            FrameHeader::Data(dblock.metadata().clone()),
+            Some(dblock.data().encode()?),
+        ),
+        Payload::EOF => (FrameHeader::Eof, None),
+        Payload::Signal(signal) => (FrameHeader::Signal(signal), None),
+        Payload::Error(error) => (FrameHeader::Error(error), None),
+    };
+    let header = serde_json::to_vec(&header)?;
+    stream.write_all(&(header.len() as u32).to_be_bytes())?;
+    stream.write_all(&header)?;
+    if let Some(data) = data {
+        stream.write_all(&(data.len() as u64).to_be_bytes())?;
+        stream.write_all(&data)?;
+    }
+    Ok(())
+}
+
+fn read_message<T: WireFormat>(stream: &mut impl Read) -> io::Result<DataMessage<T>> {
+    let mut header_len = [0; 4];
+    stream.read_exact(&mut header_len)?;
+    let header = read_frame(stream, u32::from_be_bytes(header_len) as u64)?;
+    let message = match serde_json::from_slice(&header)? {
+        FrameHeader::Data(metadata) => {
+            let mut data_len = [0; 8];
+            stream.read_exact(&mut data_len)?;
+            let data = read_frame(stream, u64::from_be_bytes(data_len))?;
+            DataMessage::from(DataBlock::new(T::decode(&data)?, metadata))
+        }
+        FrameHeader::Eof => DataMessage::eof(),
+        FrameHeader::Signal(Signal::STOP) => DataMessage::stop(),
+        FrameHeader::Signal(Signal::PAUSE) => DataMessage::pause(),
+        FrameHeader::Signal(Signal::RESUME) => DataMessage::resume(),
+        FrameHeader::Signal(Signal::CHECKPOINT) => DataMessage::checkpoint(),
+        FrameHeader::Error(error) => DataMessage::error(error),
+    };
+    Ok(message)
+}
+
+fn read_frame(stream: &mut impl Read, len: u64) -> io::Result<Vec<u8>> {
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Frame of {} bytes exceeds {} bytes", len, MAX_FRAME_LEN),
+        ));
+    }
+    let mut frame = vec![0; len as usize];
+    stream.read_exact(&mut frame)?;
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{MetaCell, Schema};
+    use crate::graph::{ExecutionNode, ExecutionService, NodeReader, Subscribable};
+    use crate::processor::SimpleMapper;
+    use polars::prelude::{NamedFrom, Series};
+
+    fn localhost() -> TcpListener {
+        TcpListener::bind("127.0.0.1:0").unwrap()
+    }
+
+    #[test]
+    fn sends_data_metadata_and_signals() {
+        let listener = localhost();
+        let writer = TcpChannel::connect::<String>(listener.local_addr().unwrap()).unwrap();
+        let metadata = BlockMetadata::new()
+            .with_progress(0.5)
+            .with_schema(Schema::from_example("lineitem").unwrap())
+            .with_entry("key", MetaCell::from("value"));
+        writer.write(DataMessage::from(DataBlock::new(
+            "a".to_string(),
+            metadata.clone(),
+        )));
+        writer.write(DataMessage::checkpoint());
+        writer.write(DataMessage::eof());
+
+        let reader = TcpChannel::accept::<String>(&listener).unwrap();
+        let message = reader.read();
+        assert_eq!(message.datablock().data(), "a");
+        assert_eq!(message.datablock().metadata(), &metadata);
+        assert_eq!(message.datablock().schema().index("l_partkey"), 1);
+        assert!(reader.read().is_checkpoint());
+        assert!(reader.read().is_eof());
+    }
+
+    #[test]
+    fn encodes_dataframe_as_arrow_ipc() {
+        let df = polars::df!("a" => [1, 2, 3]).unwrap();
+        let encoded = df.encode().unwrap();
+        assert_eq!(DataFrame::decode(&encoded).unwrap(), df);
+    }
+
+    #[test]
+    fn ends_stream_with_error_on_lost_connection() {
+        let listener = localhost();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let reader = TcpChannel::accept::<String>(&listener).unwrap();
+        drop(stream);
+        assert!(reader.read().is_error());
+    }
+
+    #[test]
+    fn rejects_oversized_frame() {
+        let listener = localhost();
+        let mut stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let reader = TcpChannel::accept::<String>(&listener).unwrap();
+        let header = serde_json::to_vec(&FrameHeader::Data(BlockMetadata::new())).unwrap();
+        stream
+            .write_all(&(header.len() as u32).to_be_bytes())
+            .unwrap();
+        stream.write_all(&header).unwrap();
+        stream.write_all(&u64::MAX.to_be_bytes()).unwrap();
+        match reader.read().payload() {
+            Payload::Error(error) => assert!(error.message().contains("exceeds")),
+            payload => panic!("Expected an error, got {:?}", payload),
+        }
+    }
+
+    /// A node of a "worker" query sends what it writes to a node of a "coordinator" query.
+    #[test]
+    fn connects_nodes_of_two_queries() {
+        let listener = localhost();
+        let worker_node =
+            ExecutionNode::from(SimpleMapper::from(|r: &String| Some(format!("{}!", r))));
+        worker_node.add(TcpChannel::connect(listener.local_addr().unwrap()).unwrap());
+        for input in ["a", "b"] {
+            worker_node.write_to_self(0, DataMessage::from(input.to_string()));
+        }
+        worker_node.write_to_self(0, DataMessage::eof());
+
+        let coordinator_node = ExecutionNode::<String>::from(SimpleMapper::identity());
+        TcpChannel::accept_into(&listener, coordinator_node.self_writer(0)).unwrap();
+        let reader_node = NodeReader::new(&coordinator_node);
+
+        let mut worker = ExecutionService::create();
+        worker.add(worker_node);
+        let mut coordinator = ExecutionService::create();
+        coordinator.add(coordinator_node);
+        worker.run();
+        coordinator.run();
+        assert_eq!(reader_node.read().datablock().data(), "a!");
+        assert_eq!(reader_node.read().datablock().data(), "b!");
+        assert!(reader_node.read().is_eof());
+        worker.join().unwrap();
+        coordinator.join().unwrap();
+    }
+}
@@ -0,0 +1,168 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::{MetaCell, Schema};
+
+/// The kind of a [super::DataBlock], kept as is by the operators passing the block on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlockType {
+    Dm,
+    Da,
+}
+
+/// The metadata of a [super::DataBlock].
+///
+/// Every field is optional, since a block carries only what its writer knows, e.g., a test
+/// block may carry nothing at all. Entries other than the fields below can be added under a
+/// key of their own with [Self::insert].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BlockMetadata {
+    schema: Option<Schema>,
+
+    /// The fraction of its input the writer of the block had processed, between 0 and 1.
+    progress: Option<f64>,
+
+    /// The (estimated) number of records of the input, from which a reader derives the
+    /// progress of the blocks it writes.
+    expected_total: Option<f64>,
+
+    block_type: Option<BlockType>,
+
+    /// The position of the block in the output of its source, e.g., the index of the file a
+    /// reader read it from.
+    epoch: Option<u64>,
+
+    /// What the block originates from, e.g., the file a reader read it from.
+    source_id: Option<String>,
+
+    /// User-defined entries.
+    entries: HashMap<String, MetaCell>,
+}
+
+impl BlockMetadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The metadata of a whole table (or a result) of `schema`: a [BlockType::Da] block with
+    /// a progress of 1.
+    pub fn from_schema(schema: Schema) -> Self {
+        Self::new()
+            .with_schema(schema)
+            .with_block_type(BlockType::Da)
+            .with_progress(1.0)
+    }
+
+    pub fn schema(&self) -> Option<&Schema> {
+        self.schema.as_ref()
+    }
+
+    pub fn progress(&self) -> Option<f64> {
+        self.progress
+    }
+
+    pub fn expected_total(&self) -> Option<f64> {
+        self.expected_total
+    }
+
+    pub fn block_type(&self) -> Option<BlockType> {
+        self.block_type
+    }
+
+    pub fn epoch(&self) -> Option<u64> {
+        self.epoch
+    }
+
+    pub fn source_id(&self) -> Option<&str> {
+        self.source_id.as_deref()
+    }
+
+    pub fn set_schema(&mut self, schema: Schema) {
+        self.schema = Some(schema);
+    }
+
+    pub fn set_progress(&mut self, progress: f64) {
+        self.progress = Some(progress);
+    }
+
+    pub fn set_expected_total(&mut self, expected_total: f64) {
+        self.expected_total = Some(expected_total);
+    }
+
+    pub fn set_block_type(&mut self, block_type: BlockType) {
+        self.block_type = Some(block_type);
+    }
+
+    pub fn set_epoch(&mut self, epoch: u64) {
+        self.epoch = Some(epoch);
+    }
+
+    pub fn set_source_id(&mut self, source_id: &str) {
+        self.source_id = Some(source_id.to_string());
+    }
+
+    pub fn with_schema(mut self, schema: Schema) -> Self {
+        self.set_schema(schema);
+        self
+    }
+
+    pub fn with_progress(mut self, progress: f64) -> Self {
+        self.set_progress(progress);
+        self
+    }
+
+    pub fn with_expected_total(mut self, expected_total: f64) -> Self {
+        self.set_expected_total(expected_total);
+        self
+    }
+
+    pub fn with_block_type(mut self, block_type: BlockType) -> Self {
+        self.set_block_type(block_type);
+        self
+    }
+
+    /// A user-defined entry.
+    pub fn get(&self, key: &str) -> Option<&MetaCell> {
+        self.entries.get(key)
+    }
+
+    /// Adds a user-defined entry, replacing the one under the same key, if any.
+    pub fn insert(&mut self, key: &str, value: MetaCell) {
+        self.entries.insert(key.to_string(), value);
+    }
+
+    pub fn with_entry(mut self, key: &str, value: MetaCell) -> Self {
+        self.insert(key, value);
+        self
+    }
+
+    /// The user-defined entries.
+    pub fn entries(&self) -> &HashMap<String, MetaCell> {
+        &self.entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whole_table_is_complete() {
+        let schema = Schema::from_example("lineitem").unwrap();
+        let metadata = BlockMetadata::from_schema(schema.clone());
+        assert_eq!(metadata.schema(), Some(&schema));
+        assert_eq!(metadata.block_type(), Some(BlockType::Da));
+        assert_eq!(metadata.progress(), Some(1.0));
+        assert_eq!(metadata.expected_total(), None);
+    }
+
+    #[test]
+    fn keeps_user_defined_entries() {
+        let mut metadata = BlockMetadata::new().with_entry("key", MetaCell::from("value"));
+        metadata.set_progress(0.5);
+        assert_eq!(metadata.get("key"), Some(&MetaCell::from("value")));
+        assert_eq!(metadata.get("missing"), None);
+        assert_eq!(metadata.entries().len(), 1);
+        assert_eq!(metadata.progress(), Some(0.5));
+    }
+}
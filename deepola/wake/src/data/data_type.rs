@@ -1,3 +1,5 @@
+use polars::prelude::DataType as PolarsDataType;
+use serde::{Deserialize, Serialize};
 use std::collections::hash_map::DefaultHasher;
 use std::convert::Infallible;
 use std::error::Error;
@@ -6,17 +8,60 @@ use std::hash::Hasher;
 use std::str;
 use std::str::FromStr;
 
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub enum DataType {
     Boolean,
     UnsignedInt,
     Integer,
     Float,
     Text,
+    Date,
     Tuple,
     Null,
 }
 
+// Every integer (or float) width of Polars maps to the single integer (or float) type here,
+// which maps back to the widest one. Decimals are read as floats.
+impl TryFrom<&DataType> for PolarsDataType {
+    type Error = Box<dyn Error>;
+
+    fn try_from(dtype: &DataType) -> Result<Self, Self::Error> {
+        match dtype {
+            DataType::Boolean => Ok(PolarsDataType::Boolean),
+            DataType::UnsignedInt => Ok(PolarsDataType::UInt64),
+            DataType::Integer => Ok(PolarsDataType::Int64),
+            DataType::Float => Ok(PolarsDataType::Float64),
+            DataType::Text => Ok(PolarsDataType::Utf8),
+            DataType::Date => Ok(PolarsDataType::Date),
+            DataType::Null => Ok(PolarsDataType::Null),
+            DataType::Tuple => Err("Tuple has no Polars data type".into()),
+        }
+    }
+}
+
+impl TryFrom<&PolarsDataType> for DataType {
+    type Error = Box<dyn Error>;
+
+    fn try_from(dtype: &PolarsDataType) -> Result<Self, Self::Error> {
+        match dtype {
+            PolarsDataType::Boolean => Ok(DataType::Boolean),
+            PolarsDataType::UInt8
+            | PolarsDataType::UInt16
+            | PolarsDataType::UInt32
+            | PolarsDataType::UInt64 => Ok(DataType::UnsignedInt),
+            PolarsDataType::Int8
+            | PolarsDataType::Int16
+            | PolarsDataType::Int32
+            | PolarsDataType::Int64 => Ok(DataType::Integer),
+            PolarsDataType::Float32 | PolarsDataType::Float64 => Ok(DataType::Float),
+            PolarsDataType::Utf8 => Ok(DataType::Text),
+            PolarsDataType::Date => Ok(DataType::Date),
+            PolarsDataType::Null => Ok(DataType::Null),
+            dtype => Err(format!("Unsupported Polars data type: {:?}", dtype).into()),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub enum DataCell {
     Boolean(bool),
@@ -462,6 +507,31 @@ mod tests {
         );
     }
 
+    #[test]
+    fn can_convert_to_polars_dtype() {
+        for dtype in [
+            DataType::Boolean,
+            DataType::UnsignedInt,
+            DataType::Integer,
+            DataType::Float,
+            DataType::Text,
+            DataType::Date,
+            DataType::Null,
+        ] {
+            let polars_dtype = PolarsDataType::try_from(&dtype).unwrap();
+            assert_eq!(DataType::try_from(&polars_dtype).unwrap(), dtype);
+        }
+        assert!(PolarsDataType::try_from(&DataType::Tuple).is_err());
+        assert_eq!(
+            DataType::try_from(&PolarsDataType::Int32).unwrap(),
+            DataType::Integer
+        );
+        assert_eq!(
+            DataType::try_from(&PolarsDataType::Float32).unwrap(),
+            DataType::Float
+        );
+    }
+
     #[test]
     fn can_hash_datacell() {
         let cell1 = DataCell::Integer(1);
@@ -0,0 +1,87 @@
+use getset::Getters;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// What went wrong in a [StreamError].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StreamErrorKind {
+    /// An input could not be read, e.g., a file is missing.
+    Io,
+
+    /// An input could not be parsed, e.g., a file is malformed.
+    InvalidData,
+
+    /// An operator failed while processing, e.g., it panicked.
+    Operator,
+}
+
+/// An error that ends a stream. Sent downstream as [super::Payload::Error] in place of the
+/// rest of the stream, and passed on as is by every node, so that it reaches the reader of
+/// the query along with the node it originated from.
+#[derive(Debug, Clone, PartialEq, Getters, Serialize, Deserialize)]
+#[getset(get = "pub")]
+pub struct StreamError {
+    /// The node the error originated from. Set by the node as the error is written, so an
+    /// operator creates the error without it.
+    node_id: Option<String>,
+
+    kind: StreamErrorKind,
+
+    message: String,
+}
+
+impl StreamError {
+    pub fn new(kind: StreamErrorKind, message: &str) -> Self {
+        Self {
+            node_id: None,
+            kind,
+            message: message.to_string(),
+        }
+    }
+
+    /// Prefixes the message with what failed, e.g., the file that could not be read.
+    pub fn context(mut self, context: &str) -> Self {
+        self.message = format!("{}: {}", context, self.message);
+        self
+    }
+
+    /// Sets the node the error originated from, unless it is already set.
+    pub fn with_origin(mut self, node_id: &str) -> Self {
+        if self.node_id.is_none() {
+            self.node_id = Some(node_id.to_string());
+        }
+        self
+    }
+}
+
+impl From<std::io::Error> for StreamError {
+    fn from(error: std::io::Error) -> Self {
+        Self::new(StreamErrorKind::Io, &error.to_string())
+    }
+}
+
+impl From<polars::prelude::PolarsError> for StreamError {
+    fn from(error: polars::prelude::PolarsError) -> Self {
+        match error {
+            polars::prelude::PolarsError::Io(error) => Self::from(error),
+            error => Self::new(StreamErrorKind::InvalidData, &error.to_string()),
+        }
+    }
+}
+
+impl From<polars::export::arrow::error::Error> for StreamError {
+    fn from(error: polars::export::arrow::error::Error) -> Self {
+        Self::from(polars::prelude::PolarsError::from(error))
+    }
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.node_id {
+            Some(node_id) => write!(f, "Node [{}] failed: {}", node_id, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for StreamError {}
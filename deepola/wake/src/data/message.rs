@@ -2,6 +2,7 @@ use getset::Getters;
 use std::fmt::Debug;
 
 use super::payload::*;
+use super::StreamError;
 
 /// DataMessage is the unit of exchanging information between execution nodes.
 #[derive(Getters)]
@@ -57,10 +58,51 @@ impl<T> DataMessage<T> {
         }
     }
 
+    pub fn pause() -> Self {
+        Self {
+            payload: Payload::Signal(Signal::PAUSE),
+        }
+    }
+
+    pub fn resume() -> Self {
+        Self {
+            payload: Payload::Signal(Signal::RESUME),
+        }
+    }
+
+    pub fn checkpoint() -> Self {
+        Self {
+            payload: Payload::Signal(Signal::CHECKPOINT),
+        }
+    }
+
+    pub fn error(error: StreamError) -> Self {
+        Self {
+            payload: Payload::Error(error),
+        }
+    }
+
     pub fn is_eof(&self) -> bool {
         matches!(self.payload, Payload::EOF)
     }
 
+    pub fn is_stop(&self) -> bool {
+        matches!(self.payload, Payload::Signal(Signal::STOP))
+    }
+
+    pub fn is_checkpoint(&self) -> bool {
+        matches!(self.payload, Payload::Signal(Signal::CHECKPOINT))
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(self.payload, Payload::Error(_))
+    }
+
+    /// True for the message that ends a stream: EOF, STOP, or an error.
+    pub fn is_end(&self) -> bool {
+        self.is_eof() || self.is_stop() || self.is_error()
+    }
+
     pub fn is_present(&self) -> bool {
         !self.is_eof()
     }
@@ -1,20 +1,13 @@
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
 
 use super::{Column, Schema};
 
-pub const SCHEMA_META_NAME: &str = "reserved.schema";
-pub const DATABLOCK_TYPE: &str = "reserved.type";
-pub const DATABLOCK_CARDINALITY: &str = "reserved.cardinality";
-pub const DATABLOCK_TOTAL_RECORDS: &str = "reserved.total_blocks";
-
-pub const DATABLOCK_TYPE_DM: &str = "dm";
-pub const DATABLOCK_TYPE_DA: &str = "da";
-
 pub const DEFAULT_GROUPBY_KEY: &str = "_default_groupby_key";
 pub const DEFAULT_GROUP_COLUMN: &str = "_default_group_column";
 pub const DEFAULT_GROUP_COLUMN_COUNT: &str = "_default_group_column_count";
 
-#[derive(Clone, Debug, PartialEq)]
+/// The value of a user-defined entry of a [super::BlockMetadata].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum MetaCell {
     Schema(Schema),
     Text(String),
@@ -28,22 +21,6 @@ impl MetaCell {
             _ => panic!("Not a Valid Schema DataCell"),
         }
     }
-
-    pub fn into_meta_map(&self) -> HashMap<String, MetaCell> {
-        HashMap::from([
-            (SCHEMA_META_NAME.into(), self.clone()),
-            (DATABLOCK_TYPE.into(), MetaCell::from(DATABLOCK_TYPE_DA)),
-            (DATABLOCK_CARDINALITY.into(), MetaCell::from(1.0)),
-        ])
-    }
-
-    pub fn into_dm_meta_map(&self) -> HashMap<String, MetaCell> {
-        HashMap::from([
-            (SCHEMA_META_NAME.into(), self.clone()),
-            (DATABLOCK_TYPE.into(), MetaCell::from(DATABLOCK_TYPE_DM)),
-            (DATABLOCK_CARDINALITY.into(), MetaCell::from(1.0)),
-        ])
-    }
 }
 
 impl From<&str> for MetaCell {
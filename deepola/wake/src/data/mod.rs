@@ -1,6 +1,8 @@
 mod arithmetic;
 mod array_row;
+mod block_metadata;
 mod data_type;
+mod error;
 mod kv;
 mod message;
 mod meta_type;
@@ -8,7 +10,9 @@ mod payload;
 mod schema;
 
 pub use array_row::*;
+pub use block_metadata::*;
 pub use data_type::*;
+pub use error::*;
 pub use kv::*;
 pub use message::*;
 pub use meta_type::*;
@@ -1,7 +1,7 @@
-use getset::Getters;
-use std::{collections::HashMap, fmt::Debug, sync::Arc};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Debug, sync::Arc};
 
-use super::{MetaCell, Schema, SCHEMA_META_NAME};
+use super::{BlockMetadata, Schema, StreamError};
 
 /// Either actual data (`DataBlock`) or other special signals (e.g., EOF, Signal).
 ///
@@ -13,6 +13,9 @@ pub enum Payload<T> {
     EOF,
     Some(DataBlock<T>),
     Signal(Signal),
+
+    /// Ends the stream in place of EOF when a node fails. See [StreamError].
+    Error(StreamError),
 }
 
 impl<T> Payload<T> {
@@ -24,6 +27,7 @@ impl<T> Payload<T> {
         match self {
             Self::EOF => panic!(),
             Self::Signal(_) => panic!(),
+            Self::Error(_) => panic!(),
             Self::Some(dblock) => dblock,
         }
     }
@@ -35,6 +39,7 @@ impl<T> Clone for Payload<T> {
             Self::EOF => Self::EOF,
             Self::Some(records) => Self::Some((*records).clone()),
             Self::Signal(s) => Self::Signal(s.clone()),
+            Self::Error(e) => Self::Error(e.clone()),
         }
     }
 }
@@ -45,19 +50,30 @@ impl<T> Debug for Payload<T> {
             Self::EOF => write!(f, "EOF"),
             Self::Some(dblock) => f.debug_tuple("Data").field(dblock).finish(),
             Self::Signal(s) => f.debug_tuple("Signal").field(s).finish(),
+            Self::Error(e) => f.debug_tuple("Error").field(e).finish(),
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub enum Signal {
     STOP,
+    /// Asks source readers to stop emitting partitions until [Signal::RESUME].
+    PAUSE,
+    RESUME,
+    /// A checkpoint barrier, sent by [crate::graph::ExecutionService::checkpoint]. A node
+    /// saves its state as the barrier arrives on each of its inputs, and passes the barrier
+    /// on once it has arrived on all of them.
+    CHECKPOINT,
 }
 
 impl Clone for Signal {
     fn clone(&self) -> Self {
         match self {
             Self::STOP => Self::STOP,
+            Self::PAUSE => Self::PAUSE,
+            Self::RESUME => Self::RESUME,
+            Self::CHECKPOINT => Self::CHECKPOINT,
         }
     }
 }
@@ -65,13 +81,11 @@ impl Clone for Signal {
 /// Data and metadata
 ///
 /// Introduced to store the index for the primary key. It is efficient to clone DataBlock<T>
-/// since the `data` field contains `Arc`.
-#[derive(Getters, PartialEq)]
+/// since both the `data` and `metadata` fields are `Arc`s.
+#[derive(PartialEq)]
 pub struct DataBlock<T> {
     data: Arc<T>,
-
-    #[getset(get = "pub")]
-    metadata: HashMap<String, MetaCell>,
+    metadata: Arc<BlockMetadata>,
 }
 
 impl<T> Clone for DataBlock<T> {
@@ -85,10 +99,10 @@ impl<T> Clone for DataBlock<T> {
 
 impl<T> DataBlock<T> {
     /// Public constructor.
-    pub fn new(data: T, metadata: HashMap<String, MetaCell>) -> Self {
+    pub fn new(data: T, metadata: BlockMetadata) -> Self {
         DataBlock {
             data: Arc::new(data),
-            metadata,
+            metadata: Arc::new(metadata),
         }
     }
 
@@ -96,14 +110,18 @@ impl<T> DataBlock<T> {
         self.data.as_ref()
     }
 
+    pub fn metadata(&self) -> &BlockMetadata {
+        self.metadata.as_ref()
+    }
+
     pub fn schema(&self) -> &Schema {
-        self.metadata().get(SCHEMA_META_NAME).unwrap().to_schema()
+        self.metadata.schema().expect("The block carries no schema")
     }
 }
 
 impl<T> From<T> for DataBlock<T> {
     fn from(data: T) -> Self {
-        Self::new(data, HashMap::new())
+        Self::new(data, BlockMetadata::new())
     }
 }
 
@@ -115,43 +133,37 @@ impl<T> Debug for DataBlock<T> {
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
-
     use super::*;
-    use crate::data::schema::Schema;
+    use crate::data::{schema::Schema, MetaCell};
 
     /// Simple test of a factory method.
     #[test]
     fn datablock_new() {
         let data: Vec<i64> = vec![19241];
-        let metadata = HashMap::from([("key".into(), MetaCell::Text("value".to_string()))]);
+        let metadata = BlockMetadata::new().with_entry("key", MetaCell::Text("value".to_string()));
         let dblock = DataBlock::new(data.clone(), metadata.clone());
         assert_eq!(dblock.data.as_ref(), &data);
-        assert_eq!(dblock.metadata, metadata);
+        assert_eq!(dblock.metadata(), &metadata);
     }
 
     #[test]
     fn datablock_new_with_schema() {
         let data: Vec<i64> = vec![19241];
         let lineitem_schema = Schema::from_example("lineitem").unwrap();
-        let metadata = HashMap::from([
-            ("key".into(), MetaCell::Text("value".to_string())),
-            ("schema".into(), MetaCell::Schema(lineitem_schema.clone())),
-        ]);
+        let metadata = BlockMetadata::new()
+            .with_entry("key", MetaCell::Text("value".to_string()))
+            .with_schema(lineitem_schema.clone());
         let dblock = DataBlock::new(data.clone(), metadata.clone());
         assert_eq!(dblock.data.as_ref(), &data);
-        assert_eq!(dblock.metadata, metadata);
-        assert_eq!(
-            dblock.metadata.get("schema"),
-            Some(&MetaCell::Schema(lineitem_schema))
-        );
+        assert_eq!(dblock.metadata(), &metadata);
+        assert_eq!(dblock.schema(), &lineitem_schema);
     }
 
     /// Even if a payload is cloned, their underlying data objects are the same.
     #[test]
     fn clone_doenst_copy_data() {
         let data: Vec<i64> = vec![19241];
-        let metadata = HashMap::from([("key".into(), MetaCell::Text("value".to_string()))]);
+        let metadata = BlockMetadata::new().with_entry("key", MetaCell::Text("value".to_string()));
         let dblock = DataBlock::new(data.clone(), metadata.clone());
         let payload = Payload::new(dblock);
         let payload_clone = payload.clone();
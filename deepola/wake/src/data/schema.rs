@@ -1,9 +1,11 @@
 use crate::data::data_type::DataType;
+use polars::prelude::Schema as PolarsSchema;
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::error::Error;
 
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct Column {
     pub name: String,
     pub dtype: DataType,
@@ -25,7 +27,9 @@ impl Column {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// Serialized without its indexes, which are rebuilt by [Schema::new].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "SerializedSchema", into = "SerializedSchema")]
 pub struct Schema {
     pub table: String,
     pub columns: Vec<Column>,
@@ -33,6 +37,27 @@ pub struct Schema {
     _key_index: Vec<usize>,
 }
 
+#[derive(Serialize, Deserialize)]
+struct SerializedSchema {
+    table: String,
+    columns: Vec<Column>,
+}
+
+impl From<SerializedSchema> for Schema {
+    fn from(schema: SerializedSchema) -> Self {
+        Schema::new(schema.table, schema.columns)
+    }
+}
+
+impl From<Schema> for SerializedSchema {
+    fn from(schema: Schema) -> Self {
+        SerializedSchema {
+            table: schema.table,
+            columns: schema.columns,
+        }
+    }
+}
+
 impl PartialOrd for Schema {
     fn partial_cmp(&self, _: &Schema) -> Option<Ordering> {
         panic! {"Partial Order should not be called for Schema"};
@@ -79,6 +104,23 @@ impl Schema {
         }
     }
 
+    /// Whether the schema has a column named `column`.
+    pub fn contains(&self, column: &str) -> bool {
+        self._column_index.contains_key(column)
+    }
+
+    /// Checks that the schema has all of `columns`. `role` tells what the columns are for in
+    /// the error, e.g., `HashJoinBuilder left_on`.
+    pub fn require_columns(&self, role: &str, columns: &[String]) -> Result<(), String> {
+        match columns.iter().find(|column| !self.contains(column)) {
+            Some(column) => Err(format!(
+                "{} column {} not present in input schema",
+                role, column
+            )),
+            None => Ok(()),
+        }
+    }
+
     // Get DataType for a column
     pub fn dtype(&self, column: &str) -> DataType {
         self.columns[self.index(column)].dtype.clone()
@@ -110,10 +152,9 @@ impl Schema {
                     Column::from_field("l_tax".to_string(), DataType::Float),
                     Column::from_field("l_returnflag".to_string(), DataType::Text),
                     Column::from_field("l_linestatus".to_string(), DataType::Text),
-                    // For Date type fields, implement additional datatype called Date which is stored as usize.
-                    Column::from_field("l_shipdate".to_string(), DataType::Text),
-                    Column::from_field("l_commitdate".to_string(), DataType::Text),
-                    Column::from_field("l_receiptdate".to_string(), DataType::Text),
+                    Column::from_field("l_shipdate".to_string(), DataType::Date),
+                    Column::from_field("l_commitdate".to_string(), DataType::Date),
+                    Column::from_field("l_receiptdate".to_string(), DataType::Date),
                     Column::from_field("l_shipinstruct".to_string(), DataType::Text),
                     Column::from_field("l_shipmode".to_string(), DataType::Text),
                     Column::from_field("l_comment".to_string(), DataType::Text),
@@ -126,7 +167,7 @@ impl Schema {
                     Column::from_field("o_custkey".to_string(), DataType::Integer),
                     Column::from_field("o_orderstatus".to_string(), DataType::Text),
                     Column::from_field("o_totalprice".to_string(), DataType::Float),
-                    Column::from_field("o_orderdate".to_string(), DataType::Text),
+                    Column::from_field("o_orderdate".to_string(), DataType::Date),
                     Column::from_field("o_orderpriority".to_string(), DataType::Text),
                     Column::from_field("o_clerk".to_string(), DataType::Text),
                     Column::from_field("o_shippriority".to_string(), DataType::Integer),
@@ -153,11 +194,38 @@ impl From<Vec<Column>> for Schema {
     }
 }
 
+// The table and the keys are not a part of a Polars schema.
+impl TryFrom<&Schema> for PolarsSchema {
+    type Error = Box<dyn Error>;
+
+    fn try_from(schema: &Schema) -> Result<Self, Self::Error> {
+        let mut polars_schema = PolarsSchema::new();
+        for column in &schema.columns {
+            polars_schema.with_column(column.name.clone(), (&column.dtype).try_into()?);
+        }
+        Ok(polars_schema)
+    }
+}
+
+impl TryFrom<&PolarsSchema> for Schema {
+    type Error = Box<dyn Error>;
+
+    fn try_from(schema: &PolarsSchema) -> Result<Self, Self::Error> {
+        let columns = schema
+            .iter()
+            .map(|(name, dtype)| Ok(Column::from_field(name.clone(), dtype.try_into()?)))
+            .collect::<Result<Vec<Column>, Self::Error>>()?;
+        Ok(Schema::from(columns))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::Column;
+    use super::PolarsSchema;
     use super::Schema;
     use crate::data::data_type::DataType;
+    use polars::prelude::DataType as PolarsDataType;
 
     #[test]
     fn can_create_schema_object() {
@@ -195,4 +263,35 @@ mod tests {
         );
         assert_eq!(schema.keys(), &vec![1usize, 2]);
     }
+
+    #[test]
+    fn can_convert_to_polars_schema() {
+        let schema = Schema::from_example("lineitem").unwrap();
+        let polars_schema = PolarsSchema::try_from(&schema).unwrap();
+        assert_eq!(polars_schema.len(), 16);
+        let dtypes = polars_schema.iter_dtypes().collect::<Vec<_>>();
+        assert_eq!(dtypes[0], &PolarsDataType::Int64);
+        assert_eq!(dtypes[5], &PolarsDataType::Float64);
+        assert_eq!(dtypes[8], &PolarsDataType::Utf8);
+        assert_eq!(dtypes[10], &PolarsDataType::Date);
+
+        let converted = Schema::try_from(&polars_schema).unwrap();
+        assert_eq!(converted.table, "unnamed");
+        assert_eq!(converted.columns, schema.columns);
+    }
+
+    #[test]
+    fn can_require_columns() {
+        let schema = Schema::from_example("orders").unwrap();
+        let columns = vec!["o_orderkey".to_string(), "o_custkey".to_string()];
+        assert_eq!(
+            schema.require_columns("HashJoinBuilder left_on", &columns),
+            Ok(())
+        );
+        let columns = vec!["o_orderkey".to_string(), "c_custkey".to_string()];
+        assert_eq!(
+            schema.require_columns("HashJoinBuilder left_on", &columns),
+            Err("HashJoinBuilder left_on column c_custkey not present in input schema".into())
+        );
+    }
 }
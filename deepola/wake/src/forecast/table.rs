@@ -6,7 +6,6 @@ use crate::data::ArrayRow;
 use crate::data::DataBlock;
 use crate::data::DataCell;
 use crate::data::Schema;
-use crate::data::SCHEMA_META_NAME;
 use crate::forecast::cell::ForecastSelector;
 use crate::forecast::row::RowForecast;
 use crate::forecast::TimeType;
@@ -123,11 +122,7 @@ impl SetProcessorV2<ArrayRow> for TableForecast {
         *t += 1.0;
 
         // Build output schema metadata
-        let input_schema = input_set
-            .metadata()
-            .get(SCHEMA_META_NAME)
-            .unwrap()
-            .to_schema();
+        let input_schema = input_set.schema();
         let key_indexes: Vec<usize> = self.get_key_columns()
             .iter()
             .map(|column| input_schema.index(column))
@@ -169,7 +164,7 @@ mod tests {
     use crate::data::Column;
     use crate::data::DataMessage;
     use crate::data::DataType;
-    use crate::data::MetaCell;
+    use crate::data::BlockMetadata;
     use crate::graph::NodeReader;
 
     // arrow message schema
@@ -186,8 +181,7 @@ mod tests {
 
     // generate message according to time scale t
     fn example_arrow_message(t: TimeType) -> DataMessage<ArrayRow> {
-        let metadata = MetaCell::from(example_arrow_schema())
-        .into_meta_map();
+        let metadata = BlockMetadata::from_schema(example_arrow_schema());
 
         let input_rows = vec![
             ArrayRow::from([
@@ -244,8 +238,7 @@ mod tests {
 
     // generate smaller message according to time scale t
     fn example_arrow_message_small(t: TimeType) -> DataMessage<ArrayRow> {
-        let metadata = MetaCell::from(example_arrow_schema())
-        .into_meta_map();
+        let metadata = BlockMetadata::from_schema(example_arrow_schema());
 
         let input_rows = vec![
             ArrayRow::from([
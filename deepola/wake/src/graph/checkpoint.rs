@@ -0,0 +1,538 @@
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+use crate::channel::MultiChannelBroadcaster;
+use crate::data::DataMessage;
+use crate::processor::StreamProcessor;
+
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// The state of an operator in a checkpoint: dataframes, e.g., what an accumulator has
+/// aggregated thus far, and numbers, e.g., how many files a reader has read.
+///
+/// Saved by [StreamProcessor::checkpoint] and handed back to [StreamProcessor::restore] when
+/// a query resumes from the checkpoint.
+#[derive(Debug, Clone, Default)]
+pub struct OperatorState {
+    frames: HashMap<String, DataFrame>,
+    values: HashMap<String, f64>,
+}
+
+impl OperatorState {
+    pub fn put_frame(&mut self, name: &str, df: DataFrame) {
+        self.frames.insert(name.to_string(), df);
+    }
+
+    pub fn frame(&self, name: &str) -> Option<&DataFrame> {
+        self.frames.get(name)
+    }
+
+    pub fn put_value(&mut self, name: &str, value: f64) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    pub fn value(&self, name: &str) -> Option<f64> {
+        self.values.get(name).copied()
+    }
+
+    /// Writes the frames as Parquet files into `dir`.
+    fn save(&self, dir: &Path, node_index: usize, operator: &str) -> NodeManifest {
+        let frames = self
+            .frames
+            .iter()
+            .map(|(name, df)| {
+                // Parquet cannot store a frame without columns, e.g., an empty accumulator.
+                if df.width() == 0 {
+                    return (name.clone(), None);
+                }
+                let file_name = format!("node-{}-{}.parquet", node_index, name);
+                let file = File::create(dir.join(&file_name)).unwrap();
+                ParquetWriter::new(file).finish(&mut df.clone()).unwrap();
+                (name.clone(), Some(file_name))
+            })
+            .collect();
+        NodeManifest {
+            operator: operator.to_string(),
+            finished: false,
+            frames,
+            values: self.values.clone(),
+        }
+    }
+
+    fn load(dir: &Path, manifest: &NodeManifest) -> Self {
+        let frames = manifest
+            .frames
+            .iter()
+            .map(|(name, file_name)| {
+                let df = match file_name {
+                    Some(file_name) => {
+                        let file = File::open(dir.join(file_name)).unwrap();
+                        ParquetReader::new(file).finish().unwrap()
+                    }
+                    None => DataFrame::empty(),
+                };
+                (name.clone(), df)
+            })
+            .collect();
+        OperatorState {
+            frames,
+            values: manifest.values.clone(),
+        }
+    }
+}
+
+/// What a checkpoint holds for a single node.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct NodeManifest {
+    operator: String,
+
+    /// The node had finished before the checkpoint reached it. It is not run again when the
+    /// query resumes.
+    finished: bool,
+
+    /// Names of the frames of the [OperatorState], each with the Parquet file it is saved in
+    /// (relative to the checkpoint directory), or None if the frame is empty.
+    frames: HashMap<String, Option<String>>,
+    values: HashMap<String, f64>,
+}
+
+/// The `manifest.json` of a checkpoint directory, written once every node has saved its
+/// state for the checkpoint.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct CheckpointManifest {
+    checkpoint: u64,
+
+    /// In the order the nodes were added to the service.
+    nodes: Vec<NodeManifest>,
+}
+
+/// How a node restarts when its query resumes from a checkpoint.
+#[derive(Debug, Clone)]
+pub(crate) enum RestoredNode {
+    State(OperatorState),
+    Finished,
+}
+
+/// Collects the states the nodes of a query save for each checkpoint, and commits a
+/// checkpoint by writing its manifest once it is complete.
+#[derive(Debug)]
+pub(crate) struct CheckpointCoordinator {
+    dir: PathBuf,
+
+    /// The operators of the nodes, in the order the nodes were added to the service.
+    operators: Vec<String>,
+
+    /// The checkpoint the query resumed from, or 0. The nodes number their checkpoints
+    /// from there.
+    resumed_from: u64,
+
+    /// The checkpoint committed last, or [Self::resumed_from].
+    committed: u64,
+
+    /// The states saved for the checkpoints that are not complete yet.
+    pending: BTreeMap<u64, Vec<Option<NodeManifest>>>,
+    finished: Vec<bool>,
+
+    /// Checkpoints that some node could not save its state for.
+    skipped: HashSet<u64>,
+}
+
+impl CheckpointCoordinator {
+    /// Starts checkpointing a query with `operators` into `dir`. If `dir` holds a checkpoint,
+    /// also returns how each node restarts from it.
+    ///
+    /// Panics if the checkpoint in `dir` was taken with a different plan.
+    pub(crate) fn open(dir: &Path, operators: Vec<String>) -> (Self, Option<Vec<RestoredNode>>) {
+        fs::create_dir_all(dir).unwrap();
+        let manifest = fs::read_to_string(dir.join(MANIFEST_FILE))
+            .ok()
+            .map(|json| serde_json::from_str::<CheckpointManifest>(&json).unwrap());
+        let restored = manifest.as_ref().map(|manifest| {
+            let checkpoint_operators: Vec<&String> =
+                manifest.nodes.iter().map(|node| &node.operator).collect();
+            if checkpoint_operators != operators.iter().collect::<Vec<_>>() {
+                panic!(
+                    "The checkpoint in {:?} was taken with a different plan: {:?}",
+                    dir, checkpoint_operators
+                );
+            }
+            let checkpoint_dir = dir.join(checkpoint_dir_name(manifest.checkpoint));
+            manifest
+                .nodes
+                .iter()
+                .map(|node| match node.finished {
+                    true => RestoredNode::Finished,
+                    false => RestoredNode::State(OperatorState::load(&checkpoint_dir, node)),
+                })
+                .collect()
+        });
+        let resumed_from = manifest.map_or(0, |manifest| manifest.checkpoint);
+        let coordinator = CheckpointCoordinator {
+            dir: dir.to_path_buf(),
+            finished: vec![false; operators.len()],
+            operators,
+            resumed_from,
+            committed: resumed_from,
+            pending: BTreeMap::new(),
+            skipped: HashSet::new(),
+        };
+        (coordinator, restored)
+    }
+
+    fn report(&mut self, node_index: usize, checkpoint: u64, manifest: NodeManifest) {
+        let num_nodes = self.operators.len();
+        self.pending
+            .entry(checkpoint)
+            .or_insert_with(|| vec![None; num_nodes])[node_index] = Some(manifest);
+        self.commit_complete();
+    }
+
+    fn report_skipped(&mut self, checkpoint: u64) {
+        self.skipped.insert(checkpoint);
+    }
+
+    fn report_finished(&mut self, node_index: usize) {
+        self.finished[node_index] = true;
+        self.commit_complete();
+    }
+
+    /// A checkpoint is complete once every node has either saved its state for it or
+    /// finished without the checkpoint reaching it. A node that saved its state and then
+    /// finished must restart from that state, since the nodes downstream saved theirs before
+    /// seeing the rest of its output.
+    fn commit_complete(&mut self) {
+        let complete = self.pending.iter().rev().find(|(checkpoint, nodes)| {
+            !self.skipped.contains(checkpoint)
+                && nodes
+                    .iter()
+                    .zip(self.finished.iter())
+                    .all(|(node, finished)| node.is_some() || *finished)
+        });
+        let (checkpoint, nodes) = match complete {
+            Some((checkpoint, nodes)) => (*checkpoint, nodes),
+            None => return,
+        };
+        let manifest = CheckpointManifest {
+            checkpoint,
+            nodes: nodes
+                .iter()
+                .zip(self.operators.iter())
+                .map(|(node, operator)| match node {
+                    Some(node) => node.clone(),
+                    None => NodeManifest {
+                        operator: operator.clone(),
+                        finished: true,
+                        frames: HashMap::new(),
+                        values: HashMap::new(),
+                    },
+                })
+                .collect(),
+        };
+        let manifest_path = self.dir.join(MANIFEST_FILE);
+        let temp_path = self.dir.join(format!("{}.tmp", MANIFEST_FILE));
+        fs::write(&temp_path, serde_json::to_string_pretty(&manifest).unwrap()).unwrap();
+        fs::rename(&temp_path, &manifest_path).unwrap();
+        log::info!("Committed checkpoint {} to {:?}", checkpoint, self.dir);
+
+        self.pending = self.pending.split_off(&(checkpoint + 1));
+        for older in self.committed..checkpoint {
+            let _ = fs::remove_dir_all(self.dir.join(checkpoint_dir_name(older)));
+        }
+        self.committed = checkpoint;
+    }
+}
+
+fn checkpoint_dir_name(checkpoint: u64) -> String {
+    format!("checkpoint-{}", checkpoint)
+}
+
+/// Takes the checkpoints of a single node. Set on the node's input reader, which hands it
+/// every checkpoint barrier and EOF it reads.
+pub(crate) struct BarrierTracker<T: Send> {
+    node_index: usize,
+    operator: String,
+    coordinator: Arc<Mutex<CheckpointCoordinator>>,
+
+    /// The directory of the checkpoints, and the checkpoint the query resumed from, or 0.
+    dir: PathBuf,
+    resumed_from: u64,
+    processor: Rc<RefCell<Box<dyn StreamProcessor<T>>>>,
+    output: MultiChannelBroadcaster<T>,
+
+    /// The number of barriers read from each input channel.
+    barriers: RefCell<Vec<u64>>,
+    at_eof: RefCell<Vec<bool>>,
+
+    /// The states saved for the checkpoints whose barrier has not arrived on all the input
+    /// channels yet. None if the operator could not be asked for its state.
+    pending: RefCell<BTreeMap<u64, Option<OperatorState>>>,
+}
+
+impl<T: Send> std::fmt::Debug for BarrierTracker<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BarrierTracker")
+            .field("node_index", &self.node_index)
+            .field("barriers", &self.barriers)
+            .field("at_eof", &self.at_eof)
+            .finish()
+    }
+}
+
+impl<T: Send> BarrierTracker<T> {
+    pub(crate) fn new(
+        node_index: usize,
+        operator: String,
+        coordinator: Arc<Mutex<CheckpointCoordinator>>,
+        processor: Rc<RefCell<Box<dyn StreamProcessor<T>>>>,
+        output: MultiChannelBroadcaster<T>,
+        num_inputs: usize,
+    ) -> Self {
+        let (dir, resumed_from) = {
+            let coordinator = coordinator.lock().unwrap();
+            (coordinator.dir.clone(), coordinator.resumed_from)
+        };
+        Self {
+            node_index,
+            operator,
+            coordinator,
+            dir,
+            resumed_from,
+            processor,
+            output,
+            barriers: RefCell::new(vec![0; num_inputs]),
+            at_eof: RefCell::new(vec![false; num_inputs]),
+            pending: RefCell::new(BTreeMap::new()),
+        }
+    }
+
+    /// Saves the state built from `channel` for the next checkpoint.
+    pub(crate) fn on_barrier(&self, channel: usize) {
+        let checkpoint = {
+            let mut barriers = self.barriers.borrow_mut();
+            barriers[channel] += 1;
+            barriers[channel]
+        };
+        let mut pending = self.pending.borrow_mut();
+        let state = pending
+            .entry(checkpoint)
+            .or_insert_with(|| Some(OperatorState::default()));
+        self.save_channel(channel, state);
+        drop(pending);
+        self.complete_checkpoints();
+    }
+
+    /// The state built from `channel` no longer changes, so `channel` has passed every
+    /// further checkpoint.
+    pub(crate) fn on_eof(&self, channel: usize) {
+        self.at_eof.borrow_mut()[channel] = true;
+        self.complete_checkpoints();
+    }
+
+    /// True once EOF has been read from every input channel.
+    pub(crate) fn is_finished(&self) -> bool {
+        self.at_eof.borrow().iter().all(|eof| *eof)
+    }
+
+    /// Records that the node has finished.
+    pub(crate) fn finish(&self) {
+        self.coordinator
+            .lock()
+            .unwrap()
+            .report_finished(self.node_index);
+    }
+
+    fn save_channel(&self, channel: usize, state: &mut Option<OperatorState>) {
+        // The operator is borrowed mutably while it pre-processes, e.g., a hash join building
+        // its table. It then cannot save its state, and the node skips the checkpoint.
+        match (self.processor.try_borrow(), state.as_mut()) {
+            (Ok(processor), Some(operator_state)) => processor.checkpoint(channel, operator_state),
+            _ => *state = None,
+        }
+    }
+
+    /// Completes the checkpoints whose barrier has arrived on, or whose state no longer
+    /// changes for, all input channels, and passes their barrier on.
+    fn complete_checkpoints(&self) {
+        loop {
+            let mut pending = self.pending.borrow_mut();
+            let checkpoint = match pending.keys().next() {
+                Some(checkpoint) => *checkpoint,
+                None => return,
+            };
+            let barriers = self.barriers.borrow().clone();
+            let at_eof = self.at_eof.borrow().clone();
+            if !(0..barriers.len()).all(|c| barriers[c] >= checkpoint || at_eof[c]) {
+                return;
+            }
+            let mut state = pending.remove(&checkpoint).unwrap();
+            drop(pending);
+            for channel in (0..barriers.len()).filter(|c| barriers[*c] < checkpoint) {
+                self.save_channel(channel, &mut state);
+            }
+            let checkpoint = self.resumed_from + checkpoint;
+            match state {
+                Some(state) => {
+                    let dir = self.dir.join(checkpoint_dir_name(checkpoint));
+                    fs::create_dir_all(&dir).unwrap();
+                    let manifest = state.save(&dir, self.node_index, &self.operator);
+                    self.coordinator
+                        .lock()
+                        .unwrap()
+                        .report(self.node_index, checkpoint, manifest);
+                }
+                None => {
+                    log::warn!(
+                        "Node {} ({}) skips checkpoint {}",
+                        self.node_index,
+                        self.operator,
+                        checkpoint
+                    );
+                    self.coordinator.lock().unwrap().report_skipped(checkpoint);
+                }
+            }
+            self.output.write(DataMessage::checkpoint());
+        }
+    }
+}
+
+/// Given to each node by the service when the query takes checkpoints.
+pub(crate) struct NodeCheckpointing {
+    /// The position of the node in the service.
+    pub(crate) node_index: usize,
+    pub(crate) coordinator: Arc<Mutex<CheckpointCoordinator>>,
+
+    /// Set if the query resumes from a checkpoint.
+    pub(crate) restored: Option<RestoredNode>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::data::Payload;
+    use crate::graph::{ExecutionNode, ExecutionService, NodeReader};
+    use crate::processor::MessageProcessor;
+
+    /// Passes numbers on, skipping the ones it passed before the checkpoint it resumes from,
+    /// as a reader skips files.
+    #[derive(Default)]
+    struct Source {
+        passed: Cell<f64>,
+        to_skip: Cell<f64>,
+    }
+
+    impl MessageProcessor<String> for Source {
+        fn process_msg(&self, input: &String) -> Option<String> {
+            if self.to_skip.get() > 0.0 {
+                self.to_skip.set(self.to_skip.get() - 1.0);
+                return None;
+            }
+            self.passed.set(self.passed.get() + 1.0);
+            Some(input.clone())
+        }
+
+        fn checkpoint_msg(&self, state: &mut OperatorState) {
+            state.put_value("passed", self.passed.get());
+        }
+
+        fn restore_msg(&mut self, state: &OperatorState) {
+            self.passed.set(state.value("passed").unwrap());
+            self.to_skip.set(state.value("passed").unwrap());
+        }
+    }
+
+    /// Sums up the numbers thus far.
+    #[derive(Default)]
+    struct Sum {
+        sum: Cell<f64>,
+    }
+
+    impl MessageProcessor<String> for Sum {
+        fn process_msg(&self, input: &String) -> Option<String> {
+            self.sum.set(self.sum.get() + input.parse::<f64>().unwrap());
+            Some(self.sum.get().to_string())
+        }
+
+        fn checkpoint_msg(&self, state: &mut OperatorState) {
+            state.put_value("sum", self.sum.get());
+        }
+
+        fn restore_msg(&mut self, state: &OperatorState) {
+            self.sum.set(state.value("sum").unwrap());
+        }
+    }
+
+    fn numbers(dir: &Path, inputs: &[&str]) -> (ExecutionService<String>, NodeReader<String>) {
+        let source = ExecutionNode::<String>::new(Box::new(Source::default()), 1);
+        let sum = ExecutionNode::<String>::new(Box::new(Sum::default()), 1);
+        sum.subscribe_to_node(&source, 0);
+        let reader_node = NodeReader::new(&sum);
+        for input in inputs {
+            source.write_to_self(0, DataMessage::from(input.to_string()));
+        }
+        let mut service = ExecutionService::create();
+        service.set_checkpoint_dir(Some(dir.to_path_buf()));
+        service.add(source);
+        service.add(sum);
+        service.add_reader(&reader_node);
+        (service, reader_node)
+    }
+
+    fn results(reader_node: &NodeReader<String>) -> Vec<String> {
+        let mut results = vec![];
+        while let Some(message) = reader_node.try_read() {
+            if let Payload::Some(dblock) = message.payload() {
+                results.push(dblock.data().clone());
+            }
+        }
+        results
+    }
+
+    #[test]
+    fn resumes_from_committed_checkpoint() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+
+        // Interrupted after the checkpoint taken between "2" and "3".
+        let (mut service, reader_node) = numbers(dir, &["1", "2"]);
+        let source_writer = service.nodes()[0].self_writer(0);
+        let mut executor = service.run_stepping();
+        executor.run_until_idle();
+        service.checkpoint();
+        source_writer.write(DataMessage::from("3".to_string()));
+        executor.run_until_idle();
+        assert_eq!(results(&reader_node), vec!["1", "3", "6"]);
+        assert!(dir.join(MANIFEST_FILE).exists());
+        assert!(dir.join(checkpoint_dir_name(1)).exists());
+
+        // The source replays its input from the start; what was read before the checkpoint
+        // is skipped, and the sum picks up from 3.
+        let (mut service, reader_node) = numbers(dir, &["1", "2", "3", "4"]);
+        let source_writer = service.nodes()[0].self_writer(0);
+        let executor = service.run_stepping();
+        source_writer.write(DataMessage::eof());
+        executor.finish().unwrap();
+        assert_eq!(results(&reader_node), vec!["6", "10"]);
+        assert!(reader_node.try_read().is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "different plan")]
+    fn rejects_checkpoint_of_another_plan() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = CheckpointManifest {
+            checkpoint: 1,
+            nodes: vec![],
+        };
+        let file = File::create(dir.path().join(MANIFEST_FILE)).unwrap();
+        serde_json::to_writer(file, &manifest).unwrap();
+        CheckpointCoordinator::open(dir.path(), vec!["Sum".to_string()]);
+    }
+}
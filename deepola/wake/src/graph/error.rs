@@ -0,0 +1,99 @@
+use getset::Getters;
+use std::fmt;
+
+/// A node of a query failed while running. Returned by [super::ExecutionService::join].
+#[derive(Debug, Clone, PartialEq, Getters)]
+pub struct ExecutionError {
+    #[getset(get = "pub")]
+    node_id: String,
+
+    #[getset(get = "pub")]
+    message: String,
+}
+
+impl ExecutionError {
+    pub fn new(node_id: &str, message: &str) -> Self {
+        Self {
+            node_id: node_id.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Node [{}] failed: {}", self.node_id, self.message)
+    }
+}
+
+impl std::error::Error for ExecutionError {}
+
+/// A mistake in how the nodes of a query are connected, or in the columns they read. Each of
+/// them makes the query wait forever or fail midway, so they are checked before the query
+/// runs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlanProblem {
+    /// The node reads from other nodes, but nothing writes to this input channel.
+    UnconnectedInput { node_id: String, channel: usize },
+
+    /// The node is subscribed to a node that is not added to the service.
+    UnknownSource { node_id: String, source_id: String },
+
+    /// The nodes wait on each other, in this order.
+    Cycle { node_ids: Vec<String> },
+
+    /// A [super::NodeReader] is subscribed to a node that is not added to the service.
+    UnknownReaderSource { source_id: String },
+
+    /// The schema of an input of the node lacks a column the node reads, as described.
+    SchemaMismatch { node_id: String, message: String },
+}
+
+impl fmt::Display for PlanProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnconnectedInput { node_id, channel } => {
+                write!(f, "Node [{}] has no input for channel {}", node_id, channel)
+            }
+            Self::UnknownSource { node_id, source_id } => write!(
+                f,
+                "Node [{}] is subscribed to node [{}], which is not in the service",
+                node_id, source_id
+            ),
+            Self::Cycle { node_ids } => write!(f, "Nodes form a cycle: [{}]", node_ids.join(" -> ")),
+            Self::UnknownReaderSource { source_id } => write!(
+                f,
+                "A NodeReader is subscribed to node [{}], which is not in the service",
+                source_id
+            ),
+            Self::SchemaMismatch { node_id, message } => {
+                write!(f, "Node [{}]: {}", node_id, message)
+            }
+        }
+    }
+}
+
+/// Returned by [super::ExecutionService::validate] and [super::ExecutionService::try_run].
+#[derive(Debug, Clone, PartialEq, Getters)]
+pub struct ValidationError {
+    #[getset(get = "pub")]
+    problems: Vec<PlanProblem>,
+}
+
+impl ValidationError {
+    pub fn new(problems: Vec<PlanProblem>) -> Self {
+        Self { problems }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid query plan:")?;
+        for problem in self.problems.iter() {
+            write!(f, "\n  {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationError {}
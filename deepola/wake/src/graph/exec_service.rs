@@ -1,63 +1,287 @@
-use getset::Getters;
+use getset::{Getters, Setters};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
 
-use crate::utils::log_node_mapping;
+use crate::channel::ChannelCapacity;
 
-use super::node::ExecutionNode;
+use super::checkpoint::{CheckpointCoordinator, NodeCheckpointing};
+use super::error::{ExecutionError, ValidationError};
+use super::handle::QueryHandle;
+use super::metrics::MetricsRegistry;
+use super::node::{ExecutionNode, NodeReader};
+use super::plan::ExecutionGraph;
+use super::progress::{ProgressTracker, QueryProgress};
+use super::result_stream::ResultStream;
+use super::scheduler::WorkerPool;
+use super::stepper::SteppingExecutor;
+use super::validation::validate_plan;
 
-#[derive(Getters)]
+#[derive(Getters, Setters)]
 pub struct ExecutionService<T: Send> {
     #[getset(get = "pub")]
     nodes: Vec<ExecutionNode<T>>,
 
-    thread_handles: Vec<JoinHandle<ExecutionNode<T>>>,
+    /// The number of worker threads the nodes are scheduled on. Defaults to the available
+    /// parallelism of the machine.
+    #[getset(get = "pub", set = "pub")]
+    num_threads: usize,
+
+    /// The capacity of the input channels between nodes that are not bounded specifically,
+    /// e.g., with [ExecutionNode::subscribe_to_node_with_capacity]. Applied when the service
+    /// runs. The input channels of source nodes stay unbounded.
+    #[getset(get = "pub", set = "pub")]
+    channel_capacity: Option<ChannelCapacity>,
+
+    /// The directory [Self::checkpoint] saves checkpoints to. If it already holds one when
+    /// the service runs, the query resumes from there: the operators restore their state and
+    /// the readers skip the files they had read. The query must then be built with the same
+    /// plan, i.e., the same operators added in the same order, and the same input.
+    #[getset(get = "pub", set = "pub")]
+    checkpoint_dir: Option<PathBuf>,
+
+    /// Ids of the nodes that the [NodeReader]s added with [Self::add_reader] read from.
+    reader_sources: Vec<String>,
+
+    pool: Option<WorkerPool>,
+
+    /// Signals the source nodes of the running query.
+    handle: Option<QueryHandle<T>>,
+
+    /// The metrics of every registered node, kept after the nodes are moved to the pool.
+    #[getset(get = "pub")]
+    metrics: MetricsRegistry,
+
+    /// The graph of the nodes moved to the worker pool by [Self::run].
+    running_graph: Option<ExecutionGraph>,
+
+    /// Shared with the readers added with [Self::add_reader].
+    progress: ProgressTracker,
+
+    /// Move the output of the nodes of [Self::add_result_stream] into their streams.
+    result_pumps: Vec<Box<dyn FnOnce() + Send>>,
+    pump_threads: Vec<JoinHandle<()>>,
 }
 
 impl<T: Send + 'static> ExecutionService<T> {
     /// Register a node to execute. Note that the registered node is
     /// **owned** by this service now.
     pub fn add(&mut self, node: ExecutionNode<T>) {
+        self.metrics
+            .register(node.node_id(), &node.operator_name(), node.metrics());
         self.nodes.push(node);
     }
 
-    fn aseert_empty_thread_handles(&self) {
-        if !self.thread_handles.is_empty() {
-            panic!("There are {} thread handles.", self.thread_handles.len());
+    /// Lets [Self::validate] check that `reader` reads from a node of this service, and
+    /// makes the nodes it reads from the output of [Self::progress]. The reader itself stays
+    /// with the caller; see [NodeReader::read_with_progress].
+    pub fn add_reader(&mut self, reader: &NodeReader<T>) {
+        self.reader_sources
+            .extend(reader.subscriptions().into_iter().map(|(source_id, _)| source_id));
+        reader.track_progress(self.progress.clone());
+    }
+
+    /// Publishes what `output_node` writes to a [ResultStream], which any number of consumers
+    /// can subscribe to. [Self::run] starts a thread that moves the snapshots into the stream
+    /// as soon as they are written; [Self::join] waits for it.
+    pub fn add_result_stream(&mut self, output_node: &ExecutionNode<T>) -> ResultStream<T>
+    where
+        T: Clone + Sync,
+    {
+        let reader = NodeReader::new(output_node);
+        self.add_reader(&reader);
+        let stream = ResultStream::new(reader);
+        let pumped_stream = stream.clone();
+        self.result_pumps
+            .push(Box::new(move || pumped_stream.pump()));
+        stream
+    }
+
+    /// Checks that every input channel of a node is connected, that nodes and readers only
+    /// read from nodes of this service, and that there is no cycle. Then type-checks the plan:
+    /// the schema of every node is derived from the schemas of its inputs (see
+    /// [ExecutionNode::output_schema]), starting from the schemas declared by source nodes,
+    /// and every node must find the columns it reads there. Called by [Self::run].
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate_plan(&self.nodes, &self.reader_sources)
+    }
+
+    fn assert_not_running(&self) {
+        if self.handle.is_some() {
+            panic!("The service is already running.");
+        }
+    }
+
+    /// Starts all the registered nodes as tasks on a pool of [Self::num_threads] workers.
+    ///
+    /// The returned handle can pause, resume, or cancel the query while it runs.
+    ///
+    /// Panics if the nodes are not connected correctly; see [Self::validate] and
+    /// [Self::try_run].
+    pub fn run(&mut self) -> QueryHandle<T> {
+        match self.try_run() {
+            Ok(handle) => handle,
+            Err(error) => panic!("{}", error),
+        }
+    }
+
+    /// Same as [Self::run], but returns the problems with the plan if the nodes are not
+    /// connected correctly, instead of panicking. The nodes then stay with the service, which
+    /// does not run.
+    pub fn try_run(&mut self) -> Result<QueryHandle<T>, ValidationError> {
+        let (nodes, handle) = self.start()?;
+        self.pool = Some(WorkerPool::start(nodes, self.num_threads));
+        self.pump_threads = self.result_pumps.drain(..).map(thread::spawn).collect();
+        Ok(handle)
+    }
+
+    /// Hands all the registered nodes to a [SteppingExecutor], which runs them on the calling
+    /// thread one message at a time, instead of starting them on a worker pool. Useful for
+    /// tests that check every intermediate result in a reproducible order.
+    ///
+    /// Panics if the nodes are not connected correctly; see [Self::validate].
+    pub fn run_stepping(&mut self) -> SteppingExecutor {
+        match self.start() {
+            Ok((nodes, _handle)) => SteppingExecutor::new(nodes),
+            Err(error) => panic!("{}", error),
+        }
+    }
+
+    /// Prepares the registered nodes to run and takes them out of this service, unless they
+    /// are not connected correctly.
+    fn start(&mut self) -> Result<(Vec<ExecutionNode<T>>, QueryHandle<T>), ValidationError> {
+        self.assert_not_running();
+        self.validate()?;
+
+        let source_writers = self
+            .nodes
+            .iter()
+            .filter(|node| node.is_source())
+            .flat_map(|node| node.self_writers())
+            .collect();
+        if let Some(capacity) = self.channel_capacity {
+            let subscribed_nodes = self.nodes.iter().filter(|node| !node.is_source());
+            for writer in subscribed_nodes.flat_map(|node| node.self_writers()) {
+                writer.set_default_capacity(capacity);
+            }
+        }
+        if let Some(dir) = &self.checkpoint_dir {
+            let operators = self.nodes.iter().map(|node| node.operator_name()).collect();
+            let (coordinator, restored) = CheckpointCoordinator::open(dir, operators);
+            let coordinator = Arc::new(Mutex::new(coordinator));
+            let mut restored = restored.map(|restored| restored.into_iter());
+            for (node_index, node) in self.nodes.iter().enumerate() {
+                node.set_checkpointing(NodeCheckpointing {
+                    node_index,
+                    coordinator: coordinator.clone(),
+                    restored: restored.as_mut().and_then(|restored| restored.next()),
+                });
+            }
         }
+        let handle = QueryHandle::new(source_writers);
+        self.handle = Some(handle.clone());
+        self.running_graph = Some(ExecutionGraph::from_nodes(&self.nodes));
+        self.progress.track(&self.nodes, &self.reader_sources);
+        Ok((std::mem::take(&mut self.nodes), handle))
     }
 
-    pub fn run(&mut self) {
-        self.aseert_empty_thread_handles();
+    /// The registered nodes and how they are connected. Also available while the query runs.
+    pub fn graph(&self) -> ExecutionGraph {
+        match &self.running_graph {
+            Some(graph) => graph.clone(),
+            None => ExecutionGraph::from_nodes(&self.nodes),
+        }
+    }
 
-        self.thread_handles.clear();
-        while let Some(node) = self.nodes.pop() {
-            let node_id = node.node_id().clone();
-            let handle = thread::spawn(move || {
-                node.run();
-                node
-            });
-            log_node_mapping(&node_id, handle.thread().id());
-            self.thread_handles.push(handle);
+    /// How far the query has come: the fraction each source node has read, the fraction
+    /// reflected by the output, and an estimate of the remaining time. The output nodes are
+    /// the nodes read by the readers added with [Self::add_reader], or else the nodes that no
+    /// other node reads from. None until the query starts.
+    pub fn progress(&self) -> Option<QueryProgress> {
+        self.progress.progress(None)
+    }
+
+    /// Takes a checkpoint of the running query into [Self::checkpoint_dir].
+    ///
+    /// [Signal::CHECKPOINT](crate::data::Signal::CHECKPOINT) is sent to every source node. A
+    /// reader records how many files it has read as it passes the signal on; every other node
+    /// saves its operator state (see [crate::processor::StreamProcessor::checkpoint]) as the
+    /// signal arrives on its inputs. The checkpoint is committed, i.e., its `manifest.json` is
+    /// written, once every node has saved its state or finished; earlier checkpoints are then
+    /// removed.
+    pub fn checkpoint(&self) {
+        match (&self.handle, &self.checkpoint_dir) {
+            (Some(handle), Some(_)) => handle.checkpoint(),
+            (None, _) => log::warn!("Checkpoint called on a service that is not running"),
+            (_, None) => log::warn!("Checkpoint called without a checkpoint directory"),
+        }
+    }
+
+    /// Stops a running query early, e.g., once an online-aggregation result is good enough.
+    ///
+    /// [Signal::STOP](crate::data::Signal::STOP) is sent to every source node. Readers stop
+    /// reading further files, and every operator flushes what it has accumulated and forwards
+    /// the signal downstream, so a [super::NodeReader] sees the signal after the last result.
+    /// Call [Self::join] afterwards to wait for all the nodes to finish.
+    pub fn cancel(&self) {
+        match &self.handle {
+            Some(handle) => handle.cancel(),
+            None => log::warn!("Cancel called on a service that is not running"),
         }
     }
 
-    pub fn join(&mut self) {
-        while let Some(handle) = self.thread_handles.pop() {
-            handle.join().unwrap();
+    /// Waits for all the nodes to finish.
+    ///
+    /// If a node fails (i.e., panics), the other nodes are torn down and send the failure
+    /// downstream as an error, so a [super::NodeReader] does not wait forever. The failure is
+    /// then returned here. So is an error a node writes in place of the rest of its output,
+    /// e.g., a reader that cannot open a file; see [Payload::Error](crate::data::Payload).
+    pub fn join(&mut self) -> Result<(), ExecutionError> {
+        self.handle = None;
+        let result = match self.pool.take() {
+            Some(pool) => pool.join(),
+            None => Ok(()),
+        };
+        let result = result.and_then(|()| match self.metrics.first_error() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        });
+        for pump in self.pump_threads.drain(..) {
+            pump.join().unwrap();
         }
+        result
     }
 
     pub fn create() -> Self {
         ExecutionService {
             nodes: vec![],
-            thread_handles: vec![],
+            num_threads: thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            channel_capacity: None,
+            checkpoint_dir: None,
+            reader_sources: vec![],
+            pool: None,
+            handle: None,
+            metrics: MetricsRegistry::default(),
+            running_graph: None,
+            progress: ProgressTracker::default(),
+            result_pumps: vec![],
+            pump_threads: vec![],
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{data::DataMessage, graph::node::ExecutionNode, processor::SimpleMapper};
+    use crate::{
+        channel::{ChannelCapacity, MultiChannelBroadcaster, MultiChannelReader},
+        data::{DataMessage, StreamError, StreamErrorKind},
+        graph::node::ExecutionNode,
+        graph::NodeReader,
+        processor::{SimpleMapper, StreamProcessor},
+    };
+    use std::sync::mpsc;
 
     use super::ExecutionService;
 
@@ -72,6 +296,268 @@ mod tests {
         // without this line, this test case doesn't stop, looping infinitely.
         self_writer.write(DataMessage::eof());
 
-        exec_service.join();
+        exec_service.join().unwrap();
+    }
+
+    /// A chain of many more nodes than worker threads still completes, since a node waiting on
+    /// an empty channel gives its worker back to the pool.
+    #[test]
+    fn runs_more_nodes_than_threads() {
+        let loop_count = 20;
+        let mut nodes: Vec<ExecutionNode<String>> = vec![];
+        for i in 0..loop_count {
+            let node = ExecutionNode::from(SimpleMapper::from(|r: &String| Some(r.clone() + "X")));
+            if i > 0 {
+                node.subscribe_to_node(&nodes[i - 1], 0);
+            }
+            nodes.push(node);
+        }
+        let self_writer = nodes[0].self_writer(0);
+        let reader_node = NodeReader::new(&nodes[loop_count - 1]);
+
+        let mut exec_service = ExecutionService::create();
+        exec_service.set_num_threads(2);
+        for node in nodes {
+            exec_service.add(node);
+        }
+        exec_service.run();
+        self_writer.write(DataMessage::from("".to_string()));
+        self_writer.write(DataMessage::eof());
+
+        let message = reader_node.read();
+        assert_eq!(
+            message.datablock().data(),
+            &(0..loop_count).map(|_| "X").collect::<String>()
+        );
+        assert!(reader_node.read().is_eof());
+        exec_service.join().unwrap();
+    }
+
+    #[test]
+    fn cancel_propagates_stop() {
+        let source = ExecutionNode::from(SimpleMapper::from(|r: &String| Some(r.clone() + "X")));
+        let sink = ExecutionNode::from(SimpleMapper::from(|r: &String| Some(r.clone() + "Y")));
+        sink.subscribe_to_node(&source, 0);
+        source.write_to_self(0, DataMessage::from("".to_string()));
+        let reader_node = NodeReader::new(&sink);
+
+        let mut exec_service = ExecutionService::create();
+        exec_service.add(source);
+        exec_service.add(sink);
+        exec_service.run();
+        assert_eq!(reader_node.read().datablock().data(), "XY");
+
+        // the source never sees EOF; cancel is the only way this query terminates.
+        exec_service.cancel();
+        assert!(reader_node.read().is_stop());
+        exec_service.join().unwrap();
+    }
+
+    /// A failing node ends the whole query, even though its source never sees EOF.
+    #[test]
+    fn join_reports_failing_node() {
+        let source = ExecutionNode::from(SimpleMapper::from(|r: &String| Some(r.clone())));
+        let failing = ExecutionNode::from(SimpleMapper::from(|r: &String| {
+            if r == "bad" {
+                panic!("bad input");
+            }
+            Some(r.clone())
+        }));
+        let sink = ExecutionNode::from(SimpleMapper::from(|r: &String| Some(r.clone())));
+        failing.subscribe_to_node(&source, 0);
+        sink.subscribe_to_node(&failing, 0);
+        let failing_id = failing.node_id().clone();
+        source.write_to_self(0, DataMessage::from("bad".to_string()));
+        let reader_node = NodeReader::new(&sink);
+
+        let mut exec_service = ExecutionService::create();
+        exec_service.add(source);
+        exec_service.add(failing);
+        exec_service.add(sink);
+        exec_service.run();
+
+        let stream_error = reader_node.read_result().unwrap_err();
+        assert_eq!(stream_error.node_id().as_deref(), Some(failing_id.as_str()));
+        assert_eq!(stream_error.message(), "bad input");
+        let error = exec_service.join().unwrap_err();
+        assert_eq!(error.node_id(), &failing_id);
+        assert_eq!(error.message(), "bad input");
+    }
+
+    /// Fails on its first input without panicking, as a reader does on a missing file.
+    struct RejectsInput;
+
+    impl StreamProcessor<String> for RejectsInput {
+        fn process_stream(
+            &self,
+            input_stream: MultiChannelReader<String>,
+            output_stream: MultiChannelBroadcaster<String>,
+        ) {
+            input_stream.read(0);
+            let error = StreamError::new(StreamErrorKind::InvalidData, "bad input");
+            output_stream.write(DataMessage::error(error));
+        }
+    }
+
+    #[test]
+    fn written_error_is_forwarded_to_reader() {
+        let failing = ExecutionNode::<String>::new(Box::new(RejectsInput), 1);
+        let sink = ExecutionNode::from(SimpleMapper::from(|r: &String| Some(r.clone())));
+        sink.subscribe_to_node(&failing, 0);
+        let failing_id = failing.node_id().clone();
+        failing.write_to_self(0, DataMessage::from("bad".to_string()));
+        let reader_node = NodeReader::new(&sink);
+
+        let mut exec_service = ExecutionService::create();
+        exec_service.add(failing);
+        exec_service.add(sink);
+        exec_service.run();
+
+        let stream_error = reader_node.read_result().unwrap_err();
+        assert_eq!(stream_error.node_id().as_deref(), Some(failing_id.as_str()));
+        assert_eq!(*stream_error.kind(), StreamErrorKind::InvalidData);
+        let error = exec_service.join().unwrap_err();
+        assert_eq!(error.node_id(), &failing_id);
+        assert_eq!(error.message(), "bad input");
+    }
+
+    /// Fills its single-message output channel, then fails.
+    struct FillsOutputThenPanics;
+
+    impl StreamProcessor<String> for FillsOutputThenPanics {
+        fn process_stream(
+            &self,
+            input_stream: MultiChannelReader<String>,
+            output_stream: MultiChannelBroadcaster<String>,
+        ) {
+            output_stream.write(input_stream.read(0));
+            panic!("bad input");
+        }
+    }
+
+    /// Waits until its input channel holds two messages before reading any of them. Gives
+    /// up, and says so in its output, if that does not happen in time.
+    struct WaitsForTwoMessages;
+
+    impl StreamProcessor<String> for WaitsForTwoMessages {
+        fn process_stream(
+            &self,
+            input_stream: MultiChannelReader<String>,
+            output_stream: MultiChannelBroadcaster<String>,
+        ) {
+            let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+            while input_stream.reader(0).len() < 2 {
+                if std::time::Instant::now() > deadline {
+                    output_stream.write(DataMessage::from("timed out".to_string()));
+                    return;
+                }
+                std::thread::yield_now();
+            }
+            loop {
+                let message = input_stream.read(0);
+                let done = message.is_end();
+                output_stream.write(message);
+                if done {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// A node that fails while its output channel is full still ends the channel with the
+    /// failure, instead of waiting for space.
+    #[test]
+    fn failure_reaches_full_output_channel() {
+        let failing = ExecutionNode::<String>::new(Box::new(FillsOutputThenPanics), 1);
+        let sink = ExecutionNode::<String>::new(Box::new(WaitsForTwoMessages), 1);
+        sink.subscribe_to_node(&failing, 0);
+        let failing_id = failing.node_id().clone();
+        failing.write_to_self(0, DataMessage::from("a".to_string()));
+        let reader_node = NodeReader::new(&sink);
+
+        let mut exec_service = ExecutionService::create();
+        exec_service.set_num_threads(2);
+        exec_service.set_channel_capacity(Some(ChannelCapacity::new(1)));
+        exec_service.add(failing);
+        exec_service.add(sink);
+        exec_service.run();
+
+        // The sink is torn down as soon as it reads, and passes the failure on.
+        let stream_error = reader_node.read_result().unwrap_err();
+        assert_eq!(stream_error.node_id().as_deref(), Some(failing_id.as_str()));
+        assert_eq!(stream_error.message(), "bad input");
+        let error = exec_service.join().unwrap_err();
+        assert_eq!(error.node_id(), &failing_id);
+    }
+
+    /// Reads a message only when the test allows it, and passes it on.
+    struct ReadsWhenAllowed {
+        allowed: mpsc::Receiver<()>,
+    }
+
+    impl StreamProcessor<String> for ReadsWhenAllowed {
+        fn process_stream(
+            &self,
+            input_stream: MultiChannelReader<String>,
+            output_stream: MultiChannelBroadcaster<String>,
+        ) {
+            while self.allowed.recv().is_ok() {
+                let message = input_stream.read(0);
+                let done = message.is_end();
+                output_stream.write(message);
+                if done {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// With single-message channels, the source waits for the slower nodes downstream instead
+    /// of buffering its whole input: it reads at most two messages ahead of the sink, one in
+    /// the channel to the sink and one it waits to write.
+    #[test]
+    fn bounded_channels_apply_backpressure() {
+        let source = ExecutionNode::from(SimpleMapper::from(|r: &String| Some(r.clone())));
+        let (allow, allowed) = mpsc::channel();
+        let sink = ExecutionNode::<String>::new(Box::new(ReadsWhenAllowed { allowed }), 1);
+        sink.subscribe_to_node(&source, 0);
+        let (source_id, sink_id) = (source.node_id().clone(), sink.node_id().clone());
+        let reader_node = NodeReader::new(&sink);
+        let message_count = 50;
+        for i in 0..message_count {
+            source.write_to_self(0, DataMessage::from(i.to_string()));
+        }
+        source.write_to_self(0, DataMessage::eof());
+
+        let mut exec_service = ExecutionService::create();
+        exec_service.set_num_threads(2);
+        exec_service.set_channel_capacity(Some(ChannelCapacity::new(1)));
+        exec_service.add(source);
+        exec_service.add(sink);
+        exec_service.run();
+        let source_reads = |exec_service: &ExecutionService<String>| {
+            *exec_service
+                .metrics()
+                .node(&source_id)
+                .unwrap()
+                .messages_in() as usize
+        };
+        for read in 0..message_count {
+            // Wait for the source to fill the channel, then check that it stops there.
+            let ahead = usize::min(read + 2, message_count);
+            let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+            while source_reads(&exec_service) < ahead && std::time::Instant::now() < deadline {
+                std::thread::yield_now();
+            }
+            assert_eq!(source_reads(&exec_service), ahead);
+            allow.send(()).unwrap();
+            assert_eq!(reader_node.read().datablock().data(), &read.to_string());
+        }
+        allow.send(()).unwrap();
+        assert!(reader_node.read().is_eof());
+        exec_service.join().unwrap();
+
+        let sink_metrics = exec_service.metrics().node(&sink_id).unwrap();
+        assert_eq!(*sink_metrics.peak_input_queue_depth(), 1);
     }
 }
@@ -0,0 +1,53 @@
+use crate::channel::ChannelWriter;
+use crate::data::DataMessage;
+
+/// Controls a running query. Returned by [super::ExecutionService::run].
+///
+/// The handle sends signals to the source nodes of the query. Pausing only holds back the
+/// source readers; the other nodes keep their state (e.g., what an accumulator has aggregated
+/// thus far) and simply wait for more input. Signals sent after the query has finished are
+/// ignored.
+pub struct QueryHandle<T: Send> {
+    source_writers: Vec<ChannelWriter<T>>,
+}
+
+impl<T: Send> Clone for QueryHandle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            source_writers: self.source_writers.clone(),
+        }
+    }
+}
+
+impl<T: Send> QueryHandle<T> {
+    pub(crate) fn new(source_writers: Vec<ChannelWriter<T>>) -> Self {
+        Self { source_writers }
+    }
+
+    /// Source readers stop emitting partitions until [Self::resume] is called.
+    pub fn pause(&self) {
+        self.signal_sources(DataMessage::pause());
+    }
+
+    pub fn resume(&self) {
+        self.signal_sources(DataMessage::resume());
+    }
+
+    /// See [super::ExecutionService::checkpoint].
+    pub fn checkpoint(&self) {
+        self.signal_sources(DataMessage::checkpoint());
+    }
+
+    /// See [super::ExecutionService::cancel].
+    pub fn cancel(&self) {
+        self.signal_sources(DataMessage::stop());
+    }
+
+    fn signal_sources(&self, message: DataMessage<T>) {
+        for writer in self.source_writers.iter() {
+            if !writer.write_if_open(message.clone()) {
+                log::debug!("Source channel {} is already closed", writer.channel_id());
+            }
+        }
+    }
+}
@@ -0,0 +1,313 @@
+use getset::Getters;
+use polars::prelude::DataFrame;
+use serde::Serialize;
+use std::any::Any;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::ExecutionError;
+use crate::data::{BlockMetadata, DataMessage, Payload, StreamError};
+
+/// Runtime counters of a single [super::ExecutionNode]. Updated by the node's input and
+/// output channels while it runs; see [NodeMetricsSnapshot] for what each counter means.
+#[derive(Debug, Default)]
+pub struct NodeMetrics {
+    messages_in: AtomicU64,
+    rows_in: AtomicU64,
+    messages_out: AtomicU64,
+    rows_out: AtomicU64,
+    read_blocked_ns: AtomicU64,
+    write_blocked_ns: AtomicU64,
+    peak_input_queue_depth: AtomicUsize,
+
+    /// Bits of the f64 [BlockMetadata::progress] of the last block written, or 1.0 once EOF
+    /// is written.
+    cardinality: AtomicU64,
+
+    /// The table in the schema of the blocks written, if any.
+    table: Mutex<Option<String>>,
+
+    /// The error the node wrote, which ended its output.
+    error: Mutex<Option<StreamError>>,
+    started: Mutex<Option<Instant>>,
+    finished: Mutex<Option<Instant>>,
+}
+
+impl NodeMetrics {
+    pub(crate) fn start(&self) {
+        self.started.lock().unwrap().get_or_insert_with(Instant::now);
+    }
+
+    pub(crate) fn finish(&self) {
+        self.finished.lock().unwrap().get_or_insert_with(Instant::now);
+    }
+
+    fn record_read(&self, rows: Option<usize>, queue_depth: usize, blocked: Duration) {
+        if let Some(rows) = rows {
+            self.messages_in.fetch_add(1, Ordering::Relaxed);
+            self.rows_in.fetch_add(rows as u64, Ordering::Relaxed);
+        }
+        self.read_blocked_ns
+            .fetch_add(blocked.as_nanos() as u64, Ordering::Relaxed);
+        self.peak_input_queue_depth
+            .fetch_max(queue_depth, Ordering::Relaxed);
+    }
+
+    fn record_cardinality(&self, metadata: &BlockMetadata) {
+        if let Some(cardinality) = metadata.progress() {
+            self.set_cardinality(cardinality);
+        }
+        if let Some(schema) = metadata.schema() {
+            let mut table = self.table.lock().unwrap();
+            if table.is_none() && !schema.table.is_empty() {
+                *table = Some(schema.table.clone());
+            }
+        }
+    }
+
+    fn set_cardinality(&self, cardinality: f64) {
+        self.cardinality
+            .store(cardinality.to_bits(), Ordering::Relaxed);
+    }
+
+    /// The fraction of its input the node has processed, as reported by the last block it
+    /// wrote. See [BlockMetadata::progress].
+    pub fn cardinality(&self) -> f64 {
+        f64::from_bits(self.cardinality.load(Ordering::Relaxed))
+    }
+
+    /// The table of the blocks the node writes, e.g., the table a reader node scans.
+    pub fn table(&self) -> Option<String> {
+        self.table.lock().unwrap().clone()
+    }
+
+    fn record_error(&self, error: &StreamError) {
+        self.error.lock().unwrap().get_or_insert_with(|| error.clone());
+    }
+
+    /// The error the node wrote in place of the rest of its output, if any. It may have
+    /// originated from an upstream node.
+    pub fn error(&self) -> Option<StreamError> {
+        self.error.lock().unwrap().clone()
+    }
+
+    fn record_write(&self, rows: Option<usize>, blocked: Duration) {
+        if let Some(rows) = rows {
+            self.messages_out.fetch_add(1, Ordering::Relaxed);
+            self.rows_out.fetch_add(rows as u64, Ordering::Relaxed);
+        }
+        self.write_blocked_ns
+            .fetch_add(blocked.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self, node_id: &str, operator: &str) -> NodeMetricsSnapshot {
+        let read_blocked_ns = self.read_blocked_ns.load(Ordering::Relaxed);
+        let write_blocked_ns = self.write_blocked_ns.load(Ordering::Relaxed);
+        let elapsed = match *self.started.lock().unwrap() {
+            Some(started) => match *self.finished.lock().unwrap() {
+                Some(finished) => finished - started,
+                None => started.elapsed(),
+            },
+            None => Duration::ZERO,
+        };
+        NodeMetricsSnapshot {
+            node_id: node_id.to_string(),
+            operator: operator.to_string(),
+            messages_in: self.messages_in.load(Ordering::Relaxed),
+            rows_in: self.rows_in.load(Ordering::Relaxed),
+            messages_out: self.messages_out.load(Ordering::Relaxed),
+            rows_out: self.rows_out.load(Ordering::Relaxed),
+            processing_time_ns: (elapsed.as_nanos() as u64)
+                .saturating_sub(read_blocked_ns + write_blocked_ns),
+            read_blocked_ns,
+            write_blocked_ns,
+            peak_input_queue_depth: self.peak_input_queue_depth.load(Ordering::Relaxed),
+            cardinality: self.cardinality(),
+            error: self.error(),
+        }
+    }
+}
+
+/// The metrics of a node at some point in time.
+///
+/// Only data messages are counted as messages; EOF and signals are not. A message of a
+/// [DataFrame] counts its rows, and a message of any other type counts as a single row.
+#[derive(Debug, Clone, PartialEq, Getters, Serialize)]
+#[getset(get = "pub")]
+pub struct NodeMetricsSnapshot {
+    node_id: String,
+    operator: String,
+    messages_in: u64,
+    rows_in: u64,
+    messages_out: u64,
+    rows_out: u64,
+
+    /// Time the node has been running, excluding the time it was blocked on its channels.
+    processing_time_ns: u64,
+
+    /// Time spent waiting for input messages.
+    read_blocked_ns: u64,
+
+    /// Time spent writing output messages, e.g., while a downstream channel is full.
+    write_blocked_ns: u64,
+
+    /// The largest number of messages seen waiting in an input channel.
+    peak_input_queue_depth: usize,
+
+    /// The fraction of its input the node has processed; see [NodeMetrics::cardinality].
+    cardinality: f64,
+
+    /// See [NodeMetrics::error].
+    error: Option<StreamError>,
+}
+
+/// The metrics of all the nodes registered to an [super::ExecutionService]. The nodes keep
+/// updating it while the query runs.
+#[derive(Debug, Clone, Default)]
+pub struct MetricsRegistry {
+    /// (node id, operator, metrics)
+    nodes: Vec<(String, String, Arc<NodeMetrics>)>,
+}
+
+impl MetricsRegistry {
+    pub(crate) fn register(&mut self, node_id: &str, operator: &str, metrics: Arc<NodeMetrics>) {
+        self.nodes
+            .push((node_id.to_string(), operator.to_string(), metrics));
+    }
+
+    pub fn snapshot(&self) -> Vec<NodeMetricsSnapshot> {
+        self.nodes
+            .iter()
+            .map(|(node_id, operator, metrics)| metrics.snapshot(node_id, operator))
+            .collect()
+    }
+
+    pub fn node(&self, node_id: &str) -> Option<NodeMetricsSnapshot> {
+        self.nodes
+            .iter()
+            .find(|(id, _, _)| id == node_id)
+            .map(|(node_id, operator, metrics)| metrics.snapshot(node_id, operator))
+    }
+
+    /// The first error written by any of the nodes, with the node it originated from.
+    pub(crate) fn first_error(&self) -> Option<ExecutionError> {
+        self.nodes.iter().find_map(|(node_id, _, metrics)| {
+            let error = metrics.error()?;
+            let origin = error.node_id().as_deref().unwrap_or(node_id);
+            Some(ExecutionError::new(origin, error.message()))
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.snapshot()).unwrap()
+    }
+}
+
+/// Records the messages going through the channels of a node into its [NodeMetrics].
+pub(crate) struct MetricsProbe<T> {
+    metrics: Arc<NodeMetrics>,
+    count_rows: fn(&T) -> usize,
+}
+
+impl<T> Clone for MetricsProbe<T> {
+    fn clone(&self) -> Self {
+        Self {
+            metrics: self.metrics.clone(),
+            count_rows: self.count_rows,
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for MetricsProbe<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MetricsProbe")
+            .field("metrics", &self.metrics)
+            .finish()
+    }
+}
+
+impl<T: 'static> MetricsProbe<T> {
+    pub(crate) fn new(metrics: Arc<NodeMetrics>) -> Self {
+        Self {
+            metrics,
+            count_rows: count_rows::<T>,
+        }
+    }
+}
+
+impl<T> MetricsProbe<T> {
+    fn rows(&self, message: &DataMessage<T>) -> Option<usize> {
+        match message.payload() {
+            Payload::Some(dblock) => Some((self.count_rows)(dblock.data())),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn on_read(&self, message: &DataMessage<T>, queue_depth: usize, blocked: Duration) {
+        self.metrics
+            .record_read(self.rows(message), queue_depth, blocked);
+    }
+
+    pub(crate) fn on_write(&self, message: &DataMessage<T>, blocked: Duration) {
+        self.metrics.record_write(self.rows(message), blocked);
+        match message.payload() {
+            Payload::Some(dblock) => self.metrics.record_cardinality(dblock.metadata()),
+            Payload::EOF => self.metrics.set_cardinality(1.0),
+            Payload::Error(error) => self.metrics.record_error(&error),
+            Payload::Signal(_) => (),
+        }
+    }
+}
+
+fn count_rows<T: 'static>(data: &T) -> usize {
+    match (data as &dyn Any).downcast_ref::<DataFrame>() {
+        Some(df) => df.height(),
+        None => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{ExecutionNode, ExecutionService, NodeReader};
+    use crate::processor::SimpleMapper;
+    use polars::prelude::{NamedFrom, Series};
+
+    #[test]
+    fn counts_messages_in_and_out() {
+        let node = ExecutionNode::from(SimpleMapper::from(|r: &String| {
+            if r.is_empty() {
+                None
+            } else {
+                Some(r.clone())
+            }
+        }));
+        let node_id = node.node_id().clone();
+        let reader_node = NodeReader::new(&node);
+        node.write_to_self(0, DataMessage::from("a".to_string()));
+        node.write_to_self(0, DataMessage::from("".to_string()));
+        node.write_to_self(0, DataMessage::from("b".to_string()));
+        node.write_to_self(0, DataMessage::eof());
+
+        let mut service = ExecutionService::create();
+        service.add(node);
+        service.run();
+        while !reader_node.read().is_eof() {}
+        service.join().unwrap();
+
+        let metrics = service.metrics().node(&node_id).unwrap();
+        assert_eq!(metrics.operator(), "SimpleMapper");
+        assert_eq!(*metrics.messages_in(), 3);
+        assert_eq!(*metrics.rows_in(), 3);
+        assert_eq!(*metrics.messages_out(), 2);
+        assert_eq!(*metrics.peak_input_queue_depth(), 4);
+    }
+
+    #[test]
+    fn frames_count_rows() {
+        let df = polars::df!("a" => [1, 2, 3]).unwrap();
+        assert_eq!(count_rows(&df), 3);
+        assert_eq!(count_rows(&"x".to_string()), 1);
+    }
+}
@@ -1,7 +1,30 @@
+mod checkpoint;
+mod error;
 mod exec_service;
+mod handle;
+mod metrics;
 mod node;
 /// Asynchronous Execution Graph
 mod node_base;
+mod plan;
+mod progress;
+mod result_stream;
+mod scheduler;
+mod stepper;
+mod validation;
 
+pub use checkpoint::OperatorState;
+pub use error::*;
 pub use exec_service::*;
+pub use handle::*;
+pub use metrics::{MetricsRegistry, NodeMetrics, NodeMetricsSnapshot};
 pub use node::*;
+pub use node_base::Subscribable;
+pub use plan::*;
+pub use progress::{QueryProgress, SourceProgress};
+pub use result_stream::{ResultStream, ResultSubscriber, SnapshotMode};
+pub use scheduler::WorkerPool;
+pub use stepper::*;
+pub(crate) use checkpoint::BarrierTracker;
+pub(crate) use metrics::MetricsProbe;
+pub(crate) use scheduler::{abort_if_torn_down, at_step_boundary, note_progress, yield_node};
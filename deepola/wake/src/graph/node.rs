@@ -6,8 +6,12 @@ use getset::{Getters, Setters};
 use nanoid::nanoid;
 use std::cell::RefCell;
 use std::rc::Rc;
+use std::sync::Arc;
 
+use super::checkpoint::{BarrierTracker, NodeCheckpointing, RestoredNode};
+use super::metrics::{MetricsProbe, NodeMetrics};
 use super::node_base::*;
+use super::progress::{ProgressTracker, QueryProgress};
 use crate::channel::*;
 
 /// (input channel) -> [This Node] -> (output channels)
@@ -19,9 +23,10 @@ use crate::channel::*;
 #[derive(Getters, Setters)]
 pub struct ExecutionNode<T: Send> {
     /// Note: [RefCell] enables us to keep [ExecutionNode] immutable while we can still
-    /// update [Self::stream_processor]
+    /// update [Self::stream_processor]. Shared with the [BarrierTracker] of the node, which
+    /// asks it for its state while it processes.
     #[getset(get)]
-    stream_processor: RefCell<Box<dyn StreamProcessor<T>>>,
+    stream_processor: Rc<RefCell<Box<dyn StreamProcessor<T>>>>,
 
     input_reader: RefCell<MultiChannelReader<T>>,
 
@@ -33,6 +38,24 @@ pub struct ExecutionNode<T: Send> {
     /// additional output channels.
     output_writer: RefCell<MultiChannelBroadcaster<T>>,
 
+    /// Ids of the nodes this node is subscribed to, each paired with the input channel that
+    /// the subscribed node writes to.
+    subscriptions: RefCell<Vec<(String, usize)>>,
+
+    /// Updated by the input and output channels of this node while it runs.
+    metrics: Arc<NodeMetrics>,
+
+    /// Set by the service if the query takes checkpoints.
+    checkpointing: RefCell<Option<NodeCheckpointing>>,
+
+    /// The schema of the output declared by the builder of the node, which takes precedence
+    /// over the one the operator derives. See [Self::output_schema].
+    declared_schema: RefCell<Option<Schema>>,
+
+    /// The columns the operator reads from each input channel, declared by the builder of the
+    /// node, e.g., the columns read by a closure.
+    required_columns: RefCell<Vec<(usize, Vec<String>)>>,
+
     #[getset(get = "pub")]
     node_id: String,
 }
@@ -68,7 +91,7 @@ impl<T: Send + 'static> ExecutionNode<T> {
     }
 
     pub fn set_simple_map(&mut self, map: SimpleMapper<T>) {
-        self.stream_processor = RefCell::new(Box::new(map));
+        self.stream_processor = Rc::new(RefCell::new(Box::new(map)));
     }
 
     /// This is a convenience method mostly for testing. That is, we directly write a record
@@ -78,12 +101,84 @@ impl<T: Send + 'static> ExecutionNode<T> {
         (&self.self_writers[channel_no]).write(message)
     }
 
+    /// Same as [Self::subscribe_to_node], but also bounds the input channel `for_channel` of
+    /// this node. See [ChannelCapacity].
+    pub fn subscribe_to_node_with_capacity(
+        &self,
+        source_node: &ExecutionNode<T>,
+        for_channel: usize,
+        capacity: ChannelCapacity,
+    ) {
+        self.self_writers[for_channel].set_capacity(capacity);
+        self.subscribe_to_node(source_node, for_channel);
+    }
+
     pub fn subscribe_to_node(&self, source_node: &ExecutionNode<T>, for_channel: usize) {
         let writer = &self.self_writers[for_channel];
         source_node.add(writer.clone());
+        self.subscriptions
+            .borrow_mut()
+            .push((source_node.node_id().clone(), for_channel));
         log_node_edge(source_node.node_id(), self.node_id());
     }
 
+    pub fn subscriptions(&self) -> Vec<(String, usize)> {
+        self.subscriptions.borrow().clone()
+    }
+
+    pub fn metrics(&self) -> Arc<NodeMetrics> {
+        self.metrics.clone()
+    }
+
+    /// The number of input channels of this node.
+    pub fn num_inputs(&self) -> usize {
+        self.self_writers.len()
+    }
+
+    /// See [StreamProcessor::operator_name].
+    pub fn operator_name(&self) -> String {
+        self.stream_processor.borrow().operator_name()
+    }
+
+    /// Declares the schema of the output of this node, e.g., for an operator that applies a
+    /// closure, or a reader of a known table.
+    pub fn declare_output_schema(&self, schema: Schema) {
+        *self.declared_schema.borrow_mut() = Some(schema);
+    }
+
+    /// Declares columns the operator reads from the input channel `channel`, which
+    /// [super::ExecutionService::validate] then looks for in the schema of that input.
+    pub fn require_columns(&self, channel: usize, columns: &[String]) {
+        self.required_columns
+            .borrow_mut()
+            .push((channel, columns.to_vec()));
+    }
+
+    /// The schema of the output of this node, given the schemas of its inputs (None where
+    /// unknown). Returns an error if an input lacks a required column; see
+    /// [StreamProcessor::output_schema].
+    pub fn output_schema(
+        &self,
+        input_schemas: &[Option<Schema>],
+    ) -> Result<Option<Schema>, String> {
+        for (channel, columns) in self.required_columns.borrow().iter() {
+            if let Some(Some(schema)) = input_schemas.get(*channel) {
+                schema.require_columns(&self.operator_name(), columns)?;
+            }
+        }
+        let derived = self
+            .stream_processor
+            .borrow()
+            .output_schema(input_schemas)?;
+        Ok(self.declared_schema.borrow().clone().or(derived))
+    }
+
+    /// A source node is not subscribed to any other node; its input is written directly to its
+    /// own channels (e.g., the list of files a reader node reads).
+    pub fn is_source(&self) -> bool {
+        self.subscriptions.borrow().is_empty()
+    }
+
     /// Processes the data from input stream until we see EOF from all input channels.
     ///
     /// This is the primary method used by ExecutionService to start all the nodes.
@@ -92,6 +187,14 @@ impl<T: Send + 'static> ExecutionNode<T> {
     /// behavior is defined inside [StreamProcessor::process_stream()]
     pub fn run(&self) {
         log::debug!("Starting Node: [{}]", self.node_id);
+        let (barriers, finished) = self.start_checkpointing();
+        if finished {
+            // Restored from a checkpoint taken after this node had finished.
+            self.output_writer.borrow().write(DataMessage::eof());
+            barriers.unwrap().finish();
+            self.release_inputs();
+            return;
+        }
         let input_reader = self.input_reader.borrow();
         let output_writer = self.output_writer.borrow();
         // Add log message here saying that which channels are linked to which nodes.
@@ -110,6 +213,8 @@ impl<T: Send + 'static> ExecutionNode<T> {
             );
         }
 
+        self.metrics.start();
+
         // Pre-processing (if needed)
         log_event("pre-process", "start");
         self.stream_processor
@@ -123,7 +228,53 @@ impl<T: Send + 'static> ExecutionNode<T> {
             .borrow()
             .process_stream(input_reader.clone(), output_writer.clone());
         log_event("process-stream", "end");
+        if let Some(barriers) = barriers.filter(|barriers| barriers.is_finished()) {
+            barriers.finish();
+        }
+        self.metrics.finish();
+        self.release_inputs();
+    }
+
+    pub(crate) fn set_checkpointing(&self, checkpointing: NodeCheckpointing) {
+        *self.checkpointing.borrow_mut() = Some(checkpointing);
+    }
 
+    /// Restores the state of the operator if the query resumes from a checkpoint, and lets
+    /// the input reader hand checkpoint barriers to a new [BarrierTracker]. Also returns
+    /// whether the node had finished at the checkpoint.
+    fn start_checkpointing(&self) -> (Option<Rc<BarrierTracker<T>>>, bool) {
+        let checkpointing = match self.checkpointing.borrow_mut().take() {
+            Some(checkpointing) => checkpointing,
+            None => return (None, false),
+        };
+        let finished = match &checkpointing.restored {
+            Some(RestoredNode::State(state)) => {
+                self.stream_processor.borrow_mut().restore(state);
+                false
+            }
+            Some(RestoredNode::Finished) => true,
+            None => false,
+        };
+        let barriers = Rc::new(BarrierTracker::new(
+            checkpointing.node_index,
+            self.operator_name(),
+            checkpointing.coordinator,
+            self.stream_processor.clone(),
+            self.output_writer.borrow().clone(),
+            self.num_inputs(),
+        ));
+        self.input_reader
+            .borrow_mut()
+            .set_barrier_tracker(barriers.clone());
+        (Some(barriers), finished)
+    }
+
+    /// Upstream nodes no longer wait for space in the input channels of this node once it
+    /// has stopped reading from them.
+    pub(crate) fn release_inputs(&self) {
+        for reader in self.input_reader.borrow().readers.iter() {
+            reader.release();
+        }
     }
 
     pub fn input_reader(&self) -> MultiChannelReader<T> {
@@ -151,7 +302,13 @@ impl<T: Send + 'static> ExecutionNode<T> {
     ///
     /// @arg num_input The number of input channels
     pub fn new(stream_processor: Box<dyn StreamProcessor<T>>, num_input: usize) -> Self {
+        let node_id = nanoid!(NODE_ID_LEN, &NODE_ID_ALPHABET);
+        let metrics = Arc::new(NodeMetrics::default());
         let mut input_channels = MultiChannelReader::<T>::new();
+        input_channels.set_probe(MetricsProbe::new(metrics.clone()));
+        let mut output_writer = MultiChannelBroadcaster::<T>::new();
+        output_writer.set_probe(MetricsProbe::new(metrics.clone()));
+        output_writer.set_node_id(&node_id);
         let mut self_writers = vec![];
         for _ in 0..num_input {
             let (write_channel, read_channel) = Channel::create::<T>();
@@ -160,11 +317,16 @@ impl<T: Send + 'static> ExecutionNode<T> {
         }
 
         Self {
-            stream_processor: RefCell::new(stream_processor),
+            stream_processor: Rc::new(RefCell::new(stream_processor)),
             input_reader: RefCell::new(input_channels),
             self_writers,
-            output_writer: RefCell::new(MultiChannelBroadcaster::<T>::new()),
-            node_id: nanoid!(NODE_ID_LEN, &NODE_ID_ALPHABET),
+            output_writer: RefCell::new(output_writer),
+            subscriptions: RefCell::new(vec![]),
+            metrics,
+            checkpointing: RefCell::new(None),
+            declared_schema: RefCell::new(None),
+            required_columns: RefCell::new(vec![]),
+            node_id,
         }
     }
 }
@@ -173,6 +335,20 @@ pub struct NodeReader<T: Send> {
     /// We use the channel of this node to listens to the node we want to read from.
     /// We just need to a single input channel.
     internal_node: ExecutionNode<T>,
+
+    /// Set once the reader is added to a service with [super::ExecutionService::add_reader].
+    progress: RefCell<Option<ProgressTracker>>,
+}
+
+impl<T: Send + 'static> NodeReader<T> {
+    /// See [ExecutionNode::subscriptions].
+    pub fn subscriptions(&self) -> Vec<(String, usize)> {
+        self.internal_node.subscriptions()
+    }
+
+    pub(crate) fn track_progress(&self, tracker: ProgressTracker) {
+        *self.progress.borrow_mut() = Some(tracker);
+    }
 }
 
 impl<T: Send + Clone + 'static> NodeReader<T> {
@@ -180,18 +356,56 @@ impl<T: Send + Clone + 'static> NodeReader<T> {
         self.internal_node.input_reader().read(0)
     }
 
+    /// Same as [Self::read], but returns the error that ended the stream as an error, e.g.,
+    /// a file a reader node could not open, or a node that panicked. The error carries the
+    /// node it originated from.
+    pub fn read_result(&self) -> Result<DataMessage<T>, StreamError> {
+        let message = self.read();
+        match message.payload() {
+            Payload::Error(error) => Err(error),
+            _ => Ok(message),
+        }
+    }
+
+    /// Returns the next message if one is already available, without waiting.
+    pub fn try_read(&self) -> Option<DataMessage<T>> {
+        self.internal_node.input_reader().try_read(0)
+    }
+
+    /// Reads the next message along with the progress of the query at that message. The
+    /// output fraction of the progress is the cardinality of the message itself.
+    ///
+    /// The progress is None unless this reader was added to the running service with
+    /// [super::ExecutionService::add_reader].
+    pub fn read_with_progress(&self) -> (DataMessage<T>, Option<QueryProgress>) {
+        let message = self.read();
+        let output_fraction = match message.payload() {
+            Payload::Some(dblock) => dblock.metadata().progress(),
+            Payload::EOF => Some(1.0),
+            Payload::Signal(_) | Payload::Error(_) => None,
+        };
+        let progress = self
+            .progress
+            .borrow()
+            .as_ref()
+            .and_then(|tracker| tracker.progress(output_fraction));
+        (message, progress)
+    }
+
     pub fn new(listens_to: &ExecutionNode<T>) -> Self {
         let mut node = ExecutionNode::create();
         node.set_simple_map(SimpleMapper::identity());
         node.subscribe_to_node(listens_to, 0);
         Self {
             internal_node: node,
+            progress: RefCell::new(None),
         }
     }
 
     pub fn empty() -> Self {
         Self {
             internal_node: ExecutionNode::create(),
+            progress: RefCell::new(None),
         }
     }
 
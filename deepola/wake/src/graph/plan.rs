@@ -0,0 +1,178 @@
+use getset::Getters;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use super::node::ExecutionNode;
+
+/// The structure of a query as registered to an [super::ExecutionService]. Obtained with
+/// [super::ExecutionService::graph], and can be exported as Graphviz DOT or JSON.
+#[derive(Debug, Clone, PartialEq, Getters, Serialize, Deserialize)]
+pub struct ExecutionGraph {
+    #[getset(get = "pub")]
+    nodes: Vec<GraphNode>,
+
+    #[getset(get = "pub")]
+    edges: Vec<GraphEdge>,
+}
+
+#[derive(Debug, Clone, PartialEq, Getters, Serialize, Deserialize)]
+pub struct GraphNode {
+    #[getset(get = "pub")]
+    node_id: String,
+
+    /// See [crate::processor::StreamProcessor::operator_name].
+    #[getset(get = "pub")]
+    operator: String,
+
+    /// The number of input channels, numbered from 0.
+    #[getset(get = "pub")]
+    num_inputs: usize,
+}
+
+/// `src` writes its output to the input channel `channel` of `dest`.
+#[derive(Debug, Clone, PartialEq, Getters, Serialize, Deserialize)]
+pub struct GraphEdge {
+    #[getset(get = "pub")]
+    src: String,
+
+    #[getset(get = "pub")]
+    dest: String,
+
+    #[getset(get = "pub")]
+    channel: usize,
+}
+
+impl ExecutionGraph {
+    /// Only the edges between the given nodes are included; e.g., the subscription of a
+    /// [super::NodeReader] is not part of the graph.
+    pub fn from_nodes<T: Send + 'static>(nodes: &[ExecutionNode<T>]) -> Self {
+        let node_ids: HashSet<&String> = nodes.iter().map(|node| node.node_id()).collect();
+        let graph_nodes = nodes
+            .iter()
+            .map(|node| GraphNode {
+                node_id: node.node_id().clone(),
+                operator: node.operator_name(),
+                num_inputs: node.num_inputs(),
+            })
+            .collect();
+        let edges = nodes
+            .iter()
+            .flat_map(|node| {
+                node.subscriptions()
+                    .into_iter()
+                    .filter(|(src, _)| node_ids.contains(src))
+                    .map(|(src, channel)| GraphEdge {
+                        src,
+                        dest: node.node_id().clone(),
+                        channel,
+                    })
+            })
+            .collect();
+        Self {
+            nodes: graph_nodes,
+            edges,
+        }
+    }
+
+    /// Renders the graph in Graphviz DOT, e.g., `dot -Tpdf plan.dot -o plan.pdf`. Each node is
+    /// labeled with its operator and node id, and each edge with the input channel it feeds.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph wake {\n");
+        for node in self.nodes.iter() {
+            writeln!(
+                dot,
+                "  \"{}\" [label=\"{}\\n{}\"];",
+                node.node_id, node.operator, node.node_id
+            )
+            .unwrap();
+        }
+        for edge in self.edges.iter() {
+            writeln!(
+                dot,
+                "  \"{}\" -> \"{}\" [label=\"{}\"];",
+                edge.src, edge.dest, edge.channel
+            )
+            .unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{ExecutionService, NodeReader};
+    use crate::processor::SimpleMapper;
+
+    fn two_input_service() -> (ExecutionService<String>, Vec<String>) {
+        let left = ExecutionNode::from(SimpleMapper::from(|r: &String| Some(r.clone())));
+        let right = ExecutionNode::from(SimpleMapper::from(|r: &String| Some(r.clone())));
+        let join = ExecutionNode::<String>::new_double_inputs(Box::new(SimpleMapper::identity()));
+        join.subscribe_to_node(&left, 0);
+        join.subscribe_to_node(&right, 1);
+        let ids = vec![
+            left.node_id().clone(),
+            right.node_id().clone(),
+            join.node_id().clone(),
+        ];
+        let mut service = ExecutionService::create();
+        service.add(left);
+        service.add(right);
+        service.add(join);
+        (service, ids)
+    }
+
+    #[test]
+    fn graph_has_nodes_and_edges() {
+        let (service, ids) = two_input_service();
+        let graph = service.graph();
+        assert_eq!(graph.nodes().len(), 3);
+        assert_eq!(graph.nodes()[0].operator(), "SimpleMapper");
+        assert_eq!(*graph.nodes()[2].num_inputs(), 2);
+        assert_eq!(
+            graph.edges(),
+            &vec![
+                GraphEdge {
+                    src: ids[0].clone(),
+                    dest: ids[2].clone(),
+                    channel: 0
+                },
+                GraphEdge {
+                    src: ids[1].clone(),
+                    dest: ids[2].clone(),
+                    channel: 1
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn graph_excludes_node_readers() {
+        let node = ExecutionNode::<String>::create();
+        let _reader = NodeReader::new(&node);
+        let mut service = ExecutionService::create();
+        service.add(node);
+        assert!(service.graph().edges().is_empty());
+    }
+
+    #[test]
+    fn exports_dot_and_json() {
+        let (service, ids) = two_input_service();
+        let graph = service.graph();
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph wake {"));
+        assert!(dot.contains(&format!("\"{}\" [label=\"SimpleMapper\\n{}\"];", ids[0], ids[0])));
+        assert!(dot.contains(&format!("\"{}\" -> \"{}\" [label=\"1\"];", ids[1], ids[2])));
+        assert_eq!(ExecutionGraph::from_json(&graph.to_json()).unwrap(), graph);
+    }
+}
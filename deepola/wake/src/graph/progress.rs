@@ -0,0 +1,234 @@
+use getset::Getters;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::metrics::NodeMetrics;
+use super::node::ExecutionNode;
+
+/// How far a running query has come, derived from the
+/// [progress](crate::data::BlockMetadata::progress) of the blocks its nodes write.
+///
+/// Displayed as, e.g., `37% of lineitem scanned, 12% of orders scanned; 20% of the output;
+/// about 8.5s left`.
+#[derive(Debug, Clone, PartialEq, Getters, Serialize)]
+#[getset(get = "pub")]
+pub struct QueryProgress {
+    sources: Vec<SourceProgress>,
+
+    /// The fraction of the input reflected by the output, i.e., the smallest cardinality
+    /// written by the output nodes.
+    output_fraction: f64,
+
+    /// Time since the query started.
+    elapsed: Duration,
+
+    /// Extrapolated from [Self::elapsed] and [Self::output_fraction]. None until the output
+    /// node has written its first block.
+    estimated_remaining: Option<Duration>,
+}
+
+/// How far a source node has read its input.
+#[derive(Debug, Clone, PartialEq, Getters, Serialize)]
+#[getset(get = "pub")]
+pub struct SourceProgress {
+    node_id: String,
+
+    /// The table in the schema of the blocks the source writes, if any.
+    table: Option<String>,
+
+    fraction: f64,
+}
+
+impl QueryProgress {
+    fn new(sources: Vec<SourceProgress>, output_fraction: f64, elapsed: Duration) -> Self {
+        let estimated_remaining = if output_fraction >= 1.0 {
+            Some(Duration::ZERO)
+        } else if output_fraction > 0.0 {
+            Some(elapsed.mul_f64((1.0 - output_fraction) / output_fraction))
+        } else {
+            None
+        };
+        QueryProgress {
+            sources,
+            output_fraction,
+            elapsed,
+            estimated_remaining,
+        }
+    }
+}
+
+impl fmt::Display for QueryProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, source) in self.sources.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            let name = source.table.as_ref().unwrap_or(&source.node_id);
+            write!(f, "{:.0}% of {} scanned", source.fraction * 100.0, name)?;
+        }
+        if !self.sources.is_empty() {
+            write!(f, "; ")?;
+        }
+        write!(f, "{:.0}% of the output", self.output_fraction * 100.0)?;
+        if let Some(remaining) = self.estimated_remaining {
+            write!(f, "; about {:.1}s left", remaining.as_secs_f64())?;
+        }
+        Ok(())
+    }
+}
+
+/// The nodes of a started query whose metrics make up its [QueryProgress].
+#[derive(Debug)]
+struct TrackedQuery {
+    started: Instant,
+
+    /// (node id, metrics) of the source nodes.
+    sources: Vec<(String, Arc<NodeMetrics>)>,
+    outputs: Vec<Arc<NodeMetrics>>,
+}
+
+/// Shared by an [super::ExecutionService] and its [super::NodeReader]s, so that both can
+/// report the progress of the query once it starts.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ProgressTracker {
+    query: Arc<Mutex<Option<TrackedQuery>>>,
+}
+
+impl ProgressTracker {
+    /// Starts tracking the query of `nodes`. Its output is what the nodes `output_ids` write,
+    /// or, if there are none, what the nodes no other node is subscribed to write.
+    pub(crate) fn track<T: Send + 'static>(
+        &self,
+        nodes: &[ExecutionNode<T>],
+        output_ids: &[String],
+    ) {
+        let subscribed_ids: HashSet<String> = nodes
+            .iter()
+            .flat_map(|node| node.subscriptions())
+            .map(|(source_id, _)| source_id)
+            .collect();
+        let is_output = |node: &ExecutionNode<T>| {
+            if output_ids.is_empty() {
+                !subscribed_ids.contains(node.node_id())
+            } else {
+                output_ids.contains(node.node_id())
+            }
+        };
+        let query = TrackedQuery {
+            started: Instant::now(),
+            sources: nodes
+                .iter()
+                .filter(|node| node.is_source())
+                .map(|node| (node.node_id().clone(), node.metrics()))
+                .collect(),
+            outputs: nodes
+                .iter()
+                .filter(|node| is_output(node))
+                .map(|node| node.metrics())
+                .collect(),
+        };
+        *self.query.lock().unwrap() = Some(query);
+    }
+
+    /// The progress of the tracked query, or None if it has not started. `output_fraction`
+    /// overrides the fraction the output nodes report, e.g., with the cardinality of the
+    /// message a reader has just read.
+    pub(crate) fn progress(&self, output_fraction: Option<f64>) -> Option<QueryProgress> {
+        let query = self.query.lock().unwrap();
+        let query = query.as_ref()?;
+        let sources = query
+            .sources
+            .iter()
+            .map(|(node_id, metrics)| SourceProgress {
+                node_id: node_id.clone(),
+                table: metrics.table(),
+                fraction: metrics.cardinality(),
+            })
+            .collect();
+        let output_fraction = output_fraction.unwrap_or_else(|| {
+            query
+                .outputs
+                .iter()
+                .map(|metrics| metrics.cardinality())
+                .fold(1.0, f64::min)
+        });
+        Some(QueryProgress::new(
+            sources,
+            output_fraction,
+            query.started.elapsed(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{BlockMetadata, DataBlock, DataMessage};
+    use crate::graph::{ExecutionService, NodeReader};
+    use crate::processor::SimpleMapper;
+
+    fn block(data: &str, cardinality: f64) -> DataMessage<String> {
+        let metadata = BlockMetadata::new().with_progress(cardinality);
+        DataMessage::from(DataBlock::new(data.to_string(), metadata))
+    }
+
+    #[test]
+    fn attaches_progress_to_read_messages() {
+        let source = ExecutionNode::from(SimpleMapper::from(|r: &String| Some(r.clone())));
+        let sink = ExecutionNode::from(SimpleMapper::from(|r: &String| Some(r.clone())));
+        sink.subscribe_to_node(&source, 0);
+        let source_id = source.node_id().clone();
+        let reader_node = NodeReader::new(&sink);
+        source.write_to_self(0, block("a", 0.25));
+        source.write_to_self(0, block("b", 0.5));
+        source.write_to_self(0, DataMessage::eof());
+
+        let mut service = ExecutionService::create();
+        service.add(source);
+        service.add(sink);
+        service.add_reader(&reader_node);
+        assert_eq!(service.progress(), None);
+        let mut executor = service.run_stepping();
+
+        executor.step();
+        executor.step();
+        let (_, progress) = reader_node.read_with_progress();
+        let progress = progress.unwrap();
+        assert_eq!(*progress.output_fraction(), 0.25);
+        assert_eq!(progress.sources()[0].node_id(), &source_id);
+        assert_eq!(*progress.sources()[0].fraction(), 0.25);
+        assert!(progress.estimated_remaining().is_some());
+
+        executor.run_until_idle();
+        let (_, progress) = reader_node.read_with_progress();
+        let progress = progress.unwrap();
+        assert_eq!(*progress.output_fraction(), 0.5);
+        assert_eq!(*progress.sources()[0].fraction(), 1.0);
+        assert!(reader_node.read_with_progress().0.is_eof());
+        executor.finish().unwrap();
+
+        let progress = service.progress().unwrap();
+        assert_eq!(*progress.output_fraction(), 1.0);
+        assert_eq!(progress.estimated_remaining(), &Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn displays_sources_and_output() {
+        let progress = QueryProgress::new(
+            vec![SourceProgress {
+                node_id: "abc".to_string(),
+                table: Some("lineitem".to_string()),
+                fraction: 0.37,
+            }],
+            0.2,
+            Duration::from_secs(2),
+        );
+        assert_eq!(
+            progress.to_string(),
+            "37% of lineitem scanned; 20% of the output; about 8.0s left"
+        );
+    }
+}
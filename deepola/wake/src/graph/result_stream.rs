@@ -0,0 +1,313 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+use std::time::Duration;
+
+use crate::data::{DataBlock, DataMessage, Payload, Signal};
+
+use super::node::NodeReader;
+
+/// How long a consumer waits for a snapshot before checking the output channel itself, in
+/// case no thread is moving its messages into the stream (e.g., with
+/// [super::SteppingExecutor]).
+const PUBLISH_WAIT: Duration = Duration::from_millis(1);
+
+/// Which snapshots a [ResultSubscriber] reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotMode {
+    /// Every snapshot published after subscribing, in order. Snapshots queue up until read.
+    All,
+
+    /// Only the most recent snapshot; the ones published in between two reads are skipped.
+    Latest,
+}
+
+struct ResultState<T> {
+    /// The most recent snapshot.
+    latest: Option<DataBlock<T>>,
+
+    /// The number of snapshots published so far.
+    version: u64,
+
+    /// The EOF, STOP or error message that ended the stream.
+    end: Option<DataMessage<T>>,
+
+    /// The unread snapshots of each [SnapshotMode::All] subscriber.
+    queues: HashMap<usize, VecDeque<DataBlock<T>>>,
+    next_queue_id: usize,
+}
+
+struct ResultShared<T: Send> {
+    state: Mutex<ResultState<T>>,
+    published: Condvar,
+
+    /// Reads from the output node. Whoever holds the lock moves the messages read into
+    /// [Self::state].
+    source: Mutex<NodeReader<T>>,
+}
+
+impl<T: Send> ResultShared<T> {
+    fn lock(&self) -> MutexGuard<'_, ResultState<T>> {
+        self.state.lock().unwrap()
+    }
+}
+
+/// The results (i.e., snapshots) written by the output node of a query, shared by any number
+/// of consumers. Created by [super::ExecutionService::add_result_stream].
+///
+/// Unlike a [NodeReader], a slow consumer does not hold up the output: while the query runs,
+/// the snapshots are moved off the output channel as soon as they are written, and each
+/// [ResultSubscriber] reads either every snapshot or only the latest one.
+pub struct ResultStream<T: Send> {
+    shared: Arc<ResultShared<T>>,
+}
+
+impl<T: Send> Clone for ResultStream<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T: Send> ResultStream<T> {
+    /// A new consumer of this stream. It reads the snapshots published from now on; in
+    /// [SnapshotMode::Latest], it also reads the current snapshot if there is one.
+    pub fn subscribe(&self, mode: SnapshotMode) -> ResultSubscriber<T> {
+        let mut state = self.shared.lock();
+        let queue_id = match mode {
+            SnapshotMode::All => {
+                let queue_id = state.next_queue_id;
+                state.next_queue_id += 1;
+                state.queues.insert(queue_id, VecDeque::new());
+                Some(queue_id)
+            }
+            SnapshotMode::Latest => None,
+        };
+        ResultSubscriber {
+            shared: self.shared.clone(),
+            queue_id,
+            seen_version: 0,
+        }
+    }
+
+    /// The EOF, STOP or error message that ended the stream, if it has ended.
+    pub fn end(&self) -> Option<DataMessage<T>> {
+        self.shared.lock().end.clone()
+    }
+
+    /// True once the output node has written EOF, STOP or an error.
+    pub fn is_finished(&self) -> bool {
+        self.shared.lock().end.is_some()
+    }
+
+    fn publish(&self, message: DataMessage<T>) {
+        let mut state = self.shared.lock();
+        match message.payload() {
+            Payload::Some(dblock) => {
+                for queue in state.queues.values_mut() {
+                    queue.push_back(dblock.clone());
+                }
+                state.latest = Some(dblock.clone());
+                state.version += 1;
+            }
+            Payload::EOF => state.end = Some(message),
+            Payload::Signal(Signal::STOP) => state.end = Some(message),
+            Payload::Error(_) => state.end = Some(message),
+            Payload::Signal(_) => return,
+        }
+        drop(state);
+        self.shared.published.notify_all();
+    }
+}
+
+impl<T: Send + Clone + 'static> ResultStream<T> {
+    pub(crate) fn new(reader: NodeReader<T>) -> Self {
+        let state = ResultState {
+            latest: None,
+            version: 0,
+            end: None,
+            queues: HashMap::new(),
+            next_queue_id: 0,
+        };
+        Self {
+            shared: Arc::new(ResultShared {
+                state: Mutex::new(state),
+                published: Condvar::new(),
+                source: Mutex::new(reader),
+            }),
+        }
+    }
+
+    /// The most recent snapshot, if any, without waiting.
+    pub fn latest(&self) -> Option<DataBlock<T>> {
+        self.drain();
+        self.shared.lock().latest.clone()
+    }
+
+    /// Waits for the output node to finish, and returns the snapshot written last, i.e., the
+    /// final result at EOF. If the query was stopped early, this is the last result before
+    /// STOP; [Self::end] tells the two apart.
+    pub fn wait_final(&self) -> Option<DataBlock<T>> {
+        loop {
+            self.drain();
+            let state = self.shared.lock();
+            if state.end.is_some() {
+                return state.latest.clone();
+            }
+            let _ = self.shared.published.wait_timeout(state, PUBLISH_WAIT).unwrap();
+        }
+    }
+
+    /// Moves the messages written to the output channel so far into the stream, unless
+    /// another thread is already doing so.
+    fn drain(&self) {
+        if let Ok(source) = self.shared.source.try_lock() {
+            while let Some(message) = source.try_read() {
+                self.publish(message);
+            }
+        }
+    }
+
+    /// Moves every message written to the output channel into the stream until it ends.
+    /// Run on its own thread by [super::ExecutionService::run].
+    pub(crate) fn pump(&self) {
+        let source = self.shared.source.lock().unwrap();
+        loop {
+            let message = source.read();
+            let is_end = message.is_end();
+            self.publish(message);
+            if is_end {
+                break;
+            }
+        }
+    }
+}
+
+/// A consumer of a [ResultStream]. Reads snapshots as [DataMessage]s, followed by the EOF,
+/// STOP or error that ended the stream, as a [NodeReader] does. Once the stream has ended, the end
+/// message is returned on every further read.
+pub struct ResultSubscriber<T: Send> {
+    shared: Arc<ResultShared<T>>,
+
+    /// Set for [SnapshotMode::All].
+    queue_id: Option<usize>,
+
+    /// The version of the snapshot read last, for [SnapshotMode::Latest].
+    seen_version: u64,
+}
+
+impl<T: Send + Clone + 'static> ResultSubscriber<T> {
+    /// Waits for the next snapshot, or the end of the stream.
+    pub fn read(&mut self) -> DataMessage<T> {
+        loop {
+            let stream = self.stream();
+            stream.drain();
+            let state = stream.shared.lock();
+            if let Some(message) = self.next_message(state) {
+                return message;
+            }
+            let state = stream.shared.lock();
+            let _ = stream.shared.published.wait_timeout(state, PUBLISH_WAIT).unwrap();
+        }
+    }
+
+    /// Returns the next snapshot, or the end of the stream, if available without waiting.
+    pub fn try_read(&mut self) -> Option<DataMessage<T>> {
+        let stream = self.stream();
+        stream.drain();
+        self.next_message(stream.shared.lock())
+    }
+
+    fn stream(&self) -> ResultStream<T> {
+        ResultStream {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T: Send> ResultSubscriber<T> {
+    fn next_message(&mut self, mut state: MutexGuard<ResultState<T>>) -> Option<DataMessage<T>> {
+        let snapshot = match self.queue_id {
+            Some(queue_id) => state.queues.get_mut(&queue_id).unwrap().pop_front(),
+            None if state.version > self.seen_version => {
+                self.seen_version = state.version;
+                state.latest.clone()
+            }
+            None => None,
+        };
+        snapshot.map(DataMessage::from).or_else(|| state.end.clone())
+    }
+}
+
+impl<T: Send> Drop for ResultSubscriber<T> {
+    fn drop(&mut self) {
+        if let Some(queue_id) = self.queue_id {
+            self.shared.lock().queues.remove(&queue_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::graph::{ExecutionNode, ExecutionService};
+    use crate::processor::SimpleMapper;
+
+    fn data(message: &DataMessage<String>) -> String {
+        message.datablock().data().clone()
+    }
+
+    fn snapshots_of(inputs: &[&str]) -> (ExecutionService<String>, ResultStream<String>) {
+        let node = ExecutionNode::from(SimpleMapper::from(|r: &String| Some(r.clone())));
+        for input in inputs {
+            node.write_to_self(0, DataMessage::from(input.to_string()));
+        }
+        let mut service = ExecutionService::create();
+        let stream = service.add_result_stream(&node);
+        service.add(node);
+        (service, stream)
+    }
+
+    #[test]
+    fn subscribers_read_all_or_latest() {
+        let (mut service, stream) = snapshots_of(&["a", "b", "c"]);
+        let node_writer = service.nodes()[0].self_writer(0);
+        let mut all = stream.subscribe(SnapshotMode::All);
+        let mut latest = stream.subscribe(SnapshotMode::Latest);
+        let mut executor = service.run_stepping();
+        executor.run_until_idle();
+
+        assert_eq!(data(&latest.try_read().unwrap()), "c");
+        assert!(latest.try_read().is_none());
+        for expected in ["a", "b", "c"] {
+            assert_eq!(data(&all.try_read().unwrap()), expected);
+        }
+        assert!(all.try_read().is_none());
+        assert!(!stream.is_finished());
+
+        node_writer.write(DataMessage::eof());
+        executor.finish().unwrap();
+        assert!(all.read().is_eof());
+        assert!(latest.read().is_eof());
+        assert!(latest.read().is_eof());
+        assert_eq!(stream.wait_final().unwrap().data(), "c");
+    }
+
+    #[test]
+    fn wait_final_returns_result_at_eof() {
+        let (mut service, stream) = snapshots_of(&["a", "b"]);
+        let node_writer = service.nodes()[0].self_writer(0);
+        let consumer = {
+            let stream = stream.clone();
+            thread::spawn(move || stream.wait_final().unwrap().data().clone())
+        };
+        service.run();
+        node_writer.write(DataMessage::eof());
+        assert_eq!(consumer.join().unwrap(), "b");
+        assert!(stream.end().unwrap().is_eof());
+        service.join().unwrap();
+    }
+}
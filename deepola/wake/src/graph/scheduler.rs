@@ -0,0 +1,335 @@
+use generator::{Generator, Gn};
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::data::{DataMessage, StreamError, StreamErrorKind};
+use crate::utils::log_node_mapping;
+
+use super::error::ExecutionError;
+use super::node::ExecutionNode;
+
+/// Stack size (in words) of the coroutine each node runs on. This is the same 2 MiB a spawned
+/// std thread gets by default on 64-bit targets, which is what nodes used to run on.
+const NODE_STACK_SIZE: usize = 0x40000;
+
+const MIN_IDLE_BACKOFF: Duration = Duration::from_micros(10);
+const MAX_IDLE_BACKOFF: Duration = Duration::from_millis(1);
+
+thread_local! {
+    /// Set whenever a node consumes a message. A worker uses it to tell a productive time slice
+    /// apart from one where the node only found empty input channels.
+    static MADE_PROGRESS: Cell<bool> = const { Cell::new(false) };
+
+    /// The abort flag of the pool this worker thread belongs to.
+    static POOL_ABORTED: RefCell<Option<Arc<AtomicBool>>> = const { RefCell::new(None) };
+
+    /// Set while a [super::SteppingExecutor] runs nodes on this thread.
+    static STEPPING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Hands the current worker thread back to the scheduler if we are running inside a pooled
+/// node task. Returns false outside of a pool, in which case the caller should block instead.
+pub(crate) fn yield_node() -> bool {
+    if !generator::is_generator() {
+        return false;
+    }
+    #[allow(deprecated)]
+    generator::yield_with(());
+    true
+}
+
+/// Records that the running node consumed a message during its current time slice.
+pub(crate) fn note_progress() {
+    MADE_PROGRESS.with(|progress| progress.set(true));
+}
+
+/// True if the running node must yield before it consumes another message, which is the case
+/// once it has consumed a message while being stepped by a [super::SteppingExecutor].
+pub(crate) fn at_step_boundary() -> bool {
+    STEPPING.with(|stepping| stepping.get()) && MADE_PROGRESS.with(|progress| progress.get())
+}
+
+/// Unwinds the running node if another node of its pool has failed. Called whenever a node
+/// reads from a channel, so that the whole query stops soon after a failure.
+pub(crate) fn abort_if_torn_down() {
+    let aborted = POOL_ABORTED.with(|flag| {
+        flag.borrow()
+            .as_ref()
+            .is_some_and(|flag| flag.load(Ordering::Relaxed))
+    });
+    if aborted && generator::is_generator() {
+        panic::resume_unwind(Box::new(TornDown));
+    }
+}
+
+/// Unwinding payload of a node that is stopped because another node failed.
+struct TornDown;
+
+fn panic_message(cause: &(dyn Any + Send)) -> String {
+    if let Some(message) = cause.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = cause.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// State shared by a pool and the node tasks running on it.
+#[derive(Clone, Default)]
+pub(crate) struct TaskShared {
+    /// Nodes that finished running. They are kept alive until the pool is joined so that their
+    /// input channels stay open for upstream nodes that are still writing.
+    retired: Arc<Mutex<Vec<Box<dyn Any + Send>>>>,
+
+    /// Failures of the nodes, in the order they happened.
+    failures: Arc<Mutex<Vec<ExecutionError>>>,
+
+    /// Set on the first failure; all the other nodes are then torn down.
+    aborted: Arc<AtomicBool>,
+}
+
+impl TaskShared {
+    pub(crate) fn fail(&self, error: ExecutionError) {
+        log::error!("{}", error);
+        self.failures.lock().unwrap().push(error);
+        self.aborted.store(true, Ordering::Relaxed);
+    }
+
+    /// The first failure, if any node failed.
+    pub(crate) fn result(&self) -> Result<(), ExecutionError> {
+        match self.failures.lock().unwrap().first() {
+            Some(error) => Err(error.clone()),
+            None => Ok(()),
+        }
+    }
+
+    /// Makes the nodes running on this thread unwind once a node fails; see
+    /// [abort_if_torn_down].
+    pub(crate) fn watch_on_this_thread(&self) {
+        POOL_ABORTED.with(|flag| *flag.borrow_mut() = Some(self.aborted.clone()));
+    }
+}
+
+/// Runs `task` on this thread until it finishes or yields. Returns whether it has finished,
+/// and whether it consumed a message.
+pub(crate) fn run_slice(task: &mut NodeTask, task_shared: &TaskShared) -> (bool, bool) {
+    MADE_PROGRESS.with(|progress| progress.set(false));
+    let finished = match panic::catch_unwind(AssertUnwindSafe(|| task.resume())) {
+        Ok(finished) => finished,
+        Err(cause) => {
+            task_shared.fail(ExecutionError::new(task.node_id(), &panic_message(&*cause)));
+            true
+        }
+    };
+    (finished, MADE_PROGRESS.with(|progress| progress.get()))
+}
+
+/// Makes the nodes running on this thread yield after every consumed message, until the
+/// returned guard is dropped.
+pub(crate) fn step_on_this_thread() -> SteppingGuard {
+    STEPPING.with(|stepping| stepping.set(true));
+    SteppingGuard
+}
+
+pub(crate) struct SteppingGuard;
+
+impl Drop for SteppingGuard {
+    fn drop(&mut self) {
+        STEPPING.with(|stepping| stepping.set(false));
+    }
+}
+
+/// An [ExecutionNode] wrapped into a coroutine so that it can be suspended whenever its input
+/// channel is empty and resumed later, possibly on a different worker thread.
+pub(crate) struct NodeTask {
+    node_id: String,
+    coroutine: Generator<'static, (), ()>,
+}
+
+impl NodeTask {
+    pub(crate) fn new<T: Send + 'static>(node: ExecutionNode<T>, shared: TaskShared) -> Self {
+        let node_id = node.node_id().clone();
+        let coroutine = Gn::<()>::new_opt(NODE_STACK_SIZE, move || {
+            if let Err(cause) = panic::catch_unwind(AssertUnwindSafe(|| node.run())) {
+                if let Some(generator::Error::Cancel) = cause.downcast_ref::<generator::Error>() {
+                    panic::resume_unwind(cause);
+                }
+                if !cause.is::<TornDown>() {
+                    shared.fail(ExecutionError::new(node.node_id(), &panic_message(&*cause)));
+                }
+                // Downstream nodes would otherwise wait forever for the output of this node.
+                // They get the failure that tore the pool down, which reaches the readers.
+                let message = match shared.result() {
+                    Err(failure) => DataMessage::error(
+                        StreamError::new(StreamErrorKind::Operator, failure.message())
+                            .with_origin(failure.node_id()),
+                    ),
+                    Ok(()) => DataMessage::stop(),
+                };
+                // Written without waiting for space: a full channel would make this node wait,
+                // and be torn down again, before it ends its outputs.
+                for writer in node.output_writer().iter() {
+                    writer.write_terminal_if_open(message.clone());
+                }
+                node.metrics().finish();
+                node.release_inputs();
+            }
+            shared.retired.lock().unwrap().push(Box::new(node));
+        });
+        Self { node_id, coroutine }
+    }
+
+    pub(crate) fn node_id(&self) -> &str {
+        &self.node_id
+    }
+
+    /// Runs the node until it either finishes or waits on an empty channel. Returns true once
+    /// the node has finished.
+    pub(crate) fn resume(&mut self) -> bool {
+        self.coroutine.resume();
+        self.coroutine.is_done()
+    }
+}
+
+struct PoolState {
+    /// Tasks that are ready to be resumed by any worker.
+    queue: VecDeque<NodeTask>,
+
+    /// Number of tasks that have not finished yet, including the ones currently running.
+    pending: usize,
+}
+
+struct PoolShared {
+    state: Mutex<PoolState>,
+    task_ready: Condvar,
+    all_done: Condvar,
+}
+
+impl PoolShared {
+    /// Blocks until a task is available. Returns None once all tasks have finished. The second
+    /// element is the number of tasks left in the queue.
+    fn next_task(&self) -> Option<(NodeTask, usize)> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(task) = state.queue.pop_front() {
+                let queued = state.queue.len();
+                return Some((task, queued));
+            }
+            if state.pending == 0 {
+                return None;
+            }
+            state = self.task_ready.wait(state).unwrap();
+        }
+    }
+
+    fn requeue(&self, task: NodeTask) {
+        self.state.lock().unwrap().queue.push_back(task);
+        self.task_ready.notify_one();
+    }
+
+    fn complete(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.pending -= 1;
+        if state.pending == 0 {
+            // wakes up idle workers so that they can exit
+            self.task_ready.notify_all();
+            self.all_done.notify_all();
+        }
+    }
+}
+
+/// Runs [ExecutionNode]s as tasks on a fixed number of worker threads.
+///
+/// A node yields its worker whenever it reads from an empty input channel, so a plan may have
+/// many more nodes than there are workers. If a node fails, the other nodes are torn down the
+/// next time they read from a channel, and each of them sends the failure downstream as a
+/// [Payload::Error](crate::data::Payload).
+pub struct WorkerPool {
+    shared: Arc<PoolShared>,
+    workers: Vec<JoinHandle<()>>,
+    task_shared: TaskShared,
+}
+
+impl WorkerPool {
+    pub(crate) fn start<T: Send + 'static>(
+        nodes: Vec<ExecutionNode<T>>,
+        num_threads: usize,
+    ) -> Self {
+        let task_shared = TaskShared::default();
+        let tasks: VecDeque<NodeTask> = nodes
+            .into_iter()
+            .map(|node| NodeTask::new(node, task_shared.clone()))
+            .collect();
+        let shared = Arc::new(PoolShared {
+            state: Mutex::new(PoolState {
+                pending: tasks.len(),
+                queue: tasks,
+            }),
+            task_ready: Condvar::new(),
+            all_done: Condvar::new(),
+        });
+        let workers = (0..num_threads.max(1))
+            .map(|_| {
+                let shared = shared.clone();
+                let task_shared = task_shared.clone();
+                thread::spawn(move || Self::work(shared, task_shared))
+            })
+            .collect();
+        Self {
+            shared,
+            workers,
+            task_shared,
+        }
+    }
+
+    fn work(shared: Arc<PoolShared>, task_shared: TaskShared) {
+        task_shared.watch_on_this_thread();
+        let mut idle_streak = 0;
+        let mut backoff = MIN_IDLE_BACKOFF;
+        while let Some((mut task, queued)) = shared.next_task() {
+            log_node_mapping(task.node_id(), thread::current().id());
+            let (finished, made_progress) = run_slice(&mut task, &task_shared);
+            if finished {
+                shared.complete();
+            } else {
+                shared.requeue(task);
+            }
+
+            // Every other task in the queue has been tried without any of them consuming a
+            // message; back off instead of spinning through the queue.
+            if made_progress {
+                idle_streak = 0;
+                backoff = MIN_IDLE_BACKOFF;
+            } else {
+                idle_streak += 1;
+                if idle_streak > queued {
+                    thread::sleep(backoff);
+                    backoff = (backoff * 2).min(MAX_IDLE_BACKOFF);
+                }
+            }
+        }
+    }
+
+    /// Blocks until every task has finished and the workers have exited. Returns the first
+    /// failure if any node failed.
+    pub fn join(self) -> Result<(), ExecutionError> {
+        {
+            let mut state = self.shared.state.lock().unwrap();
+            while state.pending > 0 {
+                state = self.shared.all_done.wait(state).unwrap();
+            }
+        }
+        for worker in self.workers {
+            worker.join().unwrap();
+        }
+        self.task_shared.retired.lock().unwrap().clear();
+        self.task_shared.result()
+    }
+}
@@ -0,0 +1,151 @@
+use super::error::ExecutionError;
+use super::node::ExecutionNode;
+use super::scheduler::{run_slice, step_on_this_thread, NodeTask, TaskShared};
+
+/// Runs the nodes of a query cooperatively on the calling thread, one message at a time.
+/// Created by [super::ExecutionService::run_stepping].
+///
+/// The nodes are tried in the order they were added to the service. [Self::step] lets the
+/// next node with a message in its input channels consume that single message and process it,
+/// i.e., run until it is about to read its next message. Given the same inputs, a query thus
+/// always goes through the same sequence of steps.
+pub struct SteppingExecutor {
+    /// The unfinished tasks. The next step starts with `tasks[next]`.
+    tasks: Vec<NodeTask>,
+    next: usize,
+    task_shared: TaskShared,
+}
+
+impl SteppingExecutor {
+    pub(crate) fn new<T: Send + 'static>(nodes: Vec<ExecutionNode<T>>) -> Self {
+        let task_shared = TaskShared::default();
+        let tasks = nodes
+            .into_iter()
+            .map(|node| NodeTask::new(node, task_shared.clone()))
+            .collect();
+        Self {
+            tasks,
+            next: 0,
+            task_shared,
+        }
+    }
+
+    /// Lets one node consume one message. Returns false if no node could make progress,
+    /// i.e., all the nodes have finished or are waiting for input that has not been written
+    /// yet (e.g., the EOF of a source node).
+    pub fn step(&mut self) -> bool {
+        let _stepping = step_on_this_thread();
+        self.task_shared.watch_on_this_thread();
+        for _ in 0..self.tasks.len() {
+            let index = self.next % self.tasks.len();
+            let (finished, made_progress) = run_slice(&mut self.tasks[index], &self.task_shared);
+            if finished {
+                self.tasks.remove(index);
+                self.next = index;
+                return true;
+            }
+            self.next = index + 1;
+            if made_progress {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Steps until no node can make progress. Returns the number of steps taken.
+    pub fn run_until_idle(&mut self) -> usize {
+        let mut steps = 0;
+        while self.step() {
+            steps += 1;
+        }
+        steps
+    }
+
+    /// True once every node has finished.
+    pub fn is_finished(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Runs the remaining steps. Returns the first failure if any node failed, as
+    /// [super::ExecutionService::join] does. Nodes that are still waiting for input are
+    /// dropped.
+    pub fn finish(mut self) -> Result<(), ExecutionError> {
+        self.run_until_idle();
+        self.task_shared.result()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::DataMessage;
+    use crate::graph::{ExecutionNode, ExecutionService, NodeReader};
+    use crate::processor::SimpleMapper;
+
+    fn read_data(reader_node: &NodeReader<String>) -> Option<String> {
+        reader_node
+            .try_read()
+            .map(|message| message.datablock().data().clone())
+    }
+
+    #[test]
+    fn steps_one_message_at_a_time() {
+        let first = ExecutionNode::from(SimpleMapper::from(|r: &String| Some(r.clone() + "X")));
+        let second = ExecutionNode::from(SimpleMapper::from(|r: &String| Some(r.clone() + "Y")));
+        second.subscribe_to_node(&first, 0);
+        let reader_node = NodeReader::new(&second);
+        first.write_to_self(0, DataMessage::from("a".to_string()));
+        first.write_to_self(0, DataMessage::from("b".to_string()));
+        first.write_to_self(0, DataMessage::eof());
+
+        let mut service = ExecutionService::create();
+        service.add(first);
+        service.add(second);
+        let mut executor = service.run_stepping();
+
+        assert!(executor.step()); // first consumes "a"
+        assert_eq!(read_data(&reader_node), None);
+        assert!(executor.step()); // second consumes "aX"
+        assert_eq!(read_data(&reader_node), Some("aXY".to_string()));
+        assert!(executor.step()); // first consumes "b"
+        assert_eq!(read_data(&reader_node), None);
+        assert!(executor.step()); // second consumes "bX"
+        assert_eq!(read_data(&reader_node), Some("bXY".to_string()));
+        assert!(executor.step()); // first consumes EOF
+        assert!(executor.step()); // second consumes EOF
+        assert!(reader_node.read().is_eof());
+        assert!(executor.is_finished());
+        assert!(!executor.step());
+        executor.finish().unwrap();
+    }
+
+    /// A step only happens once a source node has input to consume.
+    #[test]
+    fn idles_without_input() {
+        let node = ExecutionNode::from(SimpleMapper::from(|r: &String| Some(r.clone())));
+        let self_writer = node.self_writer(0);
+        let reader_node = NodeReader::new(&node);
+        let mut service = ExecutionService::create();
+        service.add(node);
+        let mut executor = service.run_stepping();
+
+        assert_eq!(executor.run_until_idle(), 0);
+        self_writer.write(DataMessage::from("a".to_string()));
+        assert_eq!(executor.run_until_idle(), 1);
+        assert_eq!(read_data(&reader_node), Some("a".to_string()));
+        self_writer.write(DataMessage::eof());
+        executor.finish().unwrap();
+        assert!(reader_node.read().is_eof());
+    }
+
+    #[test]
+    fn finish_reports_failing_node() {
+        let node = ExecutionNode::from(SimpleMapper::from(|_: &String| -> Option<String> {
+            panic!("bad input")
+        }));
+        node.write_to_self(0, DataMessage::from("a".to_string()));
+        let mut service = ExecutionService::create();
+        service.add(node);
+        let executor = service.run_stepping();
+        assert_eq!(executor.finish().unwrap_err().message(), "bad input");
+    }
+}
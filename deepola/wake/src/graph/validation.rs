@@ -0,0 +1,383 @@
+use std::collections::{HashMap, HashSet};
+
+use super::error::{PlanProblem, ValidationError};
+use super::node::ExecutionNode;
+use crate::data::Schema;
+
+/// Checks how `nodes` are connected with each other and with the [super::NodeReader]s that
+/// are subscribed to `reader_sources`, and that every node finds the columns it reads in the
+/// schemas of its inputs.
+pub(crate) fn validate_plan<T: Send + 'static>(
+    nodes: &[ExecutionNode<T>],
+    reader_sources: &[String],
+) -> Result<(), ValidationError> {
+    let node_ids: HashSet<&String> = nodes.iter().map(|node| node.node_id()).collect();
+    let mut problems = vec![];
+
+    for node in nodes.iter() {
+        let subscriptions = node.subscriptions();
+        // Inputs of a source node are written directly, e.g., the files a reader reads.
+        if !subscriptions.is_empty() {
+            for channel in 0..node.num_inputs() {
+                if !subscriptions.iter().any(|(_, seq_no)| *seq_no == channel) {
+                    problems.push(PlanProblem::UnconnectedInput {
+                        node_id: node.node_id().clone(),
+                        channel,
+                    });
+                }
+            }
+        }
+        for (source_id, _) in subscriptions.iter() {
+            if !node_ids.contains(source_id) {
+                problems.push(PlanProblem::UnknownSource {
+                    node_id: node.node_id().clone(),
+                    source_id: source_id.clone(),
+                });
+            }
+        }
+    }
+
+    match find_cycle(nodes) {
+        Some(node_ids) => problems.push(PlanProblem::Cycle { node_ids }),
+        None => check_schemas(nodes, &mut problems),
+    }
+
+    for source_id in reader_sources.iter() {
+        if !node_ids.contains(source_id) {
+            problems.push(PlanProblem::UnknownReaderSource {
+                source_id: source_id.clone(),
+            });
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidationError::new(problems))
+    }
+}
+
+/// Derives the output schema of every node from the schemas of its inputs, upstream first.
+/// A node whose inputs are unknown, e.g., a source node without a declared schema, checks
+/// nothing against them.
+fn check_schemas<T: Send + 'static>(nodes: &[ExecutionNode<T>], problems: &mut Vec<PlanProblem>) {
+    let by_id: HashMap<&String, &ExecutionNode<T>> =
+        nodes.iter().map(|node| (node.node_id(), node)).collect();
+    let mut schemas = HashMap::new();
+    for node in nodes.iter() {
+        derive_schema(node, &by_id, &mut schemas, problems);
+    }
+}
+
+/// Requires the nodes to be free of cycles.
+fn derive_schema<T: Send + 'static>(
+    node: &ExecutionNode<T>,
+    by_id: &HashMap<&String, &ExecutionNode<T>>,
+    schemas: &mut HashMap<String, Option<Schema>>,
+    problems: &mut Vec<PlanProblem>,
+) -> Option<Schema> {
+    if let Some(schema) = schemas.get(node.node_id()) {
+        return schema.clone();
+    }
+    let mut input_schemas = vec![None; node.num_inputs()];
+    for (source_id, channel) in node.subscriptions() {
+        if let Some(source) = by_id.get(&source_id) {
+            input_schemas[channel] = derive_schema(source, by_id, schemas, problems);
+        }
+    }
+    let schema = node
+        .output_schema(&input_schemas)
+        .unwrap_or_else(|message| {
+            problems.push(PlanProblem::SchemaMismatch {
+                node_id: node.node_id().clone(),
+                message,
+            });
+            None
+        });
+    schemas.insert(node.node_id().clone(), schema.clone());
+    schema
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Visit {
+    InProgress,
+    Done,
+}
+
+/// Returns the nodes of a cycle in the order data flows through them, if there is one.
+fn find_cycle<T: Send + 'static>(nodes: &[ExecutionNode<T>]) -> Option<Vec<String>> {
+    let mut downstream: HashMap<String, Vec<String>> = HashMap::new();
+    for node in nodes.iter() {
+        for (source_id, _) in node.subscriptions() {
+            downstream
+                .entry(source_id)
+                .or_default()
+                .push(node.node_id().clone());
+        }
+    }
+    let mut visits: HashMap<String, Visit> = HashMap::new();
+    let mut path = vec![];
+    nodes
+        .iter()
+        .find_map(|node| visit(node.node_id(), &downstream, &mut visits, &mut path))
+}
+
+fn visit(
+    node_id: &String,
+    downstream: &HashMap<String, Vec<String>>,
+    visits: &mut HashMap<String, Visit>,
+    path: &mut Vec<String>,
+) -> Option<Vec<String>> {
+    match visits.get(node_id) {
+        Some(Visit::Done) => return None,
+        Some(Visit::InProgress) => {
+            let start = path.iter().position(|id| id == node_id).unwrap();
+            let mut cycle = path[start..].to_vec();
+            cycle.push(node_id.clone());
+            return Some(cycle);
+        }
+        None => (),
+    }
+    visits.insert(node_id.clone(), Visit::InProgress);
+    path.push(node_id.clone());
+    for next in downstream.get(node_id).into_iter().flatten() {
+        if let Some(cycle) = visit(next, downstream, visits, path) {
+            return Some(cycle);
+        }
+    }
+    path.pop();
+    visits.insert(node_id.clone(), Visit::Done);
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel::{MultiChannelBroadcaster, MultiChannelReader};
+    use crate::graph::{ExecutionService, NodeReader};
+    use crate::processor::{SimpleMapper, StreamProcessor};
+
+    fn mapper() -> ExecutionNode<String> {
+        ExecutionNode::from(SimpleMapper::from(|r: &String| Some(r.clone())))
+    }
+
+    /// Derives its output schema by selecting some columns of its input.
+    struct Select(Vec<String>);
+
+    impl StreamProcessor<String> for Select {
+        fn process_stream(
+            &self,
+            _: MultiChannelReader<String>,
+            _: MultiChannelBroadcaster<String>,
+        ) {
+        }
+
+        fn output_schema(
+            &self,
+            input_schemas: &[Option<Schema>],
+        ) -> Result<Option<Schema>, String> {
+            let input = match &input_schemas[0] {
+                Some(input) => input,
+                None => return Ok(None),
+            };
+            input.require_columns("Select", &self.0)?;
+            let columns = self
+                .0
+                .iter()
+                .map(|column| input.get_column(column))
+                .collect();
+            Ok(Some(Schema::new(input.table.clone(), columns)))
+        }
+    }
+
+    fn select(columns: &[&str]) -> ExecutionNode<String> {
+        let columns = columns.iter().map(|column| column.to_string()).collect();
+        ExecutionNode::new(Box::new(Select(columns)), 1)
+    }
+
+    #[test]
+    fn propagates_schemas() {
+        let source = mapper();
+        source.declare_output_schema(Schema::from_example("lineitem").unwrap());
+        let first = select(&["l_orderkey", "l_suppkey", "l_tax"]);
+        first.subscribe_to_node(&source, 0);
+        let second = select(&["l_orderkey", "l_partkey"]);
+        second.subscribe_to_node(&first, 0);
+        let sink = mapper();
+        sink.require_columns(0, &["l_orderkey_count".to_string()]);
+        sink.subscribe_to_node(&first, 0);
+        let schema = first
+            .output_schema(&[Schema::from_example("lineitem").ok()])
+            .unwrap()
+            .unwrap();
+        let names: Vec<&str> = schema
+            .columns
+            .iter()
+            .map(|column| column.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["l_orderkey", "l_suppkey", "l_tax"]);
+        let (second_id, sink_id) = (second.node_id().clone(), sink.node_id().clone());
+        let mut service = ExecutionService::create();
+        service.add(source);
+        service.add(first);
+        service.add(second);
+        service.add(sink);
+        let error = service.validate().unwrap_err();
+        assert_eq!(
+            error.problems(),
+            &vec![
+                PlanProblem::SchemaMismatch {
+                    node_id: second_id,
+                    message: "Select column l_partkey not present in input schema".into()
+                },
+                PlanProblem::SchemaMismatch {
+                    node_id: sink_id,
+                    message: "SimpleMapper column l_orderkey_count not present in input schema"
+                        .into()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_unknown_schemas() {
+        // Nothing is declared by the source, so nothing is known downstream.
+        let source = mapper();
+        let first = select(&["l_orderkey"]);
+        first.subscribe_to_node(&source, 0);
+        let sink = mapper();
+        sink.require_columns(0, &["l_partkey".to_string()]);
+        sink.subscribe_to_node(&first, 0);
+        let mut service = ExecutionService::create();
+        service.add(source);
+        service.add(first);
+        service.add(sink);
+        assert_eq!(service.validate(), Ok(()));
+    }
+
+    #[test]
+    fn declared_schema_takes_precedence() {
+        let node = select(&["l_orderkey"]);
+        let declared = Schema::from_example("orders").unwrap();
+        node.declare_output_schema(declared.clone());
+        assert_eq!(
+            node.output_schema(&[Schema::from_example("lineitem").ok()]),
+            Ok(Some(declared))
+        );
+        // The input is still checked.
+        assert!(node
+            .output_schema(&[Schema::from_example("orders").ok()])
+            .is_err());
+    }
+
+    #[test]
+    fn accepts_connected_plan() {
+        let source = mapper();
+        let sink = mapper();
+        sink.subscribe_to_node(&source, 0);
+        let reader = NodeReader::new(&sink);
+        let mut service = ExecutionService::create();
+        service.add(source);
+        service.add(sink);
+        service.add_reader(&reader);
+        assert_eq!(service.validate(), Ok(()));
+    }
+
+    #[test]
+    fn finds_unconnected_input() {
+        let source = mapper();
+        let join = ExecutionNode::<String>::new_double_inputs(Box::new(SimpleMapper::identity()));
+        join.subscribe_to_node(&source, 1);
+        let join_id = join.node_id().clone();
+        let mut service = ExecutionService::create();
+        service.add(source);
+        service.add(join);
+        let error = service.validate().unwrap_err();
+        assert_eq!(
+            error.problems(),
+            &vec![PlanProblem::UnconnectedInput {
+                node_id: join_id,
+                channel: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn finds_nodes_not_in_service() {
+        let source = mapper();
+        let sink = mapper();
+        sink.subscribe_to_node(&source, 0);
+        let reader = NodeReader::new(&source);
+        let (source_id, sink_id) = (source.node_id().clone(), sink.node_id().clone());
+        let mut service = ExecutionService::create();
+        service.add(sink);
+        service.add_reader(&reader);
+        let error = service.validate().unwrap_err();
+        assert_eq!(
+            error.problems(),
+            &vec![
+                PlanProblem::UnknownSource {
+                    node_id: sink_id,
+                    source_id: source_id.clone()
+                },
+                PlanProblem::UnknownReaderSource { source_id },
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_cycle() {
+        let source = mapper();
+        let first = ExecutionNode::<String>::new_double_inputs(Box::new(SimpleMapper::identity()));
+        let second = mapper();
+        first.subscribe_to_node(&source, 0);
+        first.subscribe_to_node(&second, 1);
+        second.subscribe_to_node(&first, 0);
+        let (first_id, second_id) = (first.node_id().clone(), second.node_id().clone());
+        let mut service = ExecutionService::create();
+        service.add(source);
+        service.add(first);
+        service.add(second);
+        let error = service.validate().unwrap_err();
+        assert_eq!(
+            error.problems(),
+            &vec![PlanProblem::Cycle {
+                node_ids: vec![first_id.clone(), second_id, first_id]
+            }]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "has no input for channel 0")]
+    fn run_rejects_invalid_plan() {
+        let source = mapper();
+        let join = ExecutionNode::<String>::new_double_inputs(Box::new(SimpleMapper::identity()));
+        join.subscribe_to_node(&source, 1);
+        let mut service = ExecutionService::create();
+        service.add(source);
+        service.add(join);
+        service.run();
+    }
+
+    #[test]
+    fn try_run_returns_invalid_plan() {
+        let source = mapper();
+        let join = ExecutionNode::<String>::new_double_inputs(Box::new(SimpleMapper::identity()));
+        join.subscribe_to_node(&source, 1);
+        let join_id = join.node_id().clone();
+        let mut service = ExecutionService::create();
+        service.add(source);
+        service.add(join);
+        let error = service.try_run().err().unwrap();
+        assert_eq!(
+            error.problems(),
+            &vec![PlanProblem::UnconnectedInput {
+                node_id: join_id,
+                channel: 0
+            }]
+        );
+        // The service did not run, and still holds the nodes.
+        assert_eq!(service.nodes().len(), 2);
+        assert!(service.try_run().is_err());
+    }
+}
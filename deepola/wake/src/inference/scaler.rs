@@ -8,6 +8,8 @@ use std::rc::Rc;
 
 use crate::channel::MultiChannelBroadcaster;
 use crate::channel::MultiChannelReader;
+use crate::data::Column;
+use crate::data::Schema;
 use crate::data::DEFAULT_GROUP_COLUMN_COUNT;
 use crate::graph::ExecutionNode;
 use crate::inference::count::PowerCardinalityEstimator;
@@ -273,6 +275,57 @@ impl MessageFractionProcessor<DataFrame> for AggregateScaler {
             self.process(df, fraction)
         }
     }
+
+    /// The input with the scaled aggregates turned into floats, followed by the propagated
+    /// variances if tracked.
+    fn output_schema(&self, input_schema: &Schema) -> Result<Option<Schema>, String> {
+        input_schema.require_columns(
+            "AggregateScaler count_column",
+            std::slice::from_ref(&self.count_col),
+        )?;
+        let scaled: Vec<String> = self
+            .aggregates
+            .iter()
+            .map(|(column, _)| column.clone())
+            .collect();
+        input_schema.require_columns("AggregateScaler aggregates", &scaled)?;
+
+        let mut columns = input_schema.columns.clone();
+        for column in scaled {
+            set_float_column(&mut columns, column);
+        }
+        if self.track_variance {
+            let sample_vars: Vec<String> = self
+                .track_variance_sum_col
+                .iter()
+                .map(|(_, sample_var_col)| sample_var_col.clone())
+                .collect();
+            input_schema.require_columns("AggregateScaler sample variances", &sample_vars)?;
+            for (sum_col, _) in &self.track_variance_sum_col {
+                set_float_column(&mut columns, format!("{}_var", sum_col));
+            }
+            for (sum_col_1, sum_col_2) in &self.track_covariance_sum_sum_col {
+                set_float_column(&mut columns, format!("{}_{}_cov", sum_col_1, sum_col_2));
+            }
+            for count_col in &self.track_variance_count_col {
+                set_float_column(&mut columns, format!("{}_var", count_col));
+            }
+            columns.retain(|column| !sample_vars.contains(&column.name));
+        }
+        if self.remove_count_col {
+            columns.retain(|column| column.name != self.count_col);
+        }
+        Ok(Some(Schema::new(input_schema.table.clone(), columns)))
+    }
+}
+
+/// Replaces the column `name` with a float column, like [DataFrame::with_column], or appends
+/// one.
+fn set_float_column(columns: &mut Vec<Column>, name: String) {
+    match columns.iter_mut().find(|column| column.name == name) {
+        Some(column) => column.dtype = crate::data::DataType::Float,
+        None => columns.push(Column::from_field(name, crate::data::DataType::Float)),
+    }
 }
 
 
@@ -6,9 +6,12 @@ use polars::prelude::*;
 
 use crate::channel::MultiChannelBroadcaster;
 use crate::channel::MultiChannelReader;
+use crate::data::Column;
+use crate::data::Schema;
 use crate::data::DEFAULT_GROUP_COLUMN;
 use crate::data::DEFAULT_GROUP_COLUMN_COUNT;
 use crate::data::DEFAULT_GROUPBY_KEY;
+use crate::graph::OperatorState;
 use crate::processor::MessageFractionProcessor;
 use crate::processor::StreamProcessor;
 use super::AccumulatorOp;
@@ -204,6 +207,15 @@ impl AggAccumulator {
         }
     }
 
+    /// The type of the aggregate `op` of a column of type `dtype`.
+    fn aggregate_dtype(op: &str, dtype: &crate::data::DataType) -> crate::data::DataType {
+        match op {
+            "count" | "n_unique" => crate::data::DataType::UnsignedInt,
+            "mean" | "median" | "var" | "std" => crate::data::DataType::Float,
+            _ => dtype.clone(),
+        }
+    }
+
     fn extract_pair(series: &Series) -> (f64, f64) {
         let elems: Vec<f64> = series.f64().unwrap().into_no_null_iter().collect();
         assert_eq!(elems.len(), 2);
@@ -258,6 +270,58 @@ impl MessageFractionProcessor<DataFrame> for AggAccumulator {
 }
 
 impl StreamProcessor<DataFrame> for AggAccumulator {
+    /// The group key, then the aggregates in the order of [Self::aggregates], each named
+    /// `<column>_<op>`, followed by the variance of a sum if tracked. The count of the groups
+    /// comes last, if added. A scaler, if set, then derives its output from these columns.
+    fn output_schema(
+        &self,
+        input_schemas: &[Option<Schema>],
+    ) -> std::result::Result<Option<Schema>, String> {
+        let input = match &input_schemas[0] {
+            Some(input) => input,
+            None => return Ok(None),
+        };
+        input.require_columns("AggAccumulator group_key", &self.group_key)?;
+        let aggregated: Vec<String> = self
+            .aggregates
+            .iter()
+            .map(|(column, _)| column.clone())
+            .collect();
+        input.require_columns("AggAccumulator aggregates", &aggregated)?;
+
+        let mut columns: Vec<Column> = self
+            .group_key
+            .iter()
+            .map(|key| input.get_column(key))
+            .collect();
+        for (column, ops) in self.aggregates.iter() {
+            let dtype = input.dtype(column);
+            for op in ops {
+                columns.push(Column::from_field(
+                    format!("{}_{}", column, op),
+                    AggAccumulator::aggregate_dtype(op, &dtype),
+                ));
+            }
+            if self.track_variance && ops.contains(&"sum".to_string()) {
+                columns.push(Column::from_field(
+                    format!("{}_var", column),
+                    crate::data::DataType::Float,
+                ));
+            }
+        }
+        if self.track_variance || self.add_count_column {
+            columns.push(Column::from_field(
+                DEFAULT_GROUP_COLUMN_COUNT.into(),
+                crate::data::DataType::UnsignedInt,
+            ));
+        }
+        let accumulated = Schema::new(input.table.clone(), columns);
+        match &self.scaler {
+            Some(scaler) => scaler.output_schema(&accumulated),
+            None => Ok(Some(accumulated)),
+        }
+    }
+
     fn process_stream(
         &self,
         input_stream: MultiChannelReader<DataFrame>,
@@ -265,6 +329,16 @@ impl StreamProcessor<DataFrame> for AggAccumulator {
     ) {
         self.process_stream_inner(input_stream, output_stream)
     }
+
+    fn checkpoint(&self, _channel: usize, state: &mut OperatorState) {
+        state.put_frame("accumulated", self.accumulated.borrow().clone());
+    }
+
+    fn restore(&mut self, state: &OperatorState) {
+        if let Some(df) = state.frame("accumulated") {
+            *self.accumulated.borrow_mut() = df.clone();
+        }
+    }
 }
 
 
@@ -273,9 +347,11 @@ mod tests {
     use polars::prelude::*;
 
     use crate::{
+        data::BlockMetadata,
+        data::Column,
         data::DataBlock,
         data::DataMessage,
-        data::MetaCell,
+        data::Schema,
         graph::NodeReader,
         polars_operations::accumulator::AccumulatorNode,
         polars_operations::util::tests::truncate_df,
@@ -317,7 +393,7 @@ mod tests {
             .accumulator(sum_acc)
             .build();
         let input_df = get_example_df().select(["temp", "rain"]).unwrap();
-        let input_dblock = DataBlock::new(input_df, MetaCell::from("meta").into_meta_map());
+        let input_dblock = DataBlock::new(input_df, BlockMetadata::new().with_progress(1.0));
         sum_node.write_to_self(0, DataMessage::from(input_dblock));
         sum_node.write_to_self(0, DataMessage::eof());
         let reader_node = NodeReader::new(&sum_node);
@@ -515,4 +591,130 @@ mod tests {
         // │ 2020-08-21 ┆ 10        ┆ 0.1       │
         // └────────────┴───────────┴───────────┘
     }
+
+    #[test]
+    fn accumulator_output_schema() {
+        use crate::data::DataType::{Date, Float, Integer, UnsignedInt};
+
+        let mut acc = AggAccumulator::new();
+        acc.set_group_key(vec!["date".into()])
+            .set_aggregates(vec![
+                ("temp".into(), vec!["sum".into(), "count".into()]),
+                ("rain".into(), vec!["mean".into()]),
+            ])
+            .set_add_count_column(true);
+        let input = Schema::from(vec![
+            Column::from_field("date".into(), Date),
+            Column::from_field("temp".into(), Integer),
+            Column::from_field("rain".into(), Float),
+        ]);
+        let output = StreamProcessor::output_schema(&acc, &[Some(input.clone())])
+            .unwrap()
+            .unwrap();
+        let columns: Vec<(&str, crate::data::DataType)> = output
+            .columns
+            .iter()
+            .map(|column| (column.name.as_str(), column.dtype.clone()))
+            .collect();
+        assert_eq!(
+            columns,
+            vec![
+                ("date", Date),
+                ("temp_sum", Integer),
+                ("temp_count", UnsignedInt),
+                ("rain_mean", Float),
+                (DEFAULT_GROUP_COLUMN_COUNT, UnsignedInt),
+            ]
+        );
+
+        acc.set_group_key(vec!["city".into()]);
+        assert_eq!(
+            StreamProcessor::output_schema(&acc, &[Some(input)]),
+            Err("AggAccumulator group_key column city not present in input schema".into())
+        );
+    }
+
+    #[test]
+    fn scaled_accumulator_output_schema() {
+        use crate::data::DataType::{Date, Float, Integer};
+        use crate::inference::AggregateScaler;
+
+        let mut acc = AggAccumulator::new();
+        acc.set_group_key(vec!["date".into()])
+            .set_aggregates(vec![("temp".into(), vec!["sum".into(), "count".into()])])
+            .set_scaler(
+                AggregateScaler::new_growing()
+                    .count_column("temp_count".into())
+                    .scale_count("temp_count".into())
+                    .scale_sum("temp_sum".into())
+                    .into_rc(),
+            );
+        let input = Schema::from(vec![
+            Column::from_field("date".into(), Date),
+            Column::from_field("temp".into(), Integer),
+        ]);
+        let output = StreamProcessor::output_schema(&acc, &[Some(input)])
+            .unwrap()
+            .unwrap();
+        let columns: Vec<(&str, crate::data::DataType)> = output
+            .columns
+            .iter()
+            .map(|column| (column.name.as_str(), column.dtype.clone()))
+            .collect();
+        assert_eq!(
+            columns,
+            vec![("date", Date), ("temp_sum", Float), ("temp_count", Float)]
+        );
+    }
+
+    /// As in TPC-H q1, but counting a column other than the one the scaler and the select
+    /// node read.
+    #[test]
+    fn rejects_missing_scaled_count() {
+        use crate::graph::{ExecutionService, PlanProblem};
+        use crate::inference::AggregateScaler;
+        use crate::polars_operations::{AppenderNode, MapAppender};
+
+        let source = AppenderNode::<DataFrame, MapAppender>::new()
+            .output_schema(Schema::from_example("lineitem").unwrap())
+            .build();
+        let mut acc = AggAccumulator::new();
+        acc.set_group_key(vec!["l_returnflag".into(), "l_linestatus".into()])
+            .set_aggregates(vec![
+                ("l_quantity".into(), vec!["count".into(), "sum".into()]),
+                ("l_extendedprice".into(), vec!["sum".into()]),
+            ])
+            .set_scaler(
+                AggregateScaler::new_growing()
+                    .count_column("l_orderkey_count".into())
+                    .scale_count("l_orderkey_count".into())
+                    .scale_sum("l_quantity_sum".into())
+                    .scale_sum("l_extendedprice_sum".into())
+                    .into_rc(),
+            );
+        let groupby = AccumulatorNode::<DataFrame, AggAccumulator>::new()
+            .accumulator(acc)
+            .build();
+        groupby.subscribe_to_node(&source, 0);
+        let select = AppenderNode::<DataFrame, MapAppender>::new()
+            .required_columns(vec!["l_quantity_sum".into(), "l_orderkey_count".into()])
+            .build();
+        select.subscribe_to_node(&groupby, 0);
+        let groupby_id = groupby.node_id().clone();
+
+        let mut service = ExecutionService::create();
+        service.add(source);
+        service.add(groupby);
+        service.add(select);
+        let error = service.validate().unwrap_err();
+        assert_eq!(
+            error.problems(),
+            &vec![PlanProblem::SchemaMismatch {
+                node_id: groupby_id,
+                message: "AggregateScaler count_column column l_orderkey_count not present in \
+                    input schema"
+                    .into()
+            }]
+        );
+    }
 }
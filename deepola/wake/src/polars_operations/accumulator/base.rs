@@ -2,6 +2,7 @@ use std::marker::PhantomData;
 
 use getset::{Getters, Setters};
 
+use crate::data::Schema;
 use crate::graph::ExecutionNode;
 use crate::processor::StreamProcessor;
 
@@ -11,6 +12,9 @@ pub struct AccumulatorNode<T, P: AccumulatorOp<T>> {
     #[set = "pub"]
     accumulator: P,
 
+    /// See [Self::output_schema].
+    schema: Option<Schema>,
+
     // Necessary to have T as a generic type
     phantom: PhantomData<T>,
 }
@@ -20,6 +24,7 @@ impl<T, P: AccumulatorOp<T> + Clone> Default for AccumulatorNode<T, P> {
     fn default() -> Self {
         Self {
             accumulator: P::new(),
+            schema: None,
             phantom: PhantomData::default(),
         }
     }
@@ -38,9 +43,20 @@ where
         self
     }
 
+    /// Declares the schema of the frames the node writes, in place of the one the accumulator
+    /// derives, if any. See [ExecutionNode::declare_output_schema].
+    pub fn output_schema(&mut self, schema: Schema) -> &mut Self {
+        self.schema = Some(schema);
+        self
+    }
+
     pub fn build(&self) -> ExecutionNode<T> {
         let data_processor = Box::new(self.accumulator.clone());
-        ExecutionNode::<T>::new(data_processor, 1)
+        let node = ExecutionNode::<T>::new(data_processor, 1);
+        if let Some(schema) = &self.schema {
+            node.declare_output_schema(schema.clone());
+        }
+        node
     }
 }
 
@@ -4,6 +4,7 @@ use getset::{Getters, Setters};
 use polars::prelude::*;
 
 use super::AccumulatorOp;
+use crate::graph::OperatorState;
 use crate::processor::MessageProcessor;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -85,4 +86,14 @@ impl MessageProcessor<DataFrame> for MergeAccumulator {
     fn post_process_msg(&self) -> Option<DataFrame> {
         Some(self.post_process_result())
     }
+
+    fn checkpoint_msg(&self, state: &mut OperatorState) {
+        state.put_frame("accumulated", self.accumulated.borrow().clone());
+    }
+
+    fn restore_msg(&mut self, state: &OperatorState) {
+        if let Some(df) = state.frame("accumulated") {
+            *self.accumulated.borrow_mut() = df.clone();
+        }
+    }
 }
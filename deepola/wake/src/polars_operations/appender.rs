@@ -3,7 +3,7 @@ use std::{marker::PhantomData, sync::Arc};
 use getset::{Getters, Setters};
 use polars::prelude::DataFrame;
 
-use crate::{graph::ExecutionNode, processor::MessageProcessor};
+use crate::{data::Schema, graph::ExecutionNode, processor::MessageProcessor};
 
 /// Factory class for generating an Appender-type execution node, which is designed to support
 /// [AppenderOp] transformation.
@@ -18,6 +18,12 @@ where
     #[set = "pub"]
     appender: P,
 
+    /// See [Self::output_schema].
+    schema: Option<Schema>,
+
+    /// See [Self::required_columns].
+    required_columns: Vec<String>,
+
     // Necessary to have T as a generic type
     phantom: PhantomData<T>,
 }
@@ -26,6 +32,8 @@ impl<T, P: AppenderOp<T>> Default for AppenderNode<T, P> {
     fn default() -> Self {
         Self {
             appender: P::new(),
+            schema: None,
+            required_columns: vec![],
             phantom: PhantomData::default(),
         }
     }
@@ -55,9 +63,29 @@ where
         self
     }
 
+    /// Declares the schema of the frames the appender writes, which cannot be told from its
+    /// closure. See [ExecutionNode::declare_output_schema].
+    pub fn output_schema(&mut self, schema: Schema) -> &mut Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    /// Declares the columns the closure of the appender reads, so that a plan in which the
+    /// input lacks one of them is rejected before it runs. See
+    /// [ExecutionNode::require_columns].
+    pub fn required_columns(&mut self, columns: Vec<String>) -> &mut Self {
+        self.required_columns = columns;
+        self
+    }
+
     pub fn build(&self) -> ExecutionNode<T> {
         let data_processor = self.appender.clone();
-        ExecutionNode::<T>::new(Box::new(data_processor), 1)
+        let node = ExecutionNode::<T>::new(Box::new(data_processor), 1);
+        if let Some(schema) = &self.schema {
+            node.declare_output_schema(schema.clone());
+        }
+        node.require_columns(0, &self.required_columns);
+        node
     }
 }
 
@@ -0,0 +1,515 @@
+use polars::prelude::DataType;
+use polars::prelude::*;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::channel::{MultiChannelBroadcaster, MultiChannelReader};
+use crate::data::Schema;
+use crate::data::*;
+use crate::graph::ExecutionNode;
+use crate::processor::StreamProcessor;
+use crate::utils::log_event;
+
+/// Factory for creating an [ExecutionNode] that hash-partitions the rows of a stream by key
+/// columns, so that several instances of an operator can process the stream in parallel.
+///
+/// The i-th node subscribed to the exchange node receives partition i, so the instances must
+/// be the only subscribers (see [connect_partitions]). Every instance receives a block for
+/// every input block, possibly empty, with the metadata of the input block. Rows with the same
+/// key always end up in the same partition.
+#[derive(Default)]
+pub struct ExchangeBuilder {
+    key_columns: Vec<String>,
+}
+
+impl ExchangeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn key_columns(&mut self, key_columns: Vec<String>) -> &mut Self {
+        self.key_columns = key_columns;
+        self
+    }
+
+    pub fn build(&self) -> ExecutionNode<DataFrame> {
+        let exchange = Exchange {
+            key_columns: self.key_columns.clone(),
+        };
+        ExecutionNode::<DataFrame>::new(Box::new(exchange), 1)
+    }
+}
+
+struct Exchange {
+    key_columns: Vec<String>,
+}
+
+impl Exchange {
+    fn partition(&self, df: &DataFrame, num_partitions: usize) -> Vec<DataFrame> {
+        if num_partitions == 1 {
+            return vec![df.clone()];
+        }
+        let mut hashes = vec![0u64; df.height()];
+        for column in self.key_columns.iter() {
+            let series = df.column(column).unwrap();
+            let physical = series.to_physical_repr();
+            if let DataType::Utf8 = physical.dtype() {
+                combine_hashes(&mut hashes, physical.utf8().unwrap().into_iter());
+            } else {
+                // Equal values stay equal after the cast, which is all partitioning needs.
+                let values = physical.cast(&DataType::Int64).unwrap();
+                combine_hashes(&mut hashes, values.i64().unwrap().into_iter());
+            }
+        }
+        let assignments: Vec<usize> = hashes
+            .iter()
+            .map(|hash| (hash % num_partitions as u64) as usize)
+            .collect();
+        (0..num_partitions)
+            .map(|partition| {
+                let mask: Vec<bool> = assignments.iter().map(|a| *a == partition).collect();
+                df.filter(&BooleanChunked::new("mask", mask)).unwrap()
+            })
+            .collect()
+    }
+}
+
+fn combine_hashes<V: Hash>(hashes: &mut [u64], values: impl Iterator<Item = V>) {
+    for (hash, value) in hashes.iter_mut().zip(values) {
+        let mut hasher = DefaultHasher::new();
+        hash.hash(&mut hasher);
+        value.hash(&mut hasher);
+        *hash = hasher.finish();
+    }
+}
+
+impl StreamProcessor<DataFrame> for Exchange {
+    /// The input schema: partitioning only splits the rows.
+    fn output_schema(
+        &self,
+        input_schemas: &[Option<Schema>],
+    ) -> std::result::Result<Option<Schema>, String> {
+        if let Some(input) = &input_schemas[0] {
+            input.require_columns("ExchangeBuilder key_columns", &self.key_columns)?;
+        }
+        Ok(input_schemas[0].clone())
+    }
+
+    fn process_stream(
+        &self,
+        input_stream: MultiChannelReader<DataFrame>,
+        output_stream: MultiChannelBroadcaster<DataFrame>,
+    ) {
+        assert!(
+            !output_stream.is_empty(),
+            "An exchange node needs at least one subscribed partition"
+        );
+        loop {
+            let message = input_stream.read(0);
+            log_event("process-message", "start");
+            match message.payload() {
+                Payload::EOF | Payload::Signal(Signal::STOP) | Payload::Error(_) => {
+                    output_stream.write(message);
+                    log_event("process-message", "end");
+                    break;
+                }
+                Payload::Signal(_) => {
+                    log_event("process-message", "end");
+                }
+                Payload::Some(dblock) => {
+                    let partitions = self.partition(dblock.data(), output_stream.len());
+                    for (seq_no, df) in partitions.into_iter().enumerate() {
+                        let output_dblock = DataBlock::new(df, dblock.metadata().clone());
+                        output_stream.write_to(seq_no, DataMessage::from(output_dblock));
+                    }
+                    log_event("process-message", "end");
+                }
+            }
+        }
+    }
+}
+
+/// How a gather node combines the streams of the partitions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GatherMode {
+    /// Every block is passed on as is, e.g., after a filter or a join.
+    Union,
+
+    /// Each block replaces the previous block of its partition, and the latest blocks of all
+    /// partitions are passed on together, e.g., after an [crate::polars_operations::AggAccumulator]
+    /// grouping by the exchange keys.
+    Latest,
+}
+
+/// Factory for creating an [ExecutionNode] that merges the streams of the partitions created
+/// by an exchange node (see [ExchangeBuilder]) back into a single stream.
+///
+/// The [BlockMetadata::progress] of an output block is the smallest progress of the partitions
+/// thus far, so that it never overstates how much of the input has been processed. A partition
+/// that has not reported any progress yet counts as 0, and one that has ended as 1.
+/// The gather node ends with EOF once every partition has ended, or with STOP if any of them
+/// was stopped.
+pub struct GatherBuilder {
+    num_partitions: usize,
+    mode: GatherMode,
+}
+
+impl Default for GatherBuilder {
+    fn default() -> Self {
+        GatherBuilder {
+            num_partitions: 1,
+            mode: GatherMode::Union,
+        }
+    }
+}
+
+impl GatherBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn num_partitions(&mut self, num_partitions: usize) -> &mut Self {
+        self.num_partitions = num_partitions;
+        self
+    }
+
+    pub fn mode(&mut self, mode: GatherMode) -> &mut Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn build(&self) -> ExecutionNode<DataFrame> {
+        let gather = Gather { mode: self.mode };
+        ExecutionNode::<DataFrame>::new(Box::new(gather), self.num_partitions)
+    }
+}
+
+struct Gather {
+    mode: GatherMode,
+}
+
+impl Gather {
+    fn merge(latest: &[Option<DataFrame>]) -> DataFrame {
+        let mut frames = latest.iter().flatten();
+        let mut merged = frames.next().unwrap().clone();
+        for df in frames {
+            merged.vstack_mut(df).unwrap();
+        }
+        merged
+    }
+}
+
+impl StreamProcessor<DataFrame> for Gather {
+    /// The schema of the partitions, which must have the same columns. Unknown schemas are
+    /// taken to be the same as the known ones.
+    fn output_schema(
+        &self,
+        input_schemas: &[Option<Schema>],
+    ) -> std::result::Result<Option<Schema>, String> {
+        let mut known = input_schemas
+            .iter()
+            .enumerate()
+            .filter_map(|(partition, schema)| schema.as_ref().map(|schema| (partition, schema)));
+        let (first_partition, first) = match known.next() {
+            Some(first) => first,
+            None => return Ok(None),
+        };
+        for (partition, schema) in known {
+            if schema.columns != first.columns {
+                return Err(format!(
+                    "GatherBuilder partition {} has other columns than partition {}",
+                    partition, first_partition
+                ));
+            }
+        }
+        Ok(Some(first.clone()))
+    }
+
+    fn process_stream(
+        &self,
+        input_stream: MultiChannelReader<DataFrame>,
+        output_stream: MultiChannelBroadcaster<DataFrame>,
+    ) {
+        let num_partitions = input_stream.readers.len();
+        let mut open: Vec<usize> = (0..num_partitions).collect();
+        let mut latest: Vec<Option<DataFrame>> = vec![None; num_partitions];
+        let mut progress: Vec<Option<f64>> = vec![None; num_partitions];
+        let mut stopped = false;
+        while !open.is_empty() {
+            let (seq_no, message) = input_stream.read_any(&open);
+            log_event("process-message", "start");
+            match message.payload() {
+                Payload::EOF => {
+                    progress[seq_no] = Some(1.0);
+                    open.retain(|s| *s != seq_no);
+                }
+                Payload::Signal(Signal::STOP) => {
+                    stopped = true;
+                    open.retain(|s| *s != seq_no);
+                }
+                Payload::Error(_) => {
+                    // The union of the partitions would be incomplete.
+                    output_stream.write(message);
+                    log_event("process-message", "end");
+                    return;
+                }
+                Payload::Signal(_) => (),
+                Payload::Some(dblock) => {
+                    let mut metadata = dblock.metadata().clone();
+                    if let Some(cardinality) = metadata.progress() {
+                        progress[seq_no] = Some(cardinality);
+                        // Partitions that have not reported yet have made no progress.
+                        let least_progress = progress
+                            .iter()
+                            .map(|p| p.unwrap_or(0.0))
+                            .fold(1.0, f64::min);
+                        metadata.set_progress(least_progress);
+                    }
+                    let output_df = match self.mode {
+                        GatherMode::Union => dblock.data().clone(),
+                        GatherMode::Latest => {
+                            latest[seq_no] = Some(dblock.data().clone());
+                            Gather::merge(&latest)
+                        }
+                    };
+                    output_stream.write(DataMessage::from(DataBlock::new(output_df, metadata)));
+                }
+            }
+            log_event("process-message", "end");
+        }
+        if stopped {
+            output_stream.write(DataMessage::stop());
+        } else {
+            output_stream.write(DataMessage::eof());
+        }
+    }
+}
+
+/// Connects `instances` between `exchange` and `gather`: instance i reads partition i and
+/// writes to input channel i of `gather`.
+pub fn connect_partitions(
+    exchange: &ExecutionNode<DataFrame>,
+    instances: &[ExecutionNode<DataFrame>],
+    gather: &ExecutionNode<DataFrame>,
+) {
+    assert_eq!(
+        instances.len(),
+        gather.num_inputs(),
+        "The gather node needs an input channel for each instance"
+    );
+    for (seq_no, instance) in instances.iter().enumerate() {
+        instance.subscribe_to_node(exchange, 0);
+        gather.subscribe_to_node(instance, seq_no);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::graph::{ExecutionService, NodeReader};
+    use crate::polars_operations::{AccumulatorNode, AggAccumulator};
+    use crate::processor::SimpleMapper;
+
+    fn keys(df: &DataFrame) -> HashSet<String> {
+        df.column("key")
+            .unwrap()
+            .utf8()
+            .unwrap()
+            .into_no_null_iter()
+            .map(|key| key.to_string())
+            .collect()
+    }
+
+    fn example_df() -> DataFrame {
+        df!(
+            "key" => &["a", "b", "c", "a", "d", "b", "e", "a"],
+            "value" => &[1, 2, 3, 4, 5, 6, 7, 8],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn partitions_by_key() {
+        let exchange = Exchange {
+            key_columns: vec!["key".into()],
+        };
+        let df = example_df();
+        let partitions = exchange.partition(&df, 3);
+        assert_eq!(partitions.len(), 3);
+        assert_eq!(
+            partitions.iter().map(|p| p.height()).sum::<usize>(),
+            df.height()
+        );
+        for (i, left) in partitions.iter().enumerate() {
+            for right in partitions.iter().skip(i + 1) {
+                assert!(keys(left).is_disjoint(&keys(right)));
+            }
+        }
+    }
+
+    #[test]
+    fn passes_schema_through() {
+        let lineitem = Schema::from_example("lineitem").unwrap();
+        let exchange = ExchangeBuilder::new()
+            .key_columns(vec!["l_orderkey".into()])
+            .build();
+        assert_eq!(
+            exchange.output_schema(&[Some(lineitem.clone())]),
+            Ok(Some(lineitem.clone()))
+        );
+        let exchange = ExchangeBuilder::new()
+            .key_columns(vec!["o_orderkey".into()])
+            .build();
+        assert_eq!(
+            exchange.output_schema(&[Some(lineitem.clone())]),
+            Err("ExchangeBuilder key_columns column o_orderkey not present in input schema".into())
+        );
+
+        let gather = GatherBuilder::new().num_partitions(3).build();
+        assert_eq!(gather.output_schema(&[None, None, None]), Ok(None));
+        assert_eq!(
+            gather.output_schema(&[None, Some(lineitem.clone()), Some(lineitem.clone())]),
+            Ok(Some(lineitem.clone()))
+        );
+        let orders = Schema::from_example("orders").unwrap();
+        assert_eq!(
+            gather.output_schema(&[None, Some(lineitem), Some(orders)]),
+            Err("GatherBuilder partition 2 has other columns than partition 1".into())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one subscribed partition")]
+    fn exchange_requires_a_partition() {
+        let exchange = Exchange {
+            key_columns: vec!["key".into()],
+        };
+        exchange.process_stream(MultiChannelReader::new(), MultiChannelBroadcaster::new());
+    }
+
+    #[test]
+    fn gather_counts_silent_partitions_as_no_progress() {
+        let gather = GatherBuilder::new().num_partitions(2).build();
+        let df = example_df();
+        let metadata = BlockMetadata::new().with_progress(1.0);
+        gather.write_to_self(0, DataMessage::from(DataBlock::new(df.clone(), metadata)));
+        gather.write_to_self(0, DataMessage::eof());
+        let metadata = BlockMetadata::new().with_progress(0.5);
+        gather.write_to_self(1, DataMessage::from(DataBlock::new(df, metadata)));
+        gather.write_to_self(1, DataMessage::eof());
+        let reader_node = NodeReader::new(&gather);
+
+        let mut service = ExecutionService::create();
+        service.add(gather);
+        service.run();
+        let mut progress = vec![];
+        loop {
+            let message = reader_node.read();
+            if message.is_eof() {
+                break;
+            }
+            progress.push(message.datablock().metadata().progress().unwrap());
+        }
+        service.join().unwrap();
+        // Whichever partition is read first, the other one has not reported yet.
+        assert_eq!(progress, vec![0.0, 0.5]);
+    }
+
+    #[test]
+    fn partitions_by_numeric_key() {
+        let exchange = Exchange {
+            key_columns: vec!["value".into()],
+        };
+        let df = example_df();
+        let partitions = exchange.partition(&df, 4);
+        assert_eq!(
+            partitions.iter().map(|p| p.height()).sum::<usize>(),
+            df.height()
+        );
+    }
+
+    fn run_partitioned(
+        instances: Vec<ExecutionNode<DataFrame>>,
+        mode: GatherMode,
+    ) -> Vec<DataBlock<DataFrame>> {
+        let exchange = ExchangeBuilder::new()
+            .key_columns(vec!["key".into()])
+            .build();
+        let gather = GatherBuilder::new()
+            .num_partitions(instances.len())
+            .mode(mode)
+            .build();
+        connect_partitions(&exchange, &instances, &gather);
+        let df = example_df();
+        for (offset, cardinality) in [(0, 0.5), (4, 1.0)] {
+            let metadata = BlockMetadata::new().with_progress(cardinality);
+            let dblock = DataBlock::new(df.slice(offset, 4), metadata);
+            exchange.write_to_self(0, DataMessage::from(dblock));
+        }
+        exchange.write_to_self(0, DataMessage::eof());
+        let reader_node = NodeReader::new(&gather);
+
+        let mut service = ExecutionService::create();
+        service.add(exchange);
+        for instance in instances {
+            service.add(instance);
+        }
+        service.add(gather);
+        service.run();
+        let mut output = vec![];
+        loop {
+            let message = reader_node.read();
+            if message.is_eof() {
+                break;
+            }
+            output.push(message.datablock().clone());
+        }
+        service.join().unwrap();
+        output
+    }
+
+    #[test]
+    fn gathers_union_of_partitions() {
+        let instances = (0..3)
+            .map(|_| ExecutionNode::from(SimpleMapper::<DataFrame>::identity()))
+            .collect();
+        let output = run_partitioned(instances, GatherMode::Union);
+        // Each of the 3 instances sees both input blocks.
+        assert_eq!(output.len(), 6);
+        let total_rows: usize = output.iter().map(|dblock| dblock.data().height()).sum();
+        assert_eq!(total_rows, 8);
+        let progress: Vec<f64> = output
+            .iter()
+            .map(|dblock| dblock.metadata().progress().unwrap())
+            .collect();
+        assert!(progress.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(progress.last(), Some(&1.0));
+    }
+
+    #[test]
+    fn gathers_latest_aggregates() {
+        let instances = (0..2)
+            .map(|_| {
+                let mut agg_accumulator = AggAccumulator::new();
+                agg_accumulator
+                    .set_group_key(vec!["key".into()])
+                    .set_aggregates(vec![("value".into(), vec!["sum".into()])]);
+                AccumulatorNode::<DataFrame, AggAccumulator>::new()
+                    .accumulator(agg_accumulator)
+                    .build()
+            })
+            .collect();
+        let output = run_partitioned(instances, GatherMode::Latest);
+        let last = output.last().unwrap();
+        assert_eq!(last.metadata().progress(), Some(1.0));
+        let result = last.data().sort(["key"], false).unwrap();
+        let expected = df!(
+            "key" => &["a", "b", "c", "d", "e"],
+            "value_sum" => &[13, 8, 3, 5, 7],
+        )
+        .unwrap();
+        assert_eq!(result, expected);
+    }
+}
@@ -1,8 +1,12 @@
 // use polars::series::Series;
 use polars::prelude::*;
 
+use std::cell::RefCell;
+
+use super::util::join_schema;
+use crate::data::Schema;
 use crate::data::*;
-use crate::graph::ExecutionNode;
+use crate::graph::{ExecutionNode, OperatorState};
 use crate::processor::StreamProcessor;
 use crate::utils::log_event;
 
@@ -53,7 +57,10 @@ impl HashJoinBuilder {
 struct HashJoinNode {
     left_on: Vec<String>,
     right_on: Vec<String>,
-    right_df: DataFrame,
+
+    /// Built from the right stream before the left stream is probed. [RefCell] lets the node
+    /// save it into a checkpoint while it is being built.
+    right_df: RefCell<DataFrame>,
     join_type: JoinType,
     swap: bool,
 }
@@ -65,25 +72,27 @@ impl HashJoinNode {
         HashJoinNode {
             left_on: left_on.to_owned(),
             right_on: right_on.to_owned(),
-            right_df: DataFrame::default(),
+            right_df: RefCell::new(DataFrame::default()),
             join_type: join_type.to_owned(),
             swap,
         }
     }
 
     // Read partitions from right stream and append to the existing right dataframe.
-    pub fn pre_process(&mut self, right_df: &DataFrame) {
-        self.right_df.vstack_mut(right_df).unwrap();
+    pub fn pre_process(&self, right_df: &DataFrame) {
+        let mut acc_right_df = self.right_df.borrow_mut();
+        acc_right_df.vstack_mut(right_df).unwrap();
         // Documentation of vstack_mut recommends rechunk if multiple vstack operations performed.
-        if self.right_df.should_rechunk() {
-            self.right_df.rechunk();
+        if acc_right_df.should_rechunk() {
+            acc_right_df.rechunk();
         }
     }
 
     // Compute Hash Join given left and right df.
     pub fn process(&self, left_df: &DataFrame) -> DataFrame {
+        let right_df = self.right_df.borrow();
         let mut final_left_df = left_df;
-        let mut final_right_df = &self.right_df;
+        let mut final_right_df = &*right_df;
         let mut left_on = self.left_on.clone();
         let mut right_on = self.right_on.clone();
 
@@ -103,10 +112,13 @@ impl HashJoinNode {
             )
             .unwrap()
     }
-}
 
-impl StreamProcessor<DataFrame> for HashJoinNode {
-    fn pre_process(&mut self, input_stream: crate::channel::MultiChannelReader<DataFrame>) {
+    /// Reads the right stream to the end. Returns the message that ended it early instead, if
+    /// it was stopped or failed before EOF; the join then forwards it without probing.
+    fn build(
+        &self,
+        input_stream: &crate::channel::MultiChannelReader<DataFrame>,
+    ) -> Option<DataMessage<DataFrame>> {
         loop {
             let channel_seq = 1;
             let message = input_stream.read(channel_seq);
@@ -114,11 +126,15 @@ impl StreamProcessor<DataFrame> for HashJoinNode {
             match message.payload() {
                 Payload::EOF => {
                     log_event("process-message", "end");
-                    break;
+                    return None;
+                }
+                Payload::Error(_) => {
+                    log_event("process-message", "end");
+                    return Some(message);
                 }
                 Payload::Signal(_) => {
                     log_event("process-message", "end");
-                    break;
+                    return Some(DataMessage::stop());
                 }
                 Payload::Some(dblock) => {
                     self.pre_process(dblock.data());
@@ -127,23 +143,49 @@ impl StreamProcessor<DataFrame> for HashJoinNode {
             }
         }
     }
+}
+
+impl StreamProcessor<DataFrame> for HashJoinNode {
+    /// The left (or, if swapped, the right) columns come first.
+    fn output_schema(
+        &self,
+        input_schemas: &[Option<Schema>],
+    ) -> std::result::Result<Option<Schema>, String> {
+        let (left, right) = (input_schemas[0].as_ref(), input_schemas[1].as_ref());
+        if let Some(left) = left {
+            left.require_columns("HashJoinBuilder left_on", &self.left_on)?;
+        }
+        if let Some(right) = right {
+            right.require_columns("HashJoinBuilder right_on", &self.right_on)?;
+        }
+        Ok(match (left, right) {
+            (Some(left), Some(right)) if self.swap => Some(join_schema(right, left, &self.left_on)),
+            (Some(left), Some(right)) => Some(join_schema(left, right, &self.right_on)),
+            _ => None,
+        })
+    }
 
     fn process_stream(
         &self,
         input_stream: crate::channel::MultiChannelReader<DataFrame>,
         output_stream: crate::channel::MultiChannelBroadcaster<DataFrame>,
     ) {
+        if let Some(end) = self.build(&input_stream) {
+            output_stream.write(end);
+            return;
+        }
         loop {
             let channel_seq = 0;
             let message = input_stream.read(channel_seq);
             log_event("process-message", "start");
             match message.payload() {
-                Payload::EOF => {
+                Payload::EOF | Payload::Error(_) => {
                     output_stream.write(message);
                     log_event("process-message", "end");
                     break;
                 }
                 Payload::Signal(_) => {
+                    output_stream.write(message);
                     log_event("process-message", "end");
                     break;
                 }
@@ -157,13 +199,26 @@ impl StreamProcessor<DataFrame> for HashJoinNode {
             }
         }
     }
+
+    /// Only the right table is kept; the left stream is joined message by message.
+    fn checkpoint(&self, channel: usize, state: &mut OperatorState) {
+        if channel == 1 {
+            state.put_frame("right_df", self.right_df.borrow().clone());
+        }
+    }
+
+    fn restore(&mut self, state: &OperatorState) {
+        if let Some(df) = state.frame("right_df") {
+            *self.right_df.borrow_mut() = df.clone();
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::data::DataMessage;
-    use crate::graph::{ExecutionService, NodeReader};
+    use crate::graph::{ExecutionService, NodeReader, PlanProblem};
     use crate::polars_operations::CSVReaderBuilder;
 
     #[test]
@@ -221,4 +276,66 @@ mod tests {
         // Result = (100, ) JOIN (200, ) + (100, ) JOIN (200, )
         assert_eq!(total_len, 400);
     }
+
+    fn schema(table: &str, columns: &[&str]) -> Schema {
+        let columns = columns
+            .iter()
+            .map(|column| Column::from_field(column.to_string(), crate::data::DataType::Integer))
+            .collect();
+        Schema::new(table.into(), columns)
+    }
+
+    #[test]
+    fn test_hash_join_output_schema() {
+        let orders = schema("orders", &["o_orderkey", "o_custkey"]);
+        let customer = schema("customer", &["c_custkey", "c_nationkey"]);
+        let hash_join_node = HashJoinBuilder::new()
+            .left_on(vec!["o_custkey".into()])
+            .right_on(vec!["c_custkey".into()])
+            .build();
+        let output = hash_join_node
+            .output_schema(&[Some(orders.clone()), Some(customer)])
+            .unwrap()
+            .unwrap();
+        let names: Vec<&str> = output
+            .columns
+            .iter()
+            .map(|column| column.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["o_orderkey", "o_custkey", "c_nationkey"]);
+        assert_eq!(
+            hash_join_node.output_schema(&[Some(orders), None]),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn test_hash_join_rejects_missing_key() {
+        // The inputs are swapped by mistake.
+        let left = CSVReaderBuilder::new().build();
+        left.declare_output_schema(schema("customer", &["c_custkey", "c_nationkey"]));
+        let right = CSVReaderBuilder::new().build();
+        right.declare_output_schema(schema("orders", &["o_orderkey", "o_custkey"]));
+        let hash_join_node = HashJoinBuilder::new()
+            .left_on(vec!["o_custkey".into()])
+            .right_on(vec!["c_custkey".into()])
+            .build();
+        hash_join_node.subscribe_to_node(&left, 0);
+        hash_join_node.subscribe_to_node(&right, 1);
+        let hash_join_id = hash_join_node.node_id().clone();
+
+        let mut service = ExecutionService::<polars::prelude::DataFrame>::create();
+        service.add(left);
+        service.add(right);
+        service.add(hash_join_node);
+        let error = service.validate().unwrap_err();
+        assert_eq!(
+            error.problems(),
+            &vec![PlanProblem::SchemaMismatch {
+                node_id: hash_join_id,
+                message: "HashJoinBuilder left_on column o_custkey not present in input schema"
+                    .into()
+            }]
+        );
+    }
 }
@@ -1,4 +1,4 @@
-use crate::data::DataBlock;
+use crate::data::{DataBlock, Schema};
 use crate::{graph::ExecutionNode, processor::StreamProcessor};
 use getset::{Getters, Setters};
 use polars::frame::DataFrame;
@@ -11,6 +11,9 @@ pub struct MergerNode<T, P: MergerOp<T>> {
     #[set = "pub"]
     merger: P,
 
+    /// See [Self::output_schema].
+    schema: Option<Schema>,
+
     // Necessary to have T as a generic type
     phantom: PhantomData<T>,
 }
@@ -20,6 +23,7 @@ impl<T, P: MergerOp<T> + Clone> Default for MergerNode<T, P> {
     fn default() -> Self {
         Self {
             merger: P::new(),
+            schema: None,
             phantom: PhantomData::default(),
         }
     }
@@ -38,9 +42,20 @@ where
         self
     }
 
+    /// Declares the schema of the frames the node writes, in place of the one the merger
+    /// derives, if any. See [ExecutionNode::declare_output_schema].
+    pub fn output_schema(&mut self, schema: Schema) -> &mut Self {
+        self.schema = Some(schema);
+        self
+    }
+
     pub fn build(&self) -> ExecutionNode<T> {
         let data_processor = Box::new(self.merger.clone());
-        ExecutionNode::<T>::new(data_processor, 2)
+        let node = ExecutionNode::<T>::new(data_processor, 2);
+        if let Some(schema) = &self.schema {
+            node.declare_output_schema(schema.clone());
+        }
+        node
     }
 }
 
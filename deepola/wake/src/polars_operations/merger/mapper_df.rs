@@ -1,9 +1,8 @@
 use super::base::MergerOp;
 use crate::data::{
-    DATABLOCK_CARDINALITY,
+    BlockMetadata,
     DataBlock,
     DataMessage,
-    MetaCell,
     Payload,
 };
 use crate::utils::log_event;
@@ -13,7 +12,7 @@ use crate::{
 };
 use getset::{Getters, Setters};
 use polars::prelude::DataFrame;
-use std::{borrow::Borrow, cell::RefCell, collections::HashMap, sync::Arc};
+use std::{borrow::Borrow, cell::RefCell, sync::Arc};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum MapperDfMergerMode {
@@ -77,7 +76,7 @@ impl MapperDfMerger {
         self.mapper = Arc::new(mapper)
     }
 
-    fn current_metadata(&self) -> HashMap<String, MetaCell> {
+    fn current_metadata(&self) -> BlockMetadata {
         // Returns metadata from slow side.
         // TODO: Better?
         if *self.left_progress.borrow() <= *self.right_progress.borrow() {
@@ -110,21 +109,13 @@ impl MergerOp<DataFrame> for MapperDfMerger {
     }
 
     fn supply_left(&self, data_block: DataBlock<DataFrame>) {
-        if let Some(progress) = data_block.metadata().get(DATABLOCK_CARDINALITY) {
-            *self.left_progress.borrow_mut() = f64::from(progress);
-        } else {
-            *self.left_progress.borrow_mut() = 1.0;
-        }
+        *self.left_progress.borrow_mut() = data_block.metadata().progress().unwrap_or(1.0);
         *self.left_dblock.borrow_mut() = Some(data_block);
         *self.needs_left.borrow_mut() = false;
     }
 
     fn supply_right(&self, data_block: DataBlock<DataFrame>) {
-        if let Some(progress) = data_block.metadata().get(DATABLOCK_CARDINALITY) {
-            *self.right_progress.borrow_mut() = f64::from(progress);
-        } else {
-            *self.right_progress.borrow_mut() = 1.0;
-        }
+        *self.right_progress.borrow_mut() = data_block.metadata().progress().unwrap_or(1.0);
         *self.right_dblock.borrow_mut() = Some(data_block);
         *self.needs_right.borrow_mut() = false;
     }
@@ -184,7 +175,8 @@ impl StreamProcessor<DataFrame> for MapperDfMerger {
                             continue;
                         }
                     }
-                    Payload::Signal(_) => {
+                    Payload::Signal(_) | Payload::Error(_) => {
+                        output_stream.write(message);
                         log_event("process-message", "end");
                         break;
                     }
@@ -211,7 +203,8 @@ impl StreamProcessor<DataFrame> for MapperDfMerger {
                             continue;
                         }
                     }
-                    Payload::Signal(_) => {
+                    Payload::Signal(_) | Payload::Error(_) => {
+                        output_stream.write(message);
                         log_event("process-message", "end");
                         break;
                     }
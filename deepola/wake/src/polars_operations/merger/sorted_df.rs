@@ -1,9 +1,10 @@
 use super::base::MergerOp;
+use crate::data::BlockMetadata;
 use crate::data::DataBlock;
-use crate::data::DATABLOCK_CARDINALITY;
 use crate::data::DataMessage;
-use crate::data::MetaCell;
 use crate::data::Payload;
+use crate::data::Schema;
+use crate::polars_operations::util::join_schema;
 use crate::utils::log_event;
 use crate::{
     channel::{MultiChannelBroadcaster, MultiChannelReader},
@@ -13,7 +14,6 @@ use getset::{Getters, Setters};
 use polars::{prelude::DataFrame, series::Series};
 use std::borrow::Borrow;
 use std::cell::RefCell;
-use std::collections::HashMap;
 
 /// Inner-joins two dataframes, both of which sorted on their join keys. Assumes that the next data
 /// frame (each for left and right) does not contain the same key values appearing in the current
@@ -86,7 +86,7 @@ impl SortedDfMerger {
         }
     }
 
-    fn current_metadata(&self) -> HashMap<String, MetaCell> {
+    fn current_metadata(&self) -> BlockMetadata {
         // Returns metadata from slow side.
         // TODO: Better?
         if *self.left_progress.borrow() <= *self.right_progress.borrow() {
@@ -152,21 +152,13 @@ impl MergerOp<DataFrame> for SortedDfMerger {
     }
 
     fn supply_left(&self, data_block: DataBlock<DataFrame>) {
-        if let Some(progress) = data_block.metadata().get(DATABLOCK_CARDINALITY) {
-            *self.left_progress.borrow_mut() = f64::from(progress);
-        } else {
-            *self.left_progress.borrow_mut() = 1.0;
-        }
+        *self.left_progress.borrow_mut() = data_block.metadata().progress().unwrap_or(1.0);
         *self.left_dblock.borrow_mut() = Some(data_block);
         *self.needs_left.borrow_mut() = false;
     }
 
     fn supply_right(&self, data_block: DataBlock<DataFrame>) {
-        if let Some(progress) = data_block.metadata().get(DATABLOCK_CARDINALITY) {
-            *self.right_progress.borrow_mut() = f64::from(progress);
-        } else {
-            *self.right_progress.borrow_mut() = 1.0;
-        }
+        *self.right_progress.borrow_mut() = data_block.metadata().progress().unwrap_or(1.0);
         *self.right_dblock.borrow_mut() = Some(data_block);
         *self.needs_right.borrow_mut() = false;
     }
@@ -181,6 +173,20 @@ impl MergerOp<DataFrame> for SortedDfMerger {
 }
 
 impl StreamProcessor<DataFrame> for SortedDfMerger {
+    fn output_schema(&self, input_schemas: &[Option<Schema>]) -> Result<Option<Schema>, String> {
+        let (left, right) = (input_schemas[0].as_ref(), input_schemas[1].as_ref());
+        if let Some(left) = left {
+            left.require_columns("SortedDfMerger left_on", &self.left_on)?;
+        }
+        if let Some(right) = right {
+            right.require_columns("SortedDfMerger right_on", &self.right_on)?;
+        }
+        Ok(match (left, right) {
+            (Some(left), Some(right)) => Some(join_schema(left, right, &self.right_on)),
+            _ => None,
+        })
+    }
+
     fn process_stream(
         &self,
         input_stream: MultiChannelReader<DataFrame>,
@@ -213,7 +219,8 @@ impl StreamProcessor<DataFrame> for SortedDfMerger {
                         log_event("process-message", "end");
                         break;
                     }
-                    Payload::Signal(_) => {
+                    Payload::Signal(_) | Payload::Error(_) => {
+                        output_stream.write(message);
                         log_event("process-message", "end");
                         break;
                     }
@@ -234,7 +241,8 @@ impl StreamProcessor<DataFrame> for SortedDfMerger {
                         log_event("process-message", "end");
                         break;
                     }
-                    Payload::Signal(_) => {
+                    Payload::Signal(_) | Payload::Error(_) => {
+                        output_stream.write(message);
                         log_event("process-message", "end");
                         break;
                     }
@@ -1,13 +1,17 @@
 mod accumulator;
 mod appender;
+mod exchange;
 mod hash_join;
 mod merger;
 mod reader;
+mod rebatch;
 mod series_mq;
 mod util;
 
 pub use accumulator::*;
 pub use appender::*;
+pub use exchange::*;
 pub use hash_join::*;
 pub use merger::*;
 pub use reader::*;
+pub use rebatch::*;
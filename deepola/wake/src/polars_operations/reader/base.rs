@@ -0,0 +1,426 @@
+use polars::prelude::DataFrame;
+use std::cell::Cell;
+use std::collections::VecDeque;
+
+use crate::channel::{MultiChannelBroadcaster, MultiChannelReader};
+use crate::data::{BlockMetadata, DataBlock, DataMessage, Payload, Schema, Signal, StreamError};
+use crate::graph::OperatorState;
+use crate::utils::log_event;
+
+/// The columns a reader node reads from its files, and the names it gives them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Projection {
+    /// The positions of the columns to read, in the order of the files; all of them if None.
+    pub(crate) projected_cols: Option<Vec<usize>>,
+
+    /// The names of the columns read, in the same order; the names in the files if None.
+    pub(crate) column_names: Option<Vec<String>>,
+
+    /// The columns to write, in order; all of the ones read if None. Read by name if they
+    /// are not resolved to positions.
+    pub(crate) columns: Option<Vec<String>>,
+}
+
+impl Projection {
+    /// The projection a reader builder is set up with: `columns` resolved by `schema` (see
+    /// [Self::resolve]), or else the positions and names given to the builder, along with
+    /// `columns` to read by name.
+    pub(crate) fn new(
+        schema: Option<&Schema>,
+        columns: Option<Vec<String>>,
+        projected_cols: Option<Vec<usize>>,
+        column_names: Option<Vec<String>>,
+    ) -> Self {
+        match schema {
+            Some(schema) => Self::resolve(schema, columns),
+            None => Projection {
+                projected_cols,
+                column_names,
+                columns,
+            },
+        }
+    }
+
+    /// The projection of `columns` (or all the columns) of files of `schema`: resolves their
+    /// positions, and names the columns after the schema, e.g., for files without a header.
+    ///
+    /// Panics if the schema lacks some of the columns.
+    pub(crate) fn resolve(schema: &Schema, columns: Option<Vec<String>>) -> Self {
+        let mut projected_cols = match &columns {
+            Some(columns) => {
+                if let Err(message) = schema.require_columns("Projected", columns) {
+                    panic!("{}", message);
+                }
+                columns.iter().map(|column| schema.index(column)).collect()
+            }
+            None => (0..schema.col_count()).collect::<Vec<_>>(),
+        };
+        projected_cols.sort_unstable();
+        projected_cols.dedup();
+        let column_names = projected_cols
+            .iter()
+            .map(|index| schema.get_column_from_index(*index).name)
+            .collect();
+        Projection {
+            projected_cols: columns.as_ref().map(|_| projected_cols),
+            column_names: Some(column_names),
+            columns,
+        }
+    }
+}
+
+/// How far a reader node has come through the files it is given. Saved in checkpoints, so
+/// that a resumed query skips the files, and the chunks of a file read in chunks, that were
+/// read before the checkpoint.
+#[derive(Debug, Default)]
+pub(crate) struct ReadPosition {
+    /// Files read so far, over all the blocks of file names.
+    files_read: Cell<usize>,
+
+    /// Records read from the current block of file names, used to estimate its cardinality.
+    records_read: Cell<f64>,
+
+    /// Files still to be skipped after resuming from a checkpoint.
+    files_to_skip: Cell<usize>,
+
+    /// Chunks read from the file being read, if it is read in chunks.
+    chunks_read: Cell<usize>,
+
+    /// Chunks of the first file not skipped that are still to be skipped after resuming from
+    /// a checkpoint.
+    chunks_to_skip: Cell<usize>,
+}
+
+impl ReadPosition {
+    /// If the next file was read before the checkpoint the query resumed from, i.e., is to be
+    /// skipped, returns the number of records read up to the checkpoint.
+    pub(crate) fn skip_file(&self) -> Option<f64> {
+        match self.files_to_skip.get() {
+            0 => None,
+            n => {
+                self.files_to_skip.set(n - 1);
+                Some(self.records_read.get())
+            }
+        }
+    }
+
+    /// Same as [Self::skip_file], but for the next chunk of the file being read.
+    pub(crate) fn skip_chunk(&self) -> Option<f64> {
+        match self.chunks_to_skip.get() {
+            0 => None,
+            n => {
+                self.chunks_to_skip.set(n - 1);
+                Some(self.records_read.get())
+            }
+        }
+    }
+
+    /// The number of files read thus far, including the ones read before a checkpoint.
+    pub(crate) fn files_read(&self) -> usize {
+        self.files_read.get()
+    }
+
+    pub(crate) fn file_read(&self, records_read: f64) {
+        self.files_read.set(self.files_read.get() + 1);
+        self.chunks_read.set(0);
+        self.records_read.set(records_read);
+    }
+
+    /// A chunk of the file being read was read, but not its last one; see [Self::file_read].
+    pub(crate) fn chunk_read(&self, records_read: f64) {
+        self.chunks_read.set(self.chunks_read.get() + 1);
+        self.records_read.set(records_read);
+    }
+
+    pub(crate) fn checkpoint(&self, state: &mut OperatorState) {
+        state.put_value("files_read", self.files_read.get() as f64);
+        state.put_value("records_read", self.records_read.get());
+        state.put_value("chunks_read", self.chunks_read.get() as f64);
+    }
+
+    pub(crate) fn restore(&self, state: &OperatorState) {
+        let files_read = state.value("files_read").unwrap_or(0.0) as usize;
+        self.files_read.set(files_read);
+        self.files_to_skip.set(files_read);
+        self.records_read
+            .set(state.value("records_read").unwrap_or(0.0));
+        let chunks_read = state.value("chunks_read").unwrap_or(0.0) as usize;
+        self.chunks_read.set(chunks_read);
+        self.chunks_to_skip.set(chunks_read);
+    }
+}
+
+/// A frame a [FileReader] reads from one of its files.
+pub(crate) struct Chunk {
+    pub(crate) df: DataFrame,
+
+    /// The records of the file the frame covers, including the ones left out of it, e.g., by
+    /// predicates.
+    pub(crate) records: usize,
+
+    /// Whether the frame is the last of the file.
+    pub(crate) last: bool,
+}
+
+impl Chunk {
+    /// A chunk covering the rows of `df`.
+    pub(crate) fn new(df: DataFrame, last: bool) -> Self {
+        let records = df.height();
+        Chunk { df, records, last }
+    }
+}
+
+/// The part of a reader node that depends on the format of its files. The rest, i.e., going
+/// through the files named in the input, the progress of the blocks, resuming from a
+/// checkpoint, and pausing or stopping in between chunks, is [read_files].
+pub(crate) trait FileReader {
+    fn position(&self) -> &ReadPosition;
+
+    /// The chunks `filename` is read into, in order. `metadata` is the metadata of the block
+    /// naming the file.
+    fn read_file<'a>(
+        &'a self,
+        filename: &str,
+        metadata: &BlockMetadata,
+    ) -> Box<dyn Iterator<Item = Result<Chunk, StreamError>> + 'a>;
+
+    /// The exact number of records in the files named in `filenames`, if the format tells it
+    /// without reading them. Otherwise, the progress of the blocks derives from the expected
+    /// total in the metadata of the input.
+    fn total_records(&self, _filenames: &DataFrame) -> Result<Option<usize>, StreamError> {
+        Ok(None)
+    }
+}
+
+/// Processes the input of a reader node: reads the files named in every block with `reader`,
+/// and writes each of their chunks as a block of its own. A file that cannot be read ends the
+/// output with an error in place of EOF.
+pub(crate) fn read_files<R: FileReader>(
+    reader: &R,
+    input_stream: MultiChannelReader<DataFrame>,
+    output_stream: MultiChannelBroadcaster<DataFrame>,
+) {
+    let channel_seq = 0;
+    let mut backlog = VecDeque::new();
+    loop {
+        let message = backlog
+            .pop_front()
+            .unwrap_or_else(|| input_stream.read(channel_seq));
+        log_event("process-message", "start");
+        match message.payload() {
+            Payload::EOF | Payload::Signal(Signal::STOP) | Payload::Error(_) => {
+                output_stream.write(message);
+                log_event("process-message", "end");
+                break;
+            }
+            Payload::Signal(Signal::PAUSE) => {
+                if let Some(stop) = wait_for_resume(&input_stream, channel_seq, &mut backlog) {
+                    backlog.push_front(stop);
+                }
+                log_event("process-message", "end");
+            }
+            Payload::Signal(_) => {
+                log_event("process-message", "end");
+            }
+            Payload::Some(dblock) => {
+                let read = read_block(
+                    reader,
+                    &dblock,
+                    &input_stream,
+                    channel_seq,
+                    &output_stream,
+                    &mut backlog,
+                );
+                if let Err(error) = read {
+                    // The rest of the table is not read.
+                    log::error!("{}", error);
+                    output_stream.write(DataMessage::error(error));
+                    log_event("process-message", "end");
+                    break;
+                }
+                log_event("process-message", "end");
+            }
+        }
+    }
+}
+
+/// Reads the files named in `dblock`, and writes their chunks. Stops early, leaving the signal
+/// at the front of `backlog`, if the reader is asked to stop.
+fn read_block<R: FileReader>(
+    reader: &R,
+    dblock: &DataBlock<DataFrame>,
+    input_stream: &MultiChannelReader<DataFrame>,
+    channel_seq: usize,
+    output_stream: &MultiChannelBroadcaster<DataFrame>,
+    backlog: &mut VecDeque<DataMessage<DataFrame>>,
+) -> Result<(), StreamError> {
+    let position = reader.position();
+    let mut metadata = dblock.metadata().clone();
+    let mut expected_total_records = match reader.total_records(dblock.data())? {
+        Some(total_records) => {
+            metadata.set_expected_total(total_records as f64);
+            total_records as f64
+        }
+        None => metadata.expected_total().unwrap_or_else(|| {
+            log::warn!("Missing the expected total records in metadata");
+            1.0
+        }),
+    };
+    let mut current_total_records = 0.0;
+    for series in dblock.data().iter() {
+        // This must be a length-one Polars series containing
+        // file names in its rows
+        let rows = series.utf8()?;
+        let num_files = rows.len();
+
+        for (index, filename) in rows.into_iter().enumerate() {
+            // Read before the checkpoint this query resumed from.
+            if let Some(records_read) = position.skip_file() {
+                current_total_records = records_read;
+                continue;
+            }
+            let filename = filename.unwrap();
+            for chunk in reader.read_file(filename, &metadata) {
+                let chunk = chunk.map_err(|error| error.context(filename))?;
+                if let Some(records_read) = position.skip_chunk() {
+                    current_total_records = records_read;
+                    if chunk.last {
+                        position.file_read(current_total_records);
+                    }
+                    continue;
+                }
+
+                current_total_records += chunk.records as f64;
+                if index == num_files - 1 && chunk.last {
+                    // The expected total may be an estimate, e.g., lineitem's 6_000_000 * SF;
+                    // the table is read in full once its last chunk is.
+                    expected_total_records = current_total_records;
+                }
+                if metadata.progress().is_some() {
+                    // An empty table is read in full as soon as it is read.
+                    let progress = if expected_total_records > 0.0 {
+                        f64::min(1.0, current_total_records / expected_total_records)
+                    } else {
+                        1.0
+                    };
+                    metadata.set_progress(progress);
+                }
+                metadata.set_epoch(position.files_read() as u64);
+                metadata.set_source_id(filename);
+
+                let output_dblock = DataBlock::new(chunk.df, metadata.clone());
+                output_stream.write(DataMessage::from(output_dblock));
+                if chunk.last {
+                    position.file_read(current_total_records);
+                } else {
+                    position.chunk_read(current_total_records);
+                }
+
+                // Hold back the remaining chunks while paused, and skip them if the reader is
+                // asked to stop.
+                if let Some(signal) = poll_signal(input_stream, channel_seq, backlog) {
+                    backlog.push_front(signal);
+                    return Ok(());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks the input channel of a reader node for a signal without waiting, so that a reader
+/// working through a long list of files can be paused or stopped in between files.
+///
+/// If the reader is paused, this blocks until it is resumed. Returns the STOP message if the
+/// reader is stopped. Other messages read while checking are appended to `backlog`; the reader
+/// must process them before reading from the channel again.
+pub(crate) fn poll_signal<T: Send>(
+    input_stream: &MultiChannelReader<T>,
+    channel_seq: usize,
+    backlog: &mut VecDeque<DataMessage<T>>,
+) -> Option<DataMessage<T>> {
+    while let Some(message) = input_stream.try_read(channel_seq) {
+        match message.payload() {
+            Payload::Signal(Signal::STOP) => return Some(message),
+            Payload::Signal(Signal::PAUSE) => {
+                if let Some(stop) = wait_for_resume(input_stream, channel_seq, backlog) {
+                    return Some(stop);
+                }
+            }
+            Payload::Signal(Signal::RESUME) => {}
+            _ => backlog.push_back(message),
+        }
+    }
+    None
+}
+
+/// Blocks a paused reader until it is resumed. Returns the STOP message if the reader is
+/// stopped while paused. Other messages are appended to `backlog`.
+pub(crate) fn wait_for_resume<T: Send>(
+    input_stream: &MultiChannelReader<T>,
+    channel_seq: usize,
+    backlog: &mut VecDeque<DataMessage<T>>,
+) -> Option<DataMessage<T>> {
+    log::debug!("Reader paused");
+    loop {
+        let message = input_stream.read(channel_seq);
+        match message.payload() {
+            Payload::Signal(Signal::STOP) => return Some(message),
+            Payload::Signal(Signal::RESUME) => break,
+            Payload::Signal(Signal::PAUSE) => {}
+            _ => backlog.push_back(message),
+        }
+    }
+    log::debug!("Reader resumed");
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_projection() {
+        let schema = Schema::from_example("lineitem").unwrap();
+        let columns = vec!["l_quantity".to_string(), "l_orderkey".to_string()];
+        let projection = Projection::resolve(&schema, Some(columns.clone()));
+        assert_eq!(projection.projected_cols, Some(vec![0, 4]));
+        assert_eq!(
+            projection.column_names,
+            Some(vec!["l_orderkey".to_string(), "l_quantity".to_string()])
+        );
+        assert_eq!(projection.columns, Some(columns));
+
+        let projection = Projection::resolve(&schema, None);
+        assert_eq!(projection.projected_cols, None);
+        assert_eq!(projection.column_names.unwrap().len(), 16);
+    }
+
+    #[test]
+    #[should_panic(expected = "Projected column l_missing not present in input schema")]
+    fn rejects_missing_projected_column() {
+        let schema = Schema::from_example("lineitem").unwrap();
+        Projection::resolve(&schema, Some(vec!["l_missing".to_string()]));
+    }
+
+    #[test]
+    fn resumes_within_file() {
+        let position = ReadPosition::default();
+        position.file_read(10.0);
+        position.chunk_read(13.0);
+        position.chunk_read(16.0);
+        let mut state = OperatorState::default();
+        position.checkpoint(&mut state);
+
+        let resumed = ReadPosition::default();
+        resumed.restore(&state);
+        assert_eq!(resumed.skip_file(), Some(16.0));
+        assert_eq!(resumed.skip_file(), None);
+        assert_eq!(resumed.skip_chunk(), Some(16.0));
+        assert_eq!(resumed.skip_chunk(), Some(16.0));
+        assert_eq!(resumed.skip_chunk(), None);
+        resumed.chunk_read(19.0);
+        resumed.file_read(20.0);
+        assert_eq!(resumed.files_read(), 2);
+    }
+}
@@ -1,16 +1,35 @@
+use polars::io::mmap::MmapBytesReader;
 use polars::prelude::*;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Cursor};
+use std::result::Result;
 
-use crate::data::*;
-use crate::graph::ExecutionNode;
+use super::base::{read_files, Chunk, FileReader, Projection, ReadPosition};
+use crate::data::{BlockMetadata, Schema, StreamError, StreamErrorKind};
+use crate::graph::{ExecutionNode, OperatorState};
 use crate::processor::StreamProcessor;
-use crate::utils::log_event;
 
+/// Factory for creating an [ExecutionNode] that reads the CSV files named in its input.
+///
+/// By default, a file is written as a single block. With a target number of rows or size in
+/// bytes, a file is streamed in chunks of whole lines instead, each chunk written as soon as
+/// it is parsed, so that the first blocks do not wait for the whole file. The types of the
+/// columns are then inferred from the first chunk of a file, and kept for the rest of it,
+/// unless given by the schema of the input block.
+///
+/// The columns to read are best given by name, with [Self::columns]. With the schema of the
+/// files, the reader resolves their positions itself, and names the columns after the schema
+/// (e.g., for files without a header); otherwise, it looks them up in the header of the files.
 pub struct CSVReaderBuilder {
     delimiter: char,
     has_headers: bool,
     parse_dates: bool,
     column_names: Option<Vec<String>>,
     projected_cols: Option<Vec<usize>>,
+    columns: Option<Vec<String>>,
+    schema: Option<Schema>,
+    target_rows: Option<usize>,
+    target_bytes: Option<usize>,
 }
 
 impl Default for CSVReaderBuilder {
@@ -21,6 +40,10 @@ impl Default for CSVReaderBuilder {
             parse_dates: true,
             column_names: Option::None,
             projected_cols: Option::None,
+            columns: Option::None,
+            schema: Option::None,
+            target_rows: Option::None,
+            target_bytes: Option::None,
         }
     }
 }
@@ -50,30 +73,73 @@ impl CSVReaderBuilder {
         self
     }
 
+    /// Reads only `columns`, and writes them in this order; see [CSVReaderBuilder].
+    pub fn columns(&mut self, columns: Option<Vec<String>>) -> &mut Self {
+        self.columns = columns;
+        self
+    }
+
+    /// The schema of the files, which resolves [Self::columns] in place of
+    /// [Self::projected_cols] and [Self::column_names].
+    pub fn schema(&mut self, schema: Schema) -> &mut Self {
+        self.schema = Some(schema);
+        self
+    }
+
     pub fn parse_dates(&mut self, parse_dates: bool) -> &mut Self {
         self.parse_dates = parse_dates;
         self
     }
 
+    /// Streams every file in chunks of this many rows; see [CSVReaderBuilder].
+    pub fn target_rows(&mut self, target_rows: usize) -> &mut Self {
+        self.target_rows = Some(target_rows);
+        self
+    }
+
+    /// Streams every file in chunks of about this many bytes of the file; see
+    /// [CSVReaderBuilder].
+    pub fn target_bytes(&mut self, target_bytes: usize) -> &mut Self {
+        self.target_bytes = Some(target_bytes);
+        self
+    }
+
     pub fn build(&self) -> ExecutionNode<DataFrame> {
+        assert!(
+            self.target_rows != Some(0) && self.target_bytes != Some(0),
+            "The targets of a CSV reader must be positive"
+        );
+        let projection = Projection::new(
+            self.schema.as_ref(),
+            self.columns.clone(),
+            self.projected_cols.clone(),
+            self.column_names.clone(),
+        );
+        assert!(
+            self.has_headers || projection.projected_cols.is_some() || projection.columns.is_none(),
+            "A CSV reader without headers requires a schema to read columns by name"
+        );
         let data_processor = CSVReader::new(
             self.delimiter,
             self.has_headers,
             self.parse_dates,
-            self.column_names.clone(),
-            self.projected_cols.clone(),
+            projection,
+            self.target_rows,
+            self.target_bytes,
         );
         ExecutionNode::<DataFrame>::new(Box::new(data_processor), 1)
     }
 }
 
-/// A custom SetProcessor<Series> type for reading csv files.
+/// The [FileReader] of [CSVReaderBuilder] nodes.
 struct CSVReader {
     delimiter: char,
     has_headers: bool,
     parse_dates: bool,
-    column_names: Option<Vec<String>>,
-    projected_cols: Option<Vec<usize>>,
+    projection: Projection,
+    target_rows: Option<usize>,
+    target_bytes: Option<usize>,
+    position: ReadPosition,
 }
 
 /// A factory method for creating the custom SetProcessor<Series> type for
@@ -83,34 +149,234 @@ impl CSVReader {
         delimiter: char,
         has_headers: bool,
         parse_dates: bool,
-        column_names: Option<Vec<String>>,
-        projected_cols: Option<Vec<usize>>,
+        projection: Projection,
+        target_rows: Option<usize>,
+        target_bytes: Option<usize>,
     ) -> Self {
         CSVReader {
             delimiter,
             has_headers,
             parse_dates,
-            column_names,
-            projected_cols,
+            projection,
+            target_rows,
+            target_bytes,
+            position: ReadPosition::default(),
         }
     }
 
-    fn dataframe_from_filename(&self, filename: &str) -> DataFrame {
-        let mut reader = polars::prelude::CsvReader::from_path(filename)
-            .unwrap()
+    /// The types of the columns of the files, in the order of the columns of `schema`.
+    fn dtypes(schema: &Schema) -> Result<Vec<DataType>, StreamError> {
+        schema
+            .columns
+            .iter()
+            .map(|column| {
+                DataType::try_from(&column.dtype).map_err(|error| {
+                    StreamError::new(StreamErrorKind::InvalidData, &error.to_string())
+                        .context(&column.name)
+                })
+            })
+            .collect()
+    }
+
+    /// The options of the reader node, and the types of the columns of `dtypes` (by position,
+    /// so that they apply before the projection).
+    fn options<'a, R: MmapBytesReader + 'a>(
+        &self,
+        reader: polars::prelude::CsvReader<'a, R>,
+        dtypes: Option<&'a [DataType]>,
+    ) -> polars::prelude::CsvReader<'a, R> {
+        reader
             .has_header(self.has_headers)
             .with_parse_dates(self.parse_dates)
-            .with_delimiter(self.delimiter as u8);
-        if self.projected_cols.is_some() {
-            reader = reader.with_projection(self.projected_cols.clone());
+            .with_delimiter(self.delimiter as u8)
+            .with_dtypes_slice(dtypes)
+    }
+
+    /// Reads the columns of `dtypes` with their given types, and infers the types of any
+    /// other columns.
+    fn parse<'a, R: MmapBytesReader + 'a>(
+        &self,
+        reader: polars::prelude::CsvReader<'a, R>,
+        dtypes: Option<&'a [DataType]>,
+    ) -> Result<DataFrame, StreamError> {
+        let mut reader = self.options(reader, dtypes);
+        let projection = &self.projection;
+        if projection.projected_cols.is_some() {
+            reader = reader.with_projection(projection.projected_cols.clone());
+        } else if projection.columns.is_some() {
+            reader = reader.with_columns(projection.columns.clone());
         }
-        let mut df = reader.finish().unwrap();
-        if self.column_names.is_some() {
-            if let Some(a) = &self.column_names {
-                df.set_column_names(a).unwrap();
+        self.finish(reader.finish()?)
+    }
+
+    /// Projects a frame of all the columns of a file, as [Self::parse] reads them.
+    fn project(&self, df: DataFrame) -> Result<DataFrame, StreamError> {
+        let df = match &self.projection.projected_cols {
+            Some(projected_cols) => {
+                let columns = projected_cols
+                    .iter()
+                    .map(|index| df.select_at_idx(*index).cloned())
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| {
+                        let message = "Projected column not present in file";
+                        StreamError::new(StreamErrorKind::InvalidData, message)
+                    })?;
+                DataFrame::new(columns)?
             }
+            None => df,
+        };
+        self.finish(df)
+    }
+
+    /// Names the columns read, and puts them in the order of the projection.
+    fn finish(&self, mut df: DataFrame) -> Result<DataFrame, StreamError> {
+        if let Some(column_names) = &self.projection.column_names {
+            df.set_column_names(column_names)?;
+        }
+        if let Some(columns) = &self.projection.columns {
+            df = df.select(columns)?;
+        }
+        Ok(df)
+    }
+}
+
+/// Splits a CSV file into chunks of whole lines, each of which parses on its own: the header
+/// line, if any, starts every chunk. Assumes that no quoted field spans lines.
+struct CsvChunks {
+    lines: BufReader<File>,
+    header: Vec<u8>,
+    target_rows: usize,
+    target_bytes: usize,
+
+    /// The first chunk is returned even if the file has no rows, so that every file is read
+    /// into at least one frame.
+    started: bool,
+}
+
+impl CsvChunks {
+    fn open(
+        filename: &str,
+        has_headers: bool,
+        target_rows: usize,
+        target_bytes: usize,
+    ) -> Result<Self, StreamError> {
+        let mut lines = BufReader::new(File::open(filename)?);
+        let mut header = vec![];
+        if has_headers {
+            lines.read_until(b'\n', &mut header)?;
+        }
+        Ok(Self {
+            lines,
+            header,
+            target_rows,
+            target_bytes,
+            started: false,
+        })
+    }
+
+    /// The next chunk, and whether it is the last of the file.
+    fn next_chunk(&mut self) -> Result<Option<(Vec<u8>, bool)>, StreamError> {
+        let mut chunk = self.header.clone();
+        let mut rows = 0;
+        let mut bytes = 0;
+        while rows < self.target_rows && bytes < self.target_bytes {
+            let start = chunk.len();
+            let read = self.lines.read_until(b'\n', &mut chunk)?;
+            if read == 0 {
+                break;
+            }
+            if chunk[start..].iter().all(u8::is_ascii_whitespace) {
+                chunk.truncate(start);
+                continue;
+            }
+            rows += 1;
+            bytes += read;
+        }
+        if rows == 0 && self.started {
+            return Ok(None);
+        }
+        self.started = true;
+        Ok(Some((chunk, self.at_end()?)))
+    }
+
+    /// Whether the rest of the file is blank, so that no chunk follows. Skips the blank lines
+    /// ahead, without parsing any more of the file.
+    fn at_end(&mut self) -> Result<bool, StreamError> {
+        loop {
+            let buffer = self.lines.fill_buf()?;
+            if buffer.is_empty() {
+                return Ok(true);
+            }
+            match buffer.iter().position(|byte| !byte.is_ascii_whitespace()) {
+                Some(data) => {
+                    // The line the data is on is left whole.
+                    let blank = buffer[..data]
+                        .iter()
+                        .rposition(|byte| *byte == b'\n')
+                        .map_or(0, |newline| newline + 1);
+                    self.lines.consume(blank);
+                    return Ok(false);
+                }
+                None => {
+                    let blank = buffer.len();
+                    self.lines.consume(blank);
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for CsvChunks {
+    type Item = Result<(Vec<u8>, bool), StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_chunk().transpose()
+    }
+}
+
+impl FileReader for CSVReader {
+    fn position(&self) -> &ReadPosition {
+        &self.position
+    }
+
+    /// The whole file, or its chunks if a target size is set. The types of the columns are
+    /// the ones of the schema in `metadata`, if any.
+    fn read_file<'a>(
+        &'a self,
+        filename: &str,
+        metadata: &BlockMetadata,
+    ) -> Box<dyn Iterator<Item = Result<Chunk, StreamError>> + 'a> {
+        let mut dtypes = match metadata.schema().map(Self::dtypes).transpose() {
+            Ok(dtypes) => dtypes,
+            Err(error) => return Box::new(std::iter::once(Err(error))),
+        };
+        if self.target_rows.is_none() && self.target_bytes.is_none() {
+            let reader = polars::prelude::CsvReader::from_path(filename)
+                .map_err(StreamError::from)
+                .and_then(|reader| self.parse(reader, dtypes.as_deref()));
+            return Box::new(std::iter::once(reader.map(|df| Chunk::new(df, true))));
+        }
+        let chunks = CsvChunks::open(
+            filename,
+            self.has_headers,
+            self.target_rows.unwrap_or(usize::MAX),
+            self.target_bytes.unwrap_or(usize::MAX),
+        );
+        match chunks {
+            Ok(chunks) => Box::new(chunks.map(move |chunk| {
+                let (chunk, last) = chunk?;
+                let reader = polars::prelude::CsvReader::new(Cursor::new(chunk));
+                if let Some(dtypes) = dtypes.as_deref() {
+                    return Ok(Chunk::new(self.parse(reader, Some(dtypes))?, last));
+                }
+                // The types inferred from the first chunk hold for the rest of the file, so
+                // that all of its blocks have the same schema.
+                let df = self.options(reader, None).finish()?;
+                dtypes = Some(df.dtypes());
+                Ok(Chunk::new(self.project(df)?, last))
+            })),
+            Err(error) => Box::new(std::iter::once(Err(error))),
         }
-        df
     }
 }
 
@@ -120,70 +386,25 @@ impl StreamProcessor<DataFrame> for CSVReader {
         input_stream: crate::channel::MultiChannelReader<DataFrame>,
         output_stream: crate::channel::MultiChannelBroadcaster<DataFrame>,
     ) {
-        loop {
-            let channel_seq = 0;
-            let message = input_stream.read(channel_seq);
-            log_event("process-message", "start");
-            match message.payload() {
-                Payload::EOF => {
-                    output_stream.write(message);
-                    log_event("process-message", "end");
-                    break;
-                }
-                Payload::Signal(_) => {
-                    log_event("process-message", "end");
-                    break;
-                }
-                Payload::Some(dblock) => {
-                    let mut metadata = dblock.metadata().clone();
-                    let mut expected_total_records =
-                        if let Some(count) = metadata.get(DATABLOCK_TOTAL_RECORDS) {
-                            f64::from(count)
-                        } else {
-                            log::warn!("Missing {} in metadata", DATABLOCK_TOTAL_RECORDS);
-                            1.0
-                        };
-                    let mut currect_total_records = 0.0;
-                    for series in dblock.data().iter() {
-                        // This must be a length-one Polars series containing
-                        // file names in its rows
-                        let rows = series.utf8().unwrap();
-                        let num_files = rows.len();
-
-                        // each file name produces multiple Series (each is a column)
-                        for (index,filename) in rows.into_iter().enumerate() {
-                            let output_df = self.dataframe_from_filename(filename.unwrap());
-
-                            // Update record count
-                            currect_total_records += output_df.height() as f64;
-                            if index == num_files - 1 {
-                                // lineitem's cardinality estimate of 6_000_000 * SF is approximate.
-                                // For some scales, this value is smaller than 6_000_000 * SF.
-                                expected_total_records = currect_total_records;
-                            }
-                            if let Some(cardinality) = metadata.get_mut(DATABLOCK_CARDINALITY) {
-                                *cardinality = MetaCell::from(f64::min(1.0,
-                                    currect_total_records / expected_total_records));
-                            }
-
-                            // Compose and write output message
-                            let output_dblock = DataBlock::new(output_df, metadata.clone());
-                            let output_message = DataMessage::from(output_dblock);
-                            output_stream.write(output_message);
-                        }
-                    }
-                    log_event("process-message", "end");
-                }
-            }
-        }
+        read_files(self, input_stream, output_stream);
+    }
+
+    fn checkpoint(&self, _channel: usize, state: &mut OperatorState) {
+        self.position.checkpoint(state);
+    }
+
+    fn restore(&mut self, state: &OperatorState) {
+        self.position.restore(state);
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::data::DataMessage;
-    use crate::graph::NodeReader;
+    use crate::data::{DataBlock, DataMessage};
+    use crate::graph::{ExecutionService, NodeReader};
+    use std::thread;
+    use std::time::Duration;
 
     #[test]
     fn test_csv_reader_node() {
@@ -226,6 +447,112 @@ mod tests {
         assert_eq!(total_output_len, total_input_len);
     }
 
+    #[test]
+    fn test_csv_reader_missing_file_ends_in_error() {
+        let csvreader = CSVReaderBuilder::new()
+            .delimiter(',')
+            .has_headers(true)
+            .build();
+        let csvreader_id = csvreader.node_id().clone();
+        let input_files = df!(
+            "col" => &[
+                "resources/tpc-h/data/lineitem-100.csv",
+                "resources/tpc-h/data/missing.csv",
+                "resources/tpc-h/data/lineitem-100.csv",
+            ]
+        )
+        .unwrap();
+        csvreader.write_to_self(0, DataMessage::from(input_files));
+        csvreader.write_to_self(0, DataMessage::eof());
+        let reader_node = NodeReader::new(&csvreader);
+        csvreader.run();
+
+        let message = reader_node.read_result().unwrap();
+        assert_eq!(message.datablock().data().height(), 100);
+        assert_eq!(message.datablock().metadata().epoch(), Some(0));
+        assert_eq!(
+            message.datablock().metadata().source_id(),
+            Some("resources/tpc-h/data/lineitem-100.csv")
+        );
+        let error = reader_node.read_result().unwrap_err();
+        assert_eq!(*error.kind(), StreamErrorKind::Io);
+        assert_eq!(error.node_id().as_deref(), Some(csvreader_id.as_str()));
+        assert!(error.message().starts_with("resources/tpc-h/data/missing.csv"));
+    }
+
+    #[test]
+    fn test_csv_reader_stops_between_files() {
+        let csvreader = CSVReaderBuilder::new()
+            .delimiter(',')
+            .has_headers(true)
+            .build();
+        let input_files = df!(
+            "col" => &[
+                "resources/tpc-h/data/lineitem-100.csv",
+                "resources/tpc-h/data/lineitem-100.csv",
+                "resources/tpc-h/data/lineitem-100.csv",
+            ]
+        )
+        .unwrap();
+
+        // The stop signal is already queued once the first file is read.
+        csvreader.write_to_self(0, DataMessage::from(input_files));
+        csvreader.write_to_self(0, DataMessage::stop());
+        let reader_node = NodeReader::new(&csvreader);
+        csvreader.run();
+
+        let mut total_output_len = 0;
+        loop {
+            let message = reader_node.read();
+            if message.is_stop() {
+                break;
+            }
+            total_output_len += message.datablock().data().height();
+        }
+        assert_eq!(total_output_len, 100);
+    }
+
+    #[test]
+    fn test_csv_reader_pause_resume() {
+        let csvreader = CSVReaderBuilder::new()
+            .delimiter(',')
+            .has_headers(true)
+            .build();
+        let input_files = df!(
+            "col" => &[
+                "resources/tpc-h/data/lineitem-100.csv",
+                "resources/tpc-h/data/lineitem-100.csv",
+                "resources/tpc-h/data/lineitem-100.csv",
+            ]
+        )
+        .unwrap();
+
+        // The pause signal is already queued once the first file is read.
+        csvreader.write_to_self(0, DataMessage::from(input_files));
+        csvreader.write_to_self(0, DataMessage::pause());
+        csvreader.write_to_self(0, DataMessage::eof());
+        let reader_node = NodeReader::new(&csvreader);
+        let mut service = ExecutionService::create();
+        service.add(csvreader);
+        let handle = service.run();
+
+        assert_eq!(reader_node.read().datablock().data().height(), 100);
+        thread::sleep(Duration::from_millis(100));
+        assert!(reader_node.try_read().is_none());
+
+        handle.resume();
+        let mut total_output_len = 0;
+        loop {
+            let message = reader_node.read();
+            if message.is_eof() {
+                break;
+            }
+            total_output_len += message.datablock().data().height();
+        }
+        assert_eq!(total_output_len, 200);
+        service.join().unwrap();
+    }
+
     #[test]
     fn test_partial_col_read() {
         let input_files = df!(
@@ -282,4 +609,158 @@ mod tests {
             assert_eq!(data.width(), total_column_count);
         }
     }
+
+    #[test]
+    fn test_csv_reader_reads_schema_dtypes() {
+        // Read l_orderkey as text rather than an inferred integer.
+        let mut columns = Schema::from_example("lineitem").unwrap().columns;
+        columns[0].dtype = crate::data::DataType::Text;
+        let metadata = BlockMetadata::from_schema(Schema::new("lineitem".into(), columns))
+            .with_expected_total(100.0);
+        let input_files = df!("col" => &["resources/tpc-h/data/lineitem-100.csv"]).unwrap();
+
+        let csvreader = CSVReaderBuilder::new()
+            .delimiter(',')
+            .has_headers(true)
+            .build();
+        csvreader.write_to_self(0, DataMessage::from(DataBlock::new(input_files, metadata)));
+        csvreader.write_to_self(0, DataMessage::eof());
+        let reader_node = NodeReader::new(&csvreader);
+        csvreader.run();
+
+        let message = reader_node.read();
+        let data = message.datablock().data();
+        assert_eq!(data.column("l_orderkey").unwrap().dtype(), &DataType::Utf8);
+        assert_eq!(data.column("l_quantity").unwrap().dtype(), &DataType::Int64);
+        assert_eq!(data.column("l_shipdate").unwrap().dtype(), &DataType::Date);
+        assert!(reader_node.read().is_eof());
+    }
+
+    #[test]
+    fn test_csv_reader_streams_chunks() {
+        let metadata = BlockMetadata::from_schema(Schema::from_example("lineitem").unwrap())
+            .with_expected_total(100.0);
+        let input_files = df!("col" => &["resources/tpc-h/data/lineitem-100.csv"]).unwrap();
+
+        let csvreader = CSVReaderBuilder::new()
+            .delimiter(',')
+            .has_headers(true)
+            .target_rows(30)
+            .build();
+        csvreader.write_to_self(0, DataMessage::from(DataBlock::new(input_files, metadata)));
+        csvreader.write_to_self(0, DataMessage::eof());
+        let reader_node = NodeReader::new(&csvreader);
+        csvreader.run();
+
+        let mut blocks = vec![];
+        loop {
+            let message = reader_node.read();
+            if message.is_eof() {
+                break;
+            }
+            let dblock = message.datablock();
+            assert_eq!(dblock.data().width(), 16);
+            blocks.push((
+                dblock.data().height(),
+                dblock.metadata().progress().unwrap(),
+            ));
+        }
+        assert_eq!(blocks, vec![(30, 0.3), (30, 0.6), (30, 0.9), (10, 1.0)]);
+    }
+
+    #[test]
+    fn test_csv_reader_keeps_types_of_first_chunk() {
+        // The second chunk alone would be read with a text column of nulls.
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nulls.csv");
+        std::fs::write(&path, "a,b\n1,1\n2,2\n3,\n4,\n").unwrap();
+        let input_files = df!("col" => &[path.to_str().unwrap()]).unwrap();
+        let csvreader = CSVReaderBuilder::new()
+            .has_headers(true)
+            .target_rows(2)
+            .build();
+        csvreader.write_to_self(0, DataMessage::from(input_files));
+        csvreader.write_to_self(0, DataMessage::eof());
+        let reader_node = NodeReader::new(&csvreader);
+        csvreader.run();
+
+        let mut dtypes = vec![];
+        loop {
+            let message = reader_node.read();
+            if message.is_eof() {
+                break;
+            }
+            let data = message.datablock().data();
+            dtypes.push(data.column("b").unwrap().dtype().clone());
+        }
+        assert_eq!(dtypes, vec![DataType::Int64, DataType::Int64]);
+    }
+
+    #[test]
+    fn test_csv_chunks_end_before_blank_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("blank.csv");
+        std::fs::write(&path, "a\n1\n2\n\n3\n4\n \n\n").unwrap();
+        let chunks = CsvChunks::open(path.to_str().unwrap(), true, 2, usize::MAX).unwrap();
+        let chunks: Vec<(Vec<u8>, bool)> = chunks.map(Result::unwrap).collect();
+        assert_eq!(
+            chunks,
+            vec![
+                (b"a\n1\n2\n".to_vec(), false),
+                (b"a\n3\n4\n".to_vec(), true)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_csv_reader_streams_chunks_of_bytes() {
+        let input_files = df!("col" => &["resources/tpc-h/data/lineitem-100.csv"]).unwrap();
+        let csvreader = CSVReaderBuilder::new()
+            .delimiter(',')
+            .has_headers(true)
+            .target_bytes(4096)
+            .build();
+        csvreader.write_to_self(0, DataMessage::from(input_files));
+        csvreader.write_to_self(0, DataMessage::eof());
+        let reader_node = NodeReader::new(&csvreader);
+        csvreader.run();
+
+        let mut heights = vec![];
+        loop {
+            let message = reader_node.read();
+            if message.is_eof() {
+                break;
+            }
+            heights.push(message.datablock().data().height());
+        }
+        assert!(heights.len() > 1);
+        assert_eq!(heights.iter().sum::<usize>(), 100);
+    }
+
+    #[test]
+    fn test_csv_reader_projects_columns_by_name() {
+        let columns = vec!["l_quantity".to_string(), "l_orderkey".to_string()];
+        let mut by_header = CSVReaderBuilder::new();
+        by_header.has_headers(true).columns(Some(columns.clone()));
+        let mut by_schema = CSVReaderBuilder::new();
+        by_schema
+            .has_headers(true)
+            .columns(Some(columns.clone()))
+            .schema(Schema::from_example("lineitem").unwrap());
+
+        for builder in [by_header, by_schema] {
+            let csvreader = builder.build();
+            let input_files = df!("col" => &["resources/tpc-h/data/lineitem-100.csv"]).unwrap();
+            csvreader.write_to_self(0, DataMessage::from(input_files));
+            csvreader.write_to_self(0, DataMessage::eof());
+            let reader_node = NodeReader::new(&csvreader);
+            csvreader.run();
+
+            let message = reader_node.read();
+            let data = message.datablock().data();
+            assert_eq!(data.get_column_names(), vec!["l_quantity", "l_orderkey"]);
+            assert_eq!(data.height(), 100);
+            assert!(reader_node.read().is_eof());
+        }
+    }
 }
@@ -0,0 +1,283 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::result::Result;
+
+use arrow_format::ipc::planus::ReadAsRoot;
+use arrow_format::ipc::FooterRef;
+use polars::export::arrow::array::{new_empty_array, Array};
+use polars::export::arrow::datatypes::Field as ArrowField;
+use polars::export::arrow::io::ipc::read;
+use polars::prelude::*;
+
+use super::base::{read_files, Chunk, FileReader, Projection, ReadPosition};
+use crate::data::Schema;
+use crate::data::*;
+use crate::graph::{ExecutionNode, OperatorState};
+use crate::processor::StreamProcessor;
+
+/// Factory for creating an [ExecutionNode] that reads the Arrow IPC (Feather v2) files named
+/// in its input, the same way [super::CSVReaderBuilder] and [super::ParquetReaderBuilder] do.
+///
+/// Every record batch of a file is written as a block of its own, as soon as it is read.
+/// The columns to read are best given by name, with [Self::columns]; with the schema of the
+/// files, the reader resolves their positions itself, and otherwise looks them up in the
+/// schema stored in the files.
+#[derive(Default)]
+pub struct IpcReaderBuilder {
+    column_names: Option<Vec<String>>,
+    projected_cols: Option<Vec<usize>>,
+    columns: Option<Vec<String>>,
+    schema: Option<Schema>,
+}
+
+impl IpcReaderBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn column_names(&mut self, column_names: Option<Vec<String>>) -> &mut Self {
+        self.column_names = column_names;
+        self
+    }
+
+    pub fn projected_cols(&mut self, projected_cols: Option<Vec<usize>>) -> &mut Self {
+        self.projected_cols = projected_cols;
+        self
+    }
+
+    /// Reads only `columns`, and writes them in this order; see [IpcReaderBuilder].
+    pub fn columns(&mut self, columns: Option<Vec<String>>) -> &mut Self {
+        self.columns = columns;
+        self
+    }
+
+    /// The schema of the files, which resolves [Self::columns] in place of
+    /// [Self::projected_cols] and [Self::column_names].
+    pub fn schema(&mut self, schema: Schema) -> &mut Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    pub fn build(&self) -> ExecutionNode<DataFrame> {
+        let projection = Projection::new(
+            self.schema.as_ref(),
+            self.columns.clone(),
+            self.projected_cols.clone(),
+            self.column_names.clone(),
+        );
+        let data_processor = IpcReader::new(projection);
+        ExecutionNode::<DataFrame>::new(Box::new(data_processor), 1)
+    }
+}
+
+/// The [FileReader] of [IpcReaderBuilder] nodes, which reads a chunk per record batch.
+struct IpcReader {
+    projection: Projection,
+    position: ReadPosition,
+}
+
+impl IpcReader {
+    pub fn new(projection: Projection) -> Self {
+        IpcReader {
+            projection,
+            position: ReadPosition::default(),
+        }
+    }
+
+    /// Opens `filename`, and reads its footer. Returned with the number of record batches
+    /// listed in the footer.
+    fn open(&self, filename: &str) -> Result<(read::FileReader<File>, usize), StreamError> {
+        let mut file = File::open(filename)?;
+        let metadata = read::read_file_metadata(&mut file)?;
+        let mut projected_cols = match (&self.projection.projected_cols, &self.projection.columns) {
+            (Some(projected_cols), _) => Some(projected_cols.clone()),
+            (None, Some(columns)) => Some(
+                columns
+                    .iter()
+                    .map(|column| Self::field_index(&metadata.schema.fields, column))
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+            (None, None) => None,
+        };
+        // The columns are read in the order of the file.
+        if let Some(projected_cols) = projected_cols.as_mut() {
+            projected_cols.sort_unstable();
+            projected_cols.dedup();
+        }
+        let num_batches = Self::num_batches(&mut file)?;
+        Ok((
+            read::FileReader::new(file, metadata, projected_cols, None),
+            num_batches,
+        ))
+    }
+
+    /// The number of record batches listed in the footer of `file`, which arrow2 keeps to
+    /// itself. The footer is the flatbuffer before the last 10 bytes (its length, then the
+    /// magic number).
+    fn num_batches(file: &mut File) -> Result<usize, StreamError> {
+        let end = file.seek(SeekFrom::End(-10))?;
+        let mut footer_len = [0u8; 4];
+        file.read_exact(&mut footer_len)?;
+        let footer_len = u32::from_le_bytes(footer_len) as u64;
+        let mut footer = vec![0u8; footer_len as usize];
+        file.seek(SeekFrom::Start(end - footer_len))?;
+        file.read_exact(&mut footer)?;
+        let invalid = |error: arrow_format::ipc::planus::Error| {
+            let message = format!("Invalid IPC footer: {}", error);
+            StreamError::new(StreamErrorKind::InvalidData, &message)
+        };
+        let batches = FooterRef::read_as_root(&footer)
+            .and_then(|footer| footer.record_batches())
+            .map_err(invalid)?;
+        Ok(batches.map_or(0, |batches| batches.len()))
+    }
+
+    fn field_index(fields: &[ArrowField], column: &str) -> Result<usize, StreamError> {
+        fields
+            .iter()
+            .position(|field| field.name == column)
+            .ok_or_else(|| {
+                let message = format!("Projected column {} not present in file", column);
+                StreamError::new(StreamErrorKind::InvalidData, &message)
+            })
+    }
+
+    /// The frame of a record batch: its columns, named and put in the order of the
+    /// projection.
+    fn frame(
+        &self,
+        fields: &[ArrowField],
+        arrays: Vec<Box<dyn Array>>,
+    ) -> Result<DataFrame, StreamError> {
+        let columns = fields
+            .iter()
+            .zip(arrays)
+            .map(|(field, array)| Series::try_from((field.name.as_str(), array)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut df = DataFrame::new(columns)?;
+        if let Some(column_names) = &self.projection.column_names {
+            df.set_column_names(column_names)?;
+        }
+        if let Some(columns) = &self.projection.columns {
+            df = df.select(columns)?;
+        }
+        Ok(df)
+    }
+}
+
+impl FileReader for IpcReader {
+    fn position(&self) -> &ReadPosition {
+        &self.position
+    }
+
+    fn read_file<'a>(
+        &'a self,
+        filename: &str,
+        _metadata: &BlockMetadata,
+    ) -> Box<dyn Iterator<Item = Result<Chunk, StreamError>> + 'a> {
+        let (reader, num_batches) = match self.open(filename) {
+            Ok(opened) => opened,
+            Err(error) => return Box::new(std::iter::once(Err(error))),
+        };
+        let fields = reader.schema().fields.clone();
+        if num_batches == 0 {
+            // A file without record batches is still read into a (empty) frame.
+            let arrays = fields
+                .iter()
+                .map(|field| new_empty_array(field.data_type().clone()))
+                .collect();
+            return Box::new(std::iter::once(
+                self.frame(&fields, arrays).map(|df| Chunk::new(df, true)),
+            ));
+        }
+        Box::new(reader.enumerate().map(move |(index, batch)| {
+            let df = self.frame(&fields, batch?.into_arrays())?;
+            Ok(Chunk::new(df, index == num_batches - 1))
+        }))
+    }
+}
+
+impl StreamProcessor<DataFrame> for IpcReader {
+    fn process_stream(
+        &self,
+        input_stream: crate::channel::MultiChannelReader<DataFrame>,
+        output_stream: crate::channel::MultiChannelBroadcaster<DataFrame>,
+    ) {
+        read_files(self, input_stream, output_stream);
+    }
+
+    fn checkpoint(&self, _channel: usize, state: &mut OperatorState) {
+        self.position.checkpoint(state);
+    }
+
+    fn restore(&mut self, state: &OperatorState) {
+        self.position.restore(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::NodeReader;
+    use crate::polars_operations::reader::testing::{column_names, read_files, write_table};
+    use crate::polars_operations::ScanFormat;
+
+    /// Reads the test table with `builder`, and returns (columns, height, progress) of each
+    /// block.
+    fn read_table(builder: &IpcReaderBuilder) -> Vec<(Vec<String>, usize, f64)> {
+        let dir = tempfile::tempdir().unwrap();
+        let file = write_table(dir.path(), &ScanFormat::Ipc);
+        let metadata = BlockMetadata::new()
+            .with_progress(0.0)
+            .with_expected_total(200.0);
+        read_files(builder.build(), &[&file], metadata)
+            .iter()
+            .map(|dblock| {
+                (
+                    column_names(dblock),
+                    dblock.data().height(),
+                    dblock.metadata().progress().unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_ipc_reader_streams_record_batches() {
+        let columns = vec!["key".to_string(), "value".to_string()];
+        assert_eq!(
+            read_table(&IpcReaderBuilder::new()),
+            vec![
+                (columns.clone(), 30, 0.15),
+                (columns.clone(), 30, 0.3),
+                (columns.clone(), 30, 0.45),
+                (columns, 10, 1.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ipc_reader_projects_columns_by_name() {
+        let mut builder = IpcReaderBuilder::new();
+        builder.columns(Some(vec!["value".to_string(), "key".to_string()]));
+        let blocks = read_table(&builder);
+        assert_eq!(blocks.len(), 4);
+        for (columns, _, _) in blocks {
+            assert_eq!(columns, vec!["value".to_string(), "key".to_string()]);
+        }
+    }
+
+    #[test]
+    fn test_ipc_reader_missing_file_ends_in_error() {
+        let ipcreader = IpcReaderBuilder::new().build();
+        let input_files = df!("col" => &["resources/missing.arrow"]).unwrap();
+        ipcreader.write_to_self(0, DataMessage::from(input_files));
+        ipcreader.write_to_self(0, DataMessage::eof());
+        let reader_node = NodeReader::new(&ipcreader);
+        ipcreader.run();
+
+        let error = reader_node.read_result().unwrap_err();
+        assert_eq!(*error.kind(), StreamErrorKind::Io);
+        assert!(error.message().starts_with("resources/missing.arrow"));
+    }
+}
@@ -1,5 +1,14 @@
+mod base;
 mod csvreader;
+mod ipcreader;
 mod parquetreader;
+mod predicate;
+mod shared_scan;
+#[cfg(test)]
+mod testing;
 
 pub use csvreader::*;
+pub use ipcreader::*;
 pub use parquetreader::*;
+pub use predicate::*;
+pub use shared_scan::*;
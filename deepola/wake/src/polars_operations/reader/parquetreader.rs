@@ -1,17 +1,42 @@
 use std::fs::File;
+use std::result::Result;
 
 // use polars::series::Series;
+use polars::export::arrow::array::{new_empty_array, Array};
+use polars::export::arrow::datatypes::Schema as ArrowSchema;
+use polars::export::arrow::io::parquet::read;
+use polars::prelude::DataType;
 use polars::prelude::*;
 
+use super::base::{read_files, Chunk, FileReader, Projection, ReadPosition};
+use super::ColumnPredicate;
+use crate::data::Schema;
 use crate::data::*;
-use crate::graph::ExecutionNode;
+use crate::graph::{ExecutionNode, OperatorState};
 use crate::processor::StreamProcessor;
-use crate::utils::log_event;
 
+/// Factory for creating an [ExecutionNode] that reads the parquet files named in its input.
+///
+/// By default, a file is written as a single block. With a number of row groups per block, a
+/// file is streamed a few row groups at a time instead. Either way, the progress of the blocks
+/// is exact: the reader counts the records of all the files from their footers before reading
+/// any of them, in place of the expected total in the metadata of its input.
+///
+/// With predicates, the reader passes on only the rows that satisfy all of them, and skips the
+/// row groups whose statistics rule them out without decoding them. The rows it leaves out
+/// still count toward the progress of the blocks.
+///
+/// The columns to read are best given by name, with [Self::columns]. With the schema of the
+/// files, the reader resolves their positions itself; otherwise, it looks them up in the
+/// schema stored in the files.
 #[derive(Default)]
 pub struct ParquetReaderBuilder {
     column_names: Option<Vec<String>>,
     projected_cols: Option<Vec<usize>>,
+    columns: Option<Vec<String>>,
+    schema: Option<Schema>,
+    row_groups_per_block: Option<usize>,
+    predicates: Vec<ColumnPredicate>,
 }
 
 impl ParquetReaderBuilder {
@@ -29,42 +54,378 @@ impl ParquetReaderBuilder {
         self
     }
 
+    /// Reads only `columns`, and writes them in this order; see [ParquetReaderBuilder].
+    pub fn columns(&mut self, columns: Option<Vec<String>>) -> &mut Self {
+        self.columns = columns;
+        self
+    }
+
+    /// The schema of the files, which resolves [Self::columns] in place of
+    /// [Self::projected_cols] and [Self::column_names].
+    pub fn schema(&mut self, schema: Schema) -> &mut Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    /// Streams every file this many row groups at a time; see [ParquetReaderBuilder].
+    pub fn row_groups_per_block(&mut self, row_groups_per_block: usize) -> &mut Self {
+        self.row_groups_per_block = Some(row_groups_per_block);
+        self
+    }
+
+    /// Reads only the rows that satisfy `predicate`, along with the predicates already set;
+    /// see [ParquetReaderBuilder]. Its column need not be projected, and is named after the
+    /// schema of the files if set, or else as the reader or the files name it. A column that is
+    /// neither numeric nor a date ends the output in an error.
+    pub fn predicate(&mut self, predicate: ColumnPredicate) -> &mut Self {
+        self.predicates.push(predicate);
+        self
+    }
+
     pub fn build(&self) -> ExecutionNode<DataFrame> {
-        let data_processor =
-            ParquetReader::new(self.column_names.clone(), self.projected_cols.clone());
+        assert!(
+            self.row_groups_per_block != Some(0),
+            "A parquet reader must read at least one row group per block"
+        );
+        let projection = Projection::new(
+            self.schema.as_ref(),
+            self.columns.clone(),
+            self.projected_cols.clone(),
+            self.column_names.clone(),
+        );
+        let data_processor = ParquetReader::new(
+            projection,
+            self.schema.clone(),
+            self.row_groups_per_block,
+            self.predicates.clone(),
+        );
         ExecutionNode::<DataFrame>::new(Box::new(data_processor), 1)
     }
 }
 
-/// A custom SetProcessor<Series> type for reading parquet files.
+/// The [FileReader] of [ParquetReaderBuilder] nodes.
 struct ParquetReader {
-    column_names: Option<Vec<String>>,
-    projected_cols: Option<Vec<usize>>,
+    projection: Projection,
+
+    /// The schema of the files, if given to the builder, which names the columns of the
+    /// predicates along with the files.
+    files_schema: Option<Schema>,
+    row_groups_per_block: Option<usize>,
+    predicates: Vec<ColumnPredicate>,
+    position: ReadPosition,
 }
 
 /// A factory method for creating the custom SetProcessor<Series> type for
 /// reading parquet files
 impl ParquetReader {
-    pub fn new(column_names: Option<Vec<String>>, projected_cols: Option<Vec<usize>>) -> Self {
+    pub fn new(
+        projection: Projection,
+        files_schema: Option<Schema>,
+        row_groups_per_block: Option<usize>,
+        predicates: Vec<ColumnPredicate>,
+    ) -> Self {
         ParquetReader {
-            column_names,
-            projected_cols,
+            projection,
+            files_schema,
+            row_groups_per_block,
+            predicates,
+            position: ReadPosition::default(),
+        }
+    }
+
+    /// Splits the row groups the predicates do not rule out into groups of at most the
+    /// number of row groups per block, each with the number of records it covers. The records
+    /// of the row groups ruled out count toward the group after them, or the last one.
+    fn group(
+        &self,
+        metadata: &read::FileMetaData,
+        schema: &ArrowSchema,
+    ) -> Result<Vec<(Vec<read::RowGroupMetaData>, usize)>, StreamError> {
+        let row_groups_per_block = self.row_groups_per_block.unwrap_or(usize::MAX);
+        let mut groups: Vec<(Vec<read::RowGroupMetaData>, usize)> = vec![];
+        let mut skipped_records = 0;
+        for row_group in metadata.row_groups.iter() {
+            if !self.may_match(schema, row_group)? {
+                skipped_records += row_group.num_rows();
+                continue;
+            }
+            match groups.last_mut() {
+                Some((row_groups, records)) if row_groups.len() < row_groups_per_block => {
+                    row_groups.push(row_group.clone());
+                    *records += row_group.num_rows() + skipped_records;
+                }
+                _ => groups.push((
+                    vec![row_group.clone()],
+                    row_group.num_rows() + skipped_records,
+                )),
+            }
+            skipped_records = 0;
+        }
+        match groups.last_mut() {
+            Some((_, records)) => *records += skipped_records,
+            // A file without rows to read is still read into a (empty) frame.
+            None => groups.push((vec![], skipped_records)),
+        }
+        Ok(groups)
+    }
+
+    /// Whether the statistics of `row_group` allow some of its rows to satisfy all the
+    /// predicates. Row groups without statistics are never ruled out.
+    fn may_match(
+        &self,
+        schema: &ArrowSchema,
+        row_group: &read::RowGroupMetaData,
+    ) -> Result<bool, StreamError> {
+        for predicate in self.predicates.iter() {
+            let field = &schema.fields[self.predicate_index(schema, predicate)?];
+            let statistics = read::statistics::deserialize(field, std::slice::from_ref(row_group))?;
+            let min = Self::statistic(statistics.min_value)?;
+            let max = Self::statistic(statistics.max_value)?;
+            if let (Some(min), Some(max)) = (min, max) {
+                if !predicate.may_match(min, max) {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// The value of a min or max statistic of a single row group, if it is numeric.
+    fn statistic(value: Box<dyn Array>) -> Result<Option<f64>, StreamError> {
+        let series = Series::try_from(("statistic", value))?;
+        let series = series.to_physical_repr().cast(&DataType::Float64)?;
+        let value = series.f64()?.get(0);
+        Ok(value)
+    }
+
+    /// The position of `column` in `schema`. `role` tells what the column is for in the error.
+    fn field_index(schema: &ArrowSchema, role: &str, column: &str) -> Result<usize, StreamError> {
+        schema
+            .fields
+            .iter()
+            .position(|field| field.name == column)
+            .ok_or_else(|| {
+                let message = format!("{} column {} not present in file", role, column);
+                StreamError::new(StreamErrorKind::InvalidData, &message)
+            })
+    }
+
+    /// The position in `schema` of the column of `predicate`, which is named either the way
+    /// [Self::named_index] tells, or the way the file does. The column must be numeric or a
+    /// date.
+    fn predicate_index(
+        &self,
+        schema: &ArrowSchema,
+        predicate: &ColumnPredicate,
+    ) -> Result<usize, StreamError> {
+        let index = match self.named_index(predicate.column()) {
+            Some(index) => index,
+            None => Self::field_index(schema, "Predicate", predicate.column())?,
+        };
+        predicate.check(&DataType::from(schema.fields[index].data_type()))?;
+        Ok(index)
+    }
+
+    /// The position in the files of `column`, named after the schema given to the builder, or
+    /// else the way the reader names the columns it reads.
+    fn named_index(&self, column: &str) -> Option<usize> {
+        if let Some(files_schema) = &self.files_schema {
+            return files_schema
+                .columns
+                .iter()
+                .position(|schema_column| schema_column.name == column);
+        }
+        let column_names = self.projection.column_names.as_ref()?;
+        let position = column_names.iter().position(|name| name == column)?;
+        match &self.projection.projected_cols {
+            Some(projected_cols) => Some(projected_cols[position]),
+            None => Some(position),
+        }
+    }
+
+    /// The columns to read from a file of `schema`: the projected columns, followed by the
+    /// columns of the predicates that are not projected.
+    fn read_schema(&self, schema: &ArrowSchema) -> Result<ReadSchema, StreamError> {
+        let mut indexes = match (&self.projection.projected_cols, &self.projection.columns) {
+            (Some(projected_cols), _) => projected_cols.clone(),
+            (None, Some(columns)) => columns
+                .iter()
+                .map(|column| Self::field_index(schema, "Projected", column))
+                .collect::<Result<Vec<_>, _>>()?,
+            (None, None) => (0..schema.fields.len()).collect(),
+        };
+        let projected_len = indexes.len();
+        let mut predicate_cols = vec![];
+        for predicate in self.predicates.iter() {
+            let index = self.predicate_index(schema, predicate)?;
+            let position = match indexes.iter().position(|read| *read == index) {
+                Some(position) => position,
+                None => {
+                    indexes.push(index);
+                    indexes.len() - 1
+                }
+            };
+            predicate_cols.push(position);
+        }
+        let mut fields = schema.clone();
+        fields.fields = indexes
+            .iter()
+            .map(|index| schema.fields[*index].clone())
+            .collect();
+        Ok(ReadSchema {
+            fields,
+            projected_len,
+            predicate_cols,
+        })
+    }
+
+    /// Reads `row_groups` of `file` into a single frame of the rows that satisfy the
+    /// predicates, and the projected columns of `schema`.
+    fn read_row_groups(
+        &self,
+        file: &File,
+        schema: &ReadSchema,
+        row_groups: Vec<read::RowGroupMetaData>,
+    ) -> Result<DataFrame, StreamError> {
+        let reader = read::RowGroupReader::new(
+            file.try_clone()?,
+            schema.fields.clone(),
+            None,
+            row_groups,
+            None,
+            None,
+        );
+        let chunks = reader
+            .map(|row_group| row_group?.collect::<Result<Vec<_>, _>>())
+            .collect::<Result<Vec<_>, _>>()?
+            .concat();
+        let columns = schema
+            .fields
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(index, field)| {
+                let mut arrays: Vec<ArrayRef> = chunks
+                    .iter()
+                    .map(|chunk| chunk.arrays()[index].clone())
+                    .collect();
+                if arrays.is_empty() {
+                    arrays.push(new_empty_array(field.data_type().clone()));
+                }
+                Series::try_from((field.name.as_str(), arrays))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut df = DataFrame::new(columns)?;
+        let mask = self
+            .predicates
+            .iter()
+            .zip(schema.predicate_cols.iter())
+            .map(|(predicate, index)| predicate.mask(&df.get_columns()[*index]))
+            .reduce(|mask, other| Ok(mask? & other?))
+            .transpose()?;
+        if let Some(mask) = mask {
+            df = df.filter(&mask)?;
         }
+        let df = DataFrame::new(df.get_columns()[..schema.projected_len].to_vec())?;
+        self.finish(df)
+    }
+
+    /// Opens `filename`, and reads its footer and schema.
+    fn open(&self, filename: &str) -> Result<(File, read::FileMetaData, ArrowSchema), StreamError> {
+        let mut file = File::open(filename)?;
+        let metadata = read::read_metadata(&mut file)?;
+        let schema = read::infer_schema(&metadata)?;
+        Ok((file, metadata, schema))
     }
 
-    fn dataframe_from_filename(&self, filename: &str) -> DataFrame {
-        let file = File::open(filename).unwrap();
+    fn dataframe_from_filename(&self, filename: &str) -> Result<DataFrame, StreamError> {
+        let file = File::open(filename)?;
         let mut reader = polars::prelude::ParquetReader::new(file);
-        if self.projected_cols.is_some() {
-            reader = reader.with_projection(self.projected_cols.clone());
+        let projection = &self.projection;
+        if projection.projected_cols.is_some() {
+            reader = reader.with_projection(projection.projected_cols.clone());
+        } else if projection.columns.is_some() {
+            reader = reader.with_columns(projection.columns.clone());
+        }
+        self.finish(reader.finish()?)
+    }
+
+    /// Names the columns read, and puts them in the order of the projection.
+    fn finish(&self, mut df: DataFrame) -> Result<DataFrame, StreamError> {
+        if let Some(column_names) = &self.projection.column_names {
+            df.set_column_names(column_names)?;
+        }
+        if let Some(columns) = &self.projection.columns {
+            df = df.select(columns)?;
+        }
+        Ok(df)
+    }
+}
+
+/// The columns a [ParquetReader] reads from a file.
+struct ReadSchema {
+    /// The projected columns, followed by the columns of the predicates that are not
+    /// projected.
+    fields: ArrowSchema,
+    projected_len: usize,
+
+    /// The position in `fields` of the column of each predicate.
+    predicate_cols: Vec<usize>,
+}
+
+impl FileReader for ParquetReader {
+    fn position(&self) -> &ReadPosition {
+        &self.position
+    }
+
+    /// The whole file, or its groups of row groups if a number of row groups per block is
+    /// set.
+    fn read_file<'a>(
+        &'a self,
+        filename: &str,
+        _metadata: &BlockMetadata,
+    ) -> Box<dyn Iterator<Item = Result<Chunk, StreamError>> + 'a> {
+        if self.row_groups_per_block.is_none() && self.predicates.is_empty() {
+            let output_df = self.dataframe_from_filename(filename);
+            return Box::new(std::iter::once(output_df.map(|df| Chunk::new(df, true))));
         }
-        let mut df = reader.finish().unwrap();
-        if self.column_names.is_some() {
-            if let Some(a) = &self.column_names {
-                df.set_column_names(a).unwrap();
+        let opened = self.open(filename).and_then(|(file, metadata, schema)| {
+            let groups = self.group(&metadata, &schema)?;
+            let read_schema = self.read_schema(&schema)?;
+            Ok((file, groups, read_schema))
+        });
+        let (file, groups, read_schema) = match opened {
+            Ok(opened) => opened,
+            Err(error) => return Box::new(std::iter::once(Err(error))),
+        };
+        let num_groups = groups.len();
+        Box::new(
+            groups
+                .into_iter()
+                .enumerate()
+                .map(move |(group_index, (row_groups, records))| {
+                    let df = self.read_row_groups(&file, &read_schema, row_groups)?;
+                    Ok(Chunk {
+                        df,
+                        records,
+                        last: group_index == num_groups - 1,
+                    })
+                }),
+        )
+    }
+
+    /// From the footers of the files.
+    fn total_records(&self, filenames: &DataFrame) -> Result<Option<usize>, StreamError> {
+        let mut total_records = 0;
+        for series in filenames.iter() {
+            for filename in series.utf8()?.into_iter().flatten() {
+                let num_rows = File::open(filename)
+                    .map_err(StreamError::from)
+                    .and_then(|mut file| Ok(read::read_metadata(&mut file)?.num_rows));
+                total_records += num_rows.map_err(|error| error.context(filename))?;
             }
         }
-        df
+        Ok(Some(total_records))
     }
 }
 
@@ -74,61 +435,168 @@ impl StreamProcessor<DataFrame> for ParquetReader {
         input_stream: crate::channel::MultiChannelReader<DataFrame>,
         output_stream: crate::channel::MultiChannelBroadcaster<DataFrame>,
     ) {
-        loop {
-            let channel_seq = 0;
-            let message = input_stream.read(channel_seq);
-            log_event("process-message", "start");
-            match message.payload() {
-                Payload::EOF => {
-                    output_stream.write(message);
-                    log_event("process-message", "end");
-                    break;
-                }
-                Payload::Signal(_) => {
-                    log_event("process-message", "end");
-                    break;
-                }
-                Payload::Some(dblock) => {
-                    let mut metadata = dblock.metadata().clone();
-                    let mut expected_total_records =
-                        if let Some(count) = metadata.get(DATABLOCK_TOTAL_RECORDS) {
-                            f64::from(count)
-                        } else {
-                            log::warn!("Missing {} in metadata", DATABLOCK_TOTAL_RECORDS);
-                            1.0
-                        };
-                    let mut currect_total_records = 0.0;
-                    for series in dblock.data().iter() {
-                        // This must be a length-one Polars series containing
-                        // file names in its rows
-                        let rows = series.utf8().unwrap();
-                        let num_files = rows.len();
-
-                        // each file name produces multiple Series (each is a column)
-                        for (index,filename) in rows.into_iter().enumerate() {
-                            let output_df = self.dataframe_from_filename(filename.unwrap());
-
-                            // Update record count
-                            currect_total_records += output_df.height() as f64;
-                            if index == num_files - 1 {
-                                // lineitem's cardinality estimate of 6_000_000 * SF is approximate.
-                                // For some scales, this value is smaller than 6_000_000 * SF.
-                                expected_total_records = currect_total_records;
-                            }
-                            if let Some(cardinality) = metadata.get_mut(DATABLOCK_CARDINALITY) {
-                                *cardinality = MetaCell::from(f64::min(1.0,
-                                    currect_total_records / expected_total_records));
-                            }
-
-                            // Compose and write output message
-                            let output_dblock = DataBlock::new(output_df, metadata.clone());
-                            let output_message = DataMessage::from(output_dblock);
-                            output_stream.write(output_message);
-                        }
-                    }
-                    log_event("process-message", "end");
-                }
-            }
+        read_files(self, input_stream, output_stream);
+    }
+
+    fn checkpoint(&self, _channel: usize, state: &mut OperatorState) {
+        self.position.checkpoint(state);
+    }
+
+    fn restore(&mut self, state: &OperatorState) {
+        self.position.restore(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::NodeReader;
+    use crate::polars_operations::reader::testing::{column_names, read_files, write_table};
+    use crate::polars_operations::ScanFormat;
+
+    /// Reads `files` with `builder`, and returns (height, progress) of each block.
+    fn read_blocks(builder: &ParquetReaderBuilder, files: &[&str]) -> Vec<(usize, f64)> {
+        // The expected total in the metadata is off, and is replaced by the exact one.
+        let metadata = BlockMetadata::new()
+            .with_progress(0.0)
+            .with_expected_total(1000.0);
+        read_files(builder.build(), files, metadata)
+            .iter()
+            .map(|dblock| {
+                (
+                    dblock.data().height(),
+                    dblock.metadata().progress().unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parquet_reader_exact_progress() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = write_table(dir.path(), &ScanFormat::Parquet);
+        let blocks = read_blocks(&ParquetReaderBuilder::new(), &[&file, &file]);
+        assert_eq!(blocks, vec![(100, 0.5), (100, 1.0)]);
+    }
+
+    #[test]
+    fn test_parquet_reader_streams_row_groups() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = write_table(dir.path(), &ScanFormat::Parquet);
+        let blocks = read_blocks(
+            ParquetReaderBuilder::new().row_groups_per_block(1),
+            &[&file],
+        );
+        assert_eq!(blocks, vec![(30, 0.3), (30, 0.6), (30, 0.9), (10, 1.0)]);
+    }
+
+    #[test]
+    fn test_parquet_reader_streams_groups_of_row_groups() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = write_table(dir.path(), &ScanFormat::Parquet);
+        let mut builder = ParquetReaderBuilder::new();
+        builder
+            .row_groups_per_block(3)
+            .projected_cols(Some(vec![1]))
+            .column_names(Some(vec!["half".to_string()]));
+        let blocks = read_blocks(&builder, &[&file]);
+        assert_eq!(blocks, vec![(90, 0.9), (10, 1.0)]);
+    }
+
+    #[test]
+    fn test_parquet_reader_skips_row_groups() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = write_table(dir.path(), &ScanFormat::Parquet);
+        let mut builder = ParquetReaderBuilder::new();
+        builder
+            .row_groups_per_block(1)
+            .predicate(ColumnPredicate::Range("key".into(), 35.0, 50.0));
+        // Only the second row group is read; the others still count toward the progress.
+        assert_eq!(read_blocks(&builder, &[&file]), vec![(15, 1.0)]);
+    }
+
+    #[test]
+    fn test_parquet_reader_filters_unprojected_column() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = write_table(dir.path(), &ScanFormat::Parquet);
+        let mut builder = ParquetReaderBuilder::new();
+        builder
+            .row_groups_per_block(1)
+            .projected_cols(Some(vec![1]))
+            .predicate(ColumnPredicate::GtEq("key".into(), 65.0))
+            .predicate(ColumnPredicate::Lt("value".into(), 49.0));
+        assert_eq!(read_blocks(&builder, &[&file]), vec![(25, 0.9), (8, 1.0)]);
+    }
+
+    #[test]
+    fn test_parquet_reader_filters_renamed_column() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = write_table(dir.path(), &ScanFormat::Parquet);
+        let mut renamed = ParquetReaderBuilder::new();
+        renamed
+            .row_groups_per_block(1)
+            .projected_cols(Some(vec![1]))
+            .column_names(Some(vec!["half".to_string()]))
+            .predicate(ColumnPredicate::Lt("half".into(), 49.0));
+        let mut by_schema = ParquetReaderBuilder::new();
+        by_schema
+            .row_groups_per_block(1)
+            .schema(Schema::new(
+                "table".into(),
+                vec![
+                    Column::from_field("id".into(), crate::data::DataType::Integer),
+                    Column::from_field("half".into(), crate::data::DataType::Float),
+                ],
+            ))
+            .columns(Some(vec!["half".to_string()]))
+            .predicate(ColumnPredicate::GtEq("id".into(), 65.0));
+        assert_eq!(
+            read_blocks(&renamed, &[&file]),
+            vec![(30, 0.3), (30, 0.6), (30, 0.9), (8, 1.0)]
+        );
+        assert_eq!(
+            read_blocks(&by_schema, &[&file]),
+            vec![(25, 0.9), (10, 1.0)]
+        );
+    }
+
+    #[test]
+    fn test_parquet_reader_rejects_text_predicate() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("names.parquet");
+        let mut df = df!("name" => &["a", "b"]).unwrap();
+        ParquetWriter::new(File::create(&path).unwrap())
+            .finish(&mut df)
+            .unwrap();
+        let mut builder = ParquetReaderBuilder::new();
+        builder.predicate(ColumnPredicate::Eq("name".into(), 1.0));
+        let parquetreader = builder.build();
+        let input_files = df!("col" => &[path.to_str().unwrap()]).unwrap();
+        parquetreader.write_to_self(0, DataMessage::from(input_files));
+        parquetreader.write_to_self(0, DataMessage::eof());
+        let reader_node = NodeReader::new(&parquetreader);
+        parquetreader.run();
+
+        let error = reader_node.read_result().unwrap_err();
+        assert_eq!(*error.kind(), StreamErrorKind::InvalidData);
+        assert!(error
+            .message()
+            .ends_with("Predicate column name of type str is neither numeric nor a date"));
+    }
+
+    #[test]
+    fn test_parquet_reader_projects_columns_by_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = write_table(dir.path(), &ScanFormat::Parquet);
+        let columns = vec!["value".to_string(), "key".to_string()];
+        let mut whole_files = ParquetReaderBuilder::new();
+        whole_files.columns(Some(columns.clone()));
+        let mut row_groups = ParquetReaderBuilder::new();
+        row_groups.columns(Some(columns)).row_groups_per_block(2);
+
+        for builder in [whole_files, row_groups] {
+            let blocks = read_files(builder.build(), &[&file], BlockMetadata::new());
+            assert_eq!(column_names(&blocks[0]), vec!["value", "key"]);
         }
     }
 }
@@ -0,0 +1,116 @@
+use polars::prelude::*;
+use std::result::Result;
+
+use crate::data::{StreamError, StreamErrorKind};
+
+/// A comparison of a column with a constant, which a reader evaluates as it reads so that
+/// the rows that do not satisfy it are never passed on. Numeric columns compare by value, and
+/// date columns by days since the epoch; a predicate on a column of another type is an error.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnPredicate {
+    Eq(String, f64),
+    Lt(String, f64),
+    LtEq(String, f64),
+    Gt(String, f64),
+    GtEq(String, f64),
+
+    /// `lower <= column < upper`, e.g., the dates of a year.
+    Range(String, f64, f64),
+}
+
+impl ColumnPredicate {
+    pub fn column(&self) -> &str {
+        match self {
+            ColumnPredicate::Eq(column, _)
+            | ColumnPredicate::Lt(column, _)
+            | ColumnPredicate::LtEq(column, _)
+            | ColumnPredicate::Gt(column, _)
+            | ColumnPredicate::GtEq(column, _)
+            | ColumnPredicate::Range(column, _, _) => column,
+        }
+    }
+
+    /// Whether any value between `min` and `max` (inclusive) may satisfy the predicate.
+    pub fn may_match(&self, min: f64, max: f64) -> bool {
+        match self {
+            ColumnPredicate::Eq(_, value) => min <= *value && *value <= max,
+            ColumnPredicate::Lt(_, value) => min < *value,
+            ColumnPredicate::LtEq(_, value) => min <= *value,
+            ColumnPredicate::Gt(_, value) => max > *value,
+            ColumnPredicate::GtEq(_, value) => max >= *value,
+            ColumnPredicate::Range(_, lower, upper) => max >= *lower && min < *upper,
+        }
+    }
+
+    /// Fails unless `dtype`, the type of the column, is numeric or a date: the values of
+    /// other columns do not compare with a number, and would all be read as null.
+    pub(crate) fn check(&self, dtype: &DataType) -> Result<(), StreamError> {
+        if dtype.is_numeric() || *dtype == DataType::Date {
+            return Ok(());
+        }
+        let message = format!(
+            "Predicate column {} of type {} is neither numeric nor a date",
+            self.column(),
+            dtype
+        );
+        Err(StreamError::new(StreamErrorKind::InvalidData, &message))
+    }
+
+    /// The values of `column` that satisfy the predicate; null values do not.
+    pub fn mask(&self, column: &Series) -> Result<BooleanChunked, StreamError> {
+        self.check(column.dtype())?;
+        let values = column.to_physical_repr().cast(&DataType::Float64)?;
+        let values = values.f64()?;
+        let mask = match self {
+            ColumnPredicate::Eq(_, value) => values.equal(*value),
+            ColumnPredicate::Lt(_, value) => values.lt(*value),
+            ColumnPredicate::LtEq(_, value) => values.lt_eq(*value),
+            ColumnPredicate::Gt(_, value) => values.gt(*value),
+            ColumnPredicate::GtEq(_, value) => values.gt_eq(*value),
+            ColumnPredicate::Range(_, lower, upper) => values.gt_eq(*lower) & values.lt(*upper),
+        };
+        Ok(mask)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_out_ranges() {
+        let predicate = ColumnPredicate::Range("d".into(), 10.0, 20.0);
+        assert!(predicate.may_match(0.0, 10.0));
+        assert!(predicate.may_match(19.0, 30.0));
+        assert!(!predicate.may_match(0.0, 9.0));
+        assert!(!predicate.may_match(20.0, 30.0));
+        assert!(!ColumnPredicate::Lt("d".into(), 5.0).may_match(5.0, 8.0));
+        assert!(ColumnPredicate::LtEq("d".into(), 5.0).may_match(5.0, 8.0));
+        assert!(!ColumnPredicate::Eq("d".into(), 9.0).may_match(5.0, 8.0));
+    }
+
+    #[test]
+    fn masks_rows() {
+        let df = df!("d" => &[Some(1), Some(5), None, Some(9)]).unwrap();
+        let column = df.column("d").unwrap();
+        let mask = ColumnPredicate::Range("d".into(), 2.0, 9.0)
+            .mask(column)
+            .unwrap();
+        assert_eq!(df.filter(&mask).unwrap().height(), 1);
+        let mask = ColumnPredicate::GtEq("d".into(), 5.0).mask(column).unwrap();
+        assert_eq!(df.filter(&mask).unwrap().height(), 2);
+    }
+
+    #[test]
+    fn rejects_text_column() {
+        let column = Series::new("s", &["1", "2"]);
+        let error = ColumnPredicate::Eq("s".into(), 1.0)
+            .mask(&column)
+            .unwrap_err();
+        assert_eq!(*error.kind(), StreamErrorKind::InvalidData);
+        assert_eq!(
+            error.message(),
+            "Predicate column s of type str is neither numeric nor a date"
+        );
+    }
+}
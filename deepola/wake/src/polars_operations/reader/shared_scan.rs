@@ -0,0 +1,404 @@
+use polars::prelude::*;
+use std::collections::BTreeSet;
+use std::result::Result;
+
+use super::{CSVReaderBuilder, IpcReaderBuilder, ParquetReaderBuilder};
+use crate::channel::{ChannelWriter, MultiChannelBroadcaster, MultiChannelReader};
+use crate::data::{BlockMetadata, DataBlock, DataMessage, Payload, Schema, Signal, StreamError};
+use crate::graph::{ExecutionError, ExecutionNode, ExecutionService, Subscribable};
+use crate::processor::{SimpleMapper, StreamProcessor};
+use crate::utils::log_event;
+
+/// The format of the files a [SharedScan] reads.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScanFormat {
+    Csv { delimiter: char, has_headers: bool },
+    Parquet,
+    Ipc,
+}
+
+pub struct SharedScanBuilder {
+    schema: Option<Schema>,
+    input_files: Vec<String>,
+    total_records: Option<f64>,
+    format: ScanFormat,
+}
+
+impl Default for SharedScanBuilder {
+    fn default() -> Self {
+        SharedScanBuilder {
+            schema: None,
+            input_files: vec![],
+            total_records: None,
+            format: ScanFormat::Parquet,
+        }
+    }
+}
+
+impl SharedScanBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The schema of the table the files make up. Required.
+    pub fn schema(&mut self, schema: Schema) -> &mut Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    pub fn input_files(&mut self, input_files: Vec<String>) -> &mut Self {
+        self.input_files = input_files;
+        self
+    }
+
+    /// The (estimated) number of records in the table, from which the reader derives the
+    /// [BlockMetadata::progress] of the blocks it writes. Parquet files are counted exactly
+    /// from their footers instead.
+    pub fn total_records(&mut self, total_records: f64) -> &mut Self {
+        self.total_records = Some(total_records);
+        self
+    }
+
+    pub fn format(&mut self, format: ScanFormat) -> &mut Self {
+        self.format = format;
+        self
+    }
+
+    pub fn build(&self) -> SharedScan {
+        SharedScan {
+            schema: self
+                .schema
+                .clone()
+                .expect("A shared scan requires the schema of its table"),
+            input_files: self.input_files.clone(),
+            total_records: self.total_records,
+            format: self.format.clone(),
+            consumers: vec![],
+            consumer_writers: vec![],
+            service: ExecutionService::create(),
+            started: false,
+        }
+    }
+}
+
+/// A scan of a table shared by queries running at the same time, e.g., q1, q6 and q14 over
+/// lineitem: each file is read once, with the union of the columns the queries read, and
+/// passed on to every query.
+///
+/// Each query attaches with [Self::attach], which returns the source node to build the query
+/// on in place of a reader node. The blocks a query receives carry the metadata of the scan,
+/// so its progress follows the [BlockMetadata::progress] of the scan. The scan runs on a
+/// service of its own, and reads as fast as the slowest query consumes; a query that is
+/// stopped or dropped no longer receives blocks, and once all of them are, the scan stops.
+pub struct SharedScan {
+    schema: Schema,
+    input_files: Vec<String>,
+    total_records: Option<f64>,
+    format: ScanFormat,
+
+    /// The columns each attached query reads, or None for all of them.
+    consumers: Vec<Option<Vec<String>>>,
+
+    /// The input channels of the nodes returned by [Self::attach].
+    consumer_writers: Vec<ChannelWriter<DataFrame>>,
+
+    service: ExecutionService<DataFrame>,
+    started: bool,
+}
+
+impl SharedScan {
+    /// Returns the source node of a query reading `columns` of the table, or all of them if
+    /// None. Queries must attach before the scan runs.
+    ///
+    /// Panics if the schema of the table lacks some of the columns.
+    pub fn attach(&mut self, columns: Option<Vec<&str>>) -> ExecutionNode<DataFrame> {
+        assert!(
+            !self.started,
+            "Queries must attach to a shared scan before it runs"
+        );
+        let columns: Option<Vec<String>> =
+            columns.map(|columns| columns.iter().map(|column| column.to_string()).collect());
+        if let Some(columns) = &columns {
+            if let Err(message) = self.schema.require_columns("Attached", columns) {
+                panic!("{}", message);
+            }
+        }
+        let node = ExecutionNode::from(SimpleMapper::identity());
+        self.consumers.push(columns);
+        self.consumer_writers.push(node.self_writer(0));
+        node
+    }
+
+    /// Starts reading the files.
+    pub fn run(&mut self) {
+        assert!(!self.started, "A shared scan runs only once");
+        self.started = true;
+
+        let (projected_cols, column_names) = match self.projection() {
+            Some((projected_cols, column_names)) => (Some(projected_cols), Some(column_names)),
+            None => (None, None),
+        };
+        let reader = match self.format {
+            ScanFormat::Csv {
+                delimiter,
+                has_headers,
+            } => CSVReaderBuilder::new()
+                .delimiter(delimiter)
+                .has_headers(has_headers)
+                .column_names(column_names)
+                .projected_cols(projected_cols)
+                .build(),
+            ScanFormat::Parquet => ParquetReaderBuilder::new()
+                .column_names(column_names)
+                .projected_cols(projected_cols)
+                .build(),
+            ScanFormat::Ipc => IpcReaderBuilder::new()
+                .column_names(column_names)
+                .projected_cols(projected_cols)
+                .build(),
+        };
+
+        let fan_out = ExecutionNode::<DataFrame>::new(
+            Box::new(ScanFanOut {
+                consumers: self.consumers.clone(),
+                reader: reader.self_writer(0),
+            }),
+            1,
+        );
+        fan_out.subscribe_to_node(&reader, 0);
+        for writer in self.consumer_writers.drain(..) {
+            fan_out.add(writer);
+        }
+
+        let mut metadata = BlockMetadata::from_schema(self.schema.clone());
+        if let Some(total_records) = self.total_records {
+            metadata.set_expected_total(total_records);
+        }
+        let input_files = df!("col" => &self.input_files).unwrap();
+        reader.write_to_self(0, DataMessage::from(DataBlock::new(input_files, metadata)));
+        reader.write_to_self(0, DataMessage::eof());
+
+        self.service.add(reader);
+        self.service.add(fan_out);
+        self.service.run();
+    }
+
+    /// Waits until the scan has read all the files, or has stopped.
+    pub fn join(&mut self) -> Result<(), ExecutionError> {
+        self.service.join()
+    }
+
+    /// The indexes and names of the union of the columns the queries read, in the order of
+    /// the schema, or None if any query reads all of them. The columns are in the schema, as
+    /// [Self::attach] checks.
+    fn projection(&self) -> Option<(Vec<usize>, Vec<String>)> {
+        let mut indexes = BTreeSet::new();
+        for columns in self.consumers.iter() {
+            for column in columns.as_ref()?.iter() {
+                indexes.insert(self.schema.index(column));
+            }
+        }
+        let names = indexes
+            .iter()
+            .map(|index| self.schema.get_column_from_index(*index).name)
+            .collect();
+        Some((indexes.into_iter().collect(), names))
+    }
+}
+
+/// Passes every block the reader writes on to each query, with the columns the query reads.
+struct ScanFanOut {
+    /// The columns the i-th output reads, or None for all of them.
+    consumers: Vec<Option<Vec<String>>>,
+
+    /// The input channel of the reader, to stop it once no query is left to read.
+    reader: ChannelWriter<DataFrame>,
+}
+
+impl StreamProcessor<DataFrame> for ScanFanOut {
+    fn process_stream(
+        &self,
+        input_stream: MultiChannelReader<DataFrame>,
+        output_stream: MultiChannelBroadcaster<DataFrame>,
+    ) {
+        let mut open = vec![true; output_stream.len()];
+        let mut stopped = false;
+        loop {
+            let message = input_stream.read(0);
+            log_event("process-message", "start");
+            match message.payload() {
+                Payload::EOF | Payload::Signal(Signal::STOP) | Payload::Error(_) => {
+                    for (seq_no, _) in open.iter().enumerate().filter(|(_, open)| **open) {
+                        output_stream.write_to_if_open(seq_no, message.clone());
+                    }
+                    log_event("process-message", "end");
+                    break;
+                }
+                Payload::Signal(_) => {
+                    log_event("process-message", "end");
+                }
+                Payload::Some(dblock) => {
+                    for (seq_no, is_open) in open.iter_mut().enumerate() {
+                        if !*is_open {
+                            continue;
+                        }
+                        let df = match &self.consumers[seq_no] {
+                            Some(columns) => dblock.data().select(columns),
+                            None => Ok(dblock.data().clone()),
+                        };
+                        match df {
+                            Ok(df) => {
+                                let output_dblock = DataBlock::new(df, dblock.metadata().clone());
+                                *is_open = output_stream
+                                    .write_to_if_open(seq_no, DataMessage::from(output_dblock));
+                            }
+                            Err(error) => {
+                                // The files lack some of the columns of the query; the error
+                                // ends its output in place of EOF.
+                                let error = StreamError::from(error);
+                                log::error!("{}", error);
+                                output_stream.write_to_if_open(seq_no, DataMessage::error(error));
+                                *is_open = false;
+                            }
+                        }
+                    }
+                    if !stopped && !open.contains(&true) {
+                        log::debug!("No query reads the shared scan anymore; stopping it");
+                        self.reader.write_if_open(DataMessage::stop());
+                        stopped = true;
+                    }
+                    log_event("process-message", "end");
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Column, DataType};
+    use crate::graph::NodeReader;
+    use crate::polars_operations::reader::testing::{column_names, read_blocks, write_table};
+
+    fn lineitem_scan() -> SharedScan {
+        SharedScanBuilder::new()
+            .schema(Schema::from_example("lineitem").unwrap())
+            .input_files(vec![
+                "resources/tpc-h/data/lineitem-100.csv".to_string(),
+                "resources/tpc-h/data/lineitem-100.csv".to_string(),
+            ])
+            .total_records(200.0)
+            .format(ScanFormat::Csv {
+                delimiter: ',',
+                has_headers: true,
+            })
+            .build()
+    }
+
+    /// Reads the blocks of a query, and returns (cardinality, column names) of each of them.
+    fn read_query(reader_node: &NodeReader<DataFrame>) -> Vec<(f64, Vec<String>)> {
+        read_blocks(reader_node)
+            .iter()
+            .map(|dblock| (dblock.metadata().progress().unwrap(), column_names(dblock)))
+            .collect()
+    }
+
+    #[test]
+    fn queries_read_their_columns_of_one_scan() {
+        let mut scan = lineitem_scan();
+        let q1 = scan.attach(Some(vec!["l_quantity", "l_orderkey"]));
+        let q6 = scan.attach(Some(vec!["l_discount", "l_quantity"]));
+        assert_eq!(
+            scan.projection(),
+            Some((
+                vec![0, 4, 6],
+                vec![
+                    "l_orderkey".to_string(),
+                    "l_quantity".to_string(),
+                    "l_discount".to_string()
+                ]
+            ))
+        );
+        let q1_reader = NodeReader::new(&q1);
+        let q6_reader = NodeReader::new(&q6);
+        let mut q1_service = ExecutionService::create();
+        q1_service.add(q1);
+        let mut q6_service = ExecutionService::create();
+        q6_service.add(q6);
+        q1_service.run();
+        q6_service.run();
+        scan.run();
+
+        let q1_columns = vec!["l_quantity".to_string(), "l_orderkey".to_string()];
+        assert_eq!(
+            read_query(&q1_reader),
+            vec![(0.5, q1_columns.clone()), (1.0, q1_columns)]
+        );
+        let q6_columns = vec!["l_discount".to_string(), "l_quantity".to_string()];
+        assert_eq!(
+            read_query(&q6_reader),
+            vec![(0.5, q6_columns.clone()), (1.0, q6_columns)]
+        );
+        scan.join().unwrap();
+        q1_service.join().unwrap();
+        q6_service.join().unwrap();
+    }
+
+    #[test]
+    fn any_query_reading_all_columns_reads_all_of_them() {
+        let mut scan = lineitem_scan();
+        scan.attach(Some(vec!["l_quantity"]));
+        scan.attach(None);
+        assert_eq!(scan.projection(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Attached column l_missing not present in input schema")]
+    fn rejects_missing_column() {
+        lineitem_scan().attach(Some(vec!["l_quantity", "l_missing"]));
+    }
+
+    #[test]
+    fn scans_every_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let formats = [
+            ScanFormat::Csv {
+                delimiter: '|',
+                has_headers: true,
+            },
+            ScanFormat::Parquet,
+            ScanFormat::Ipc,
+        ];
+        for format in formats {
+            let file = write_table(dir.path(), &format);
+            let mut scan = SharedScanBuilder::new()
+                .schema(Schema::new(
+                    "table".into(),
+                    vec![
+                        Column::from_field("key".into(), DataType::Integer),
+                        Column::from_field("value".into(), DataType::Float),
+                    ],
+                ))
+                .input_files(vec![file])
+                .total_records(100.0)
+                .format(format)
+                .build();
+            let query = scan.attach(Some(vec!["value"]));
+            let reader_node = NodeReader::new(&query);
+            let mut service = ExecutionService::create();
+            service.add(query);
+            service.run();
+            scan.run();
+
+            let blocks = read_blocks(&reader_node);
+            let heights: usize = blocks.iter().map(|dblock| dblock.data().height()).sum();
+            assert_eq!(heights, 100);
+            let last = blocks.last().unwrap();
+            assert_eq!(last.metadata().progress(), Some(1.0));
+            assert_eq!(column_names(last), vec!["value".to_string()]);
+            scan.join().unwrap();
+            service.join().unwrap();
+        }
+    }
+}
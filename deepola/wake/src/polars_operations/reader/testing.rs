@@ -0,0 +1,113 @@
+//! Test helpers shared by the reader nodes.
+
+use polars::export::arrow::io::parquet::write as parquet_write;
+use polars::prelude::*;
+use std::fs::File;
+use std::path::Path;
+
+use super::ScanFormat;
+use crate::data::{BlockMetadata, DataBlock, DataMessage};
+use crate::graph::{ExecutionNode, NodeReader};
+
+/// Writes the test table to a file of `format` in `dir`, and returns its path: 100 rows of a
+/// `key` column and a `value` column of half the keys, in row groups (or record batches) of
+/// 30 rows.
+pub(crate) fn write_table(dir: &Path, format: &ScanFormat) -> String {
+    let rows = |range: std::ops::Range<i64>| {
+        df!(
+            "key" => range.clone().collect::<Vec<i64>>(),
+            "value" => range.map(|i| i as f64 / 2.0).collect::<Vec<f64>>()
+        )
+        .unwrap()
+    };
+    let mut df = rows(0..30);
+    for range in [30..60, 60..90, 90..100] {
+        df.vstack_mut(&rows(range)).unwrap();
+    }
+    let path = dir.join(match format {
+        ScanFormat::Csv { .. } => "table.csv",
+        ScanFormat::Parquet => "table.parquet",
+        ScanFormat::Ipc => "table.arrow",
+    });
+    let file = File::create(&path).unwrap();
+    match format {
+        ScanFormat::Csv {
+            delimiter,
+            has_headers,
+        } => {
+            CsvWriter::new(file)
+                .has_header(*has_headers)
+                .with_delimiter(*delimiter as u8)
+                .finish(&mut df)
+                .unwrap();
+        }
+        ScanFormat::Parquet => {
+            // polars' writer can't write row groups of a number of rows, so every chunk of the
+            // frame is written as a row group with arrow2, along with its statistics.
+            let schema = df.schema().to_arrow();
+            let options = parquet_write::WriteOptions {
+                write_statistics: true,
+                compression: parquet_write::CompressionOptions::Uncompressed,
+                version: parquet_write::Version::V2,
+            };
+            let encodings = schema
+                .fields
+                .iter()
+                .map(|_| vec![parquet_write::Encoding::Plain])
+                .collect();
+            let row_groups = parquet_write::RowGroupIterator::try_new(
+                df.iter_chunks().map(Ok),
+                &schema,
+                options,
+                encodings,
+            )
+            .unwrap();
+            let mut writer = parquet_write::FileWriter::try_new(file, schema, options).unwrap();
+            for row_group in row_groups {
+                writer.write(row_group.unwrap()).unwrap();
+            }
+            writer.end(None).unwrap();
+        }
+        ScanFormat::Ipc => {
+            IpcWriter::new(file).finish(&mut df).unwrap();
+        }
+    }
+    path.to_str().unwrap().to_string()
+}
+
+/// Runs `reader` on a single block of `files` with `metadata`, and returns the blocks it
+/// writes.
+pub(crate) fn read_files(
+    reader: ExecutionNode<DataFrame>,
+    files: &[&str],
+    metadata: BlockMetadata,
+) -> Vec<DataBlock<DataFrame>> {
+    let input_files = df!("col" => files).unwrap();
+    reader.write_to_self(0, DataMessage::from(DataBlock::new(input_files, metadata)));
+    reader.write_to_self(0, DataMessage::eof());
+    let reader_node = NodeReader::new(&reader);
+    reader.run();
+    read_blocks(&reader_node)
+}
+
+/// Reads the blocks written to `reader_node` up to EOF.
+pub(crate) fn read_blocks(reader_node: &NodeReader<DataFrame>) -> Vec<DataBlock<DataFrame>> {
+    let mut blocks = vec![];
+    loop {
+        let message = reader_node.read();
+        if message.is_eof() {
+            return blocks;
+        }
+        blocks.push(message.datablock().clone());
+    }
+}
+
+/// The names of the columns of `dblock`.
+pub(crate) fn column_names(dblock: &DataBlock<DataFrame>) -> Vec<String> {
+    dblock
+        .data()
+        .get_column_names()
+        .iter()
+        .map(|name| name.to_string())
+        .collect()
+}
@@ -0,0 +1,274 @@
+use polars::prelude::*;
+
+use crate::channel::{MultiChannelBroadcaster, MultiChannelReader};
+use crate::data::Schema;
+use crate::data::*;
+use crate::graph::ExecutionNode;
+use crate::processor::StreamProcessor;
+use crate::utils::log_event;
+
+/// Factory for creating an [ExecutionNode] that rebatches a stream of dataframes: consecutive
+/// frames are coalesced until they reach the target number of rows or the target size in
+/// bytes, and frames larger than the target are split. At least one target is required.
+///
+/// Rows keep their order. The [BlockMetadata::progress] of an output block is interpolated
+/// within the input block it ends in, by the fraction of the rows of that block it includes.
+/// The size in bytes is estimated by [DataFrame::estimated_size].
+#[derive(Default)]
+pub struct RebatchBuilder {
+    target_rows: Option<usize>,
+    target_bytes: Option<usize>,
+}
+
+impl RebatchBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn target_rows(&mut self, target_rows: usize) -> &mut Self {
+        self.target_rows = Some(target_rows);
+        self
+    }
+
+    pub fn target_bytes(&mut self, target_bytes: usize) -> &mut Self {
+        self.target_bytes = Some(target_bytes);
+        self
+    }
+
+    pub fn build(&self) -> ExecutionNode<DataFrame> {
+        assert!(
+            self.target_rows.is_some() || self.target_bytes.is_some(),
+            "A rebatch node requires a target number of rows or size in bytes"
+        );
+        assert!(
+            self.target_rows != Some(0) && self.target_bytes != Some(0),
+            "The targets of a rebatch node must be positive"
+        );
+        let rebatch = Rebatch {
+            target_rows: self.target_rows.unwrap_or(usize::MAX),
+            target_bytes: self.target_bytes.unwrap_or(usize::MAX),
+        };
+        ExecutionNode::<DataFrame>::new(Box::new(rebatch), 1)
+    }
+}
+
+struct Rebatch {
+    target_rows: usize,
+    target_bytes: usize,
+}
+
+/// The rows taken from the input but not written yet.
+#[derive(Default)]
+struct Batch {
+    frames: Vec<DataFrame>,
+    rows: usize,
+    bytes: usize,
+
+    /// The cardinality of the input read thus far, if the input has one.
+    cardinality: Option<f64>,
+}
+
+impl Batch {
+    fn take(&mut self, metadata: &BlockMetadata) -> DataBlock<DataFrame> {
+        let mut frames = self.frames.drain(..);
+        let mut df = frames.next().unwrap();
+        for frame in frames {
+            df.vstack_mut(&frame).unwrap();
+        }
+        df.rechunk();
+        self.rows = 0;
+        self.bytes = 0;
+        let mut metadata = metadata.clone();
+        if let Some(cardinality) = self.cardinality {
+            metadata.set_progress(cardinality);
+        }
+        DataBlock::new(df, metadata)
+    }
+}
+
+impl Rebatch {
+    /// Adds the rows of `dblock` to `batch`, and returns the blocks that reach a target.
+    fn rebatch(
+        &self,
+        batch: &mut Batch,
+        dblock: &DataBlock<DataFrame>,
+    ) -> Vec<DataBlock<DataFrame>> {
+        let df = dblock.data();
+        let num_rows = df.height();
+        let previous_cardinality = batch.cardinality.unwrap_or(0.0);
+        let cardinality = dblock.metadata().progress();
+        if num_rows == 0 {
+            // Kept so that an input without any rows still ends in a (empty) block.
+            batch.frames.push(df.clone());
+            batch.cardinality = cardinality;
+            return vec![];
+        }
+        let row_bytes = usize::max(1, df.estimated_size() / num_rows);
+
+        let mut output = vec![];
+        let mut offset = 0;
+        while offset < num_rows {
+            let rows_left = self.target_rows - batch.rows;
+            let bytes_left =
+                (self.target_bytes - batch.bytes).saturating_add(row_bytes - 1) / row_bytes;
+            let length = usize::min(num_rows - offset, usize::min(rows_left, bytes_left));
+            batch.frames.push(df.slice(offset as i64, length));
+            batch.rows += length;
+            batch.bytes = batch.bytes.saturating_add(length * row_bytes);
+            offset += length;
+            batch.cardinality = cardinality.map(|cardinality| {
+                let fraction = offset as f64 / num_rows as f64;
+                previous_cardinality + (cardinality - previous_cardinality) * fraction
+            });
+            if batch.rows >= self.target_rows || batch.bytes >= self.target_bytes {
+                output.push(batch.take(dblock.metadata()));
+            }
+        }
+        // The interpolation above may be off by a rounding error.
+        batch.cardinality = cardinality;
+        output
+    }
+}
+
+impl StreamProcessor<DataFrame> for Rebatch {
+    fn output_schema(
+        &self,
+        input_schemas: &[Option<Schema>],
+    ) -> std::result::Result<Option<Schema>, String> {
+        Ok(input_schemas[0].clone())
+    }
+
+    fn process_stream(
+        &self,
+        input_stream: MultiChannelReader<DataFrame>,
+        output_stream: MultiChannelBroadcaster<DataFrame>,
+    ) {
+        let mut batch = Batch::default();
+        let mut last_metadata = BlockMetadata::new();
+        loop {
+            let message = input_stream.read(0);
+            log_event("process-message", "start");
+            match message.payload() {
+                Payload::EOF | Payload::Signal(Signal::STOP) => {
+                    if !batch.frames.is_empty() {
+                        let dblock = batch.take(&last_metadata);
+                        output_stream.write(DataMessage::from(dblock));
+                    }
+                    output_stream.write(message);
+                    log_event("process-message", "end");
+                    break;
+                }
+                Payload::Error(_) => {
+                    // The rows taken thus far are dropped along with the rest of the input.
+                    output_stream.write(message);
+                    log_event("process-message", "end");
+                    break;
+                }
+                Payload::Signal(_) => {
+                    log_event("process-message", "end");
+                }
+                Payload::Some(dblock) => {
+                    for output_dblock in self.rebatch(&mut batch, &dblock) {
+                        output_stream.write(DataMessage::from(output_dblock));
+                    }
+                    last_metadata = dblock.metadata().clone();
+                    log_event("process-message", "end");
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::NodeReader;
+
+    fn block(values: &[i32], cardinality: f64) -> DataMessage<DataFrame> {
+        let metadata = BlockMetadata::new().with_progress(cardinality);
+        DataMessage::from(DataBlock::new(df!("value" => values).unwrap(), metadata))
+    }
+
+    /// Runs a rebatch node over `inputs`, and returns (values, cardinality) of every output.
+    fn rebatch(
+        builder: &RebatchBuilder,
+        inputs: Vec<DataMessage<DataFrame>>,
+    ) -> Vec<(Vec<i32>, f64)> {
+        let node = builder.build();
+        for input in inputs {
+            node.write_to_self(0, input);
+        }
+        node.write_to_self(0, DataMessage::eof());
+        let reader_node = NodeReader::new(&node);
+        node.run();
+
+        let mut outputs = vec![];
+        loop {
+            let message = reader_node.read();
+            if message.is_eof() {
+                return outputs;
+            }
+            let dblock = message.datablock();
+            let values = dblock
+                .data()
+                .column("value")
+                .unwrap()
+                .i32()
+                .unwrap()
+                .into_no_null_iter()
+                .collect();
+            let cardinality = dblock.metadata().progress().unwrap();
+            outputs.push((values, cardinality));
+        }
+    }
+
+    fn assert_outputs(outputs: Vec<(Vec<i32>, f64)>, expected: Vec<(Vec<i32>, f64)>) {
+        assert_eq!(outputs.len(), expected.len());
+        for ((values, cardinality), (expected_values, expected_cardinality)) in
+            outputs.into_iter().zip(expected)
+        {
+            assert_eq!(values, expected_values);
+            assert!((cardinality - expected_cardinality).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn coalesces_and_splits_by_rows() {
+        let inputs = vec![
+            block(&[1, 2], 0.1),
+            block(&[3, 4], 0.2),
+            block(&[5, 6], 0.3),
+            block(&[7, 8, 9, 10, 11], 1.0),
+        ];
+        let outputs = rebatch(RebatchBuilder::new().target_rows(4), inputs);
+        assert_outputs(
+            outputs,
+            vec![
+                (vec![1, 2, 3, 4], 0.2),
+                // Two of the five rows of the last input block.
+                (vec![5, 6, 7, 8], 0.3 + 0.7 * 2.0 / 5.0),
+                (vec![9, 10, 11], 1.0),
+            ],
+        );
+    }
+
+    #[test]
+    fn splits_by_bytes() {
+        let values: Vec<i32> = (0..10).collect();
+        let row_bytes = df!("value" => &values).unwrap().estimated_size() / values.len();
+        let outputs = rebatch(
+            RebatchBuilder::new().target_bytes(5 * row_bytes),
+            vec![block(&values, 1.0)],
+        );
+        assert_outputs(
+            outputs,
+            vec![(vec![0, 1, 2, 3, 4], 0.5), (vec![5, 6, 7, 8, 9], 1.0)],
+        );
+    }
+
+    #[test]
+    fn empty_input_ends_in_empty_block() {
+        let outputs = rebatch(RebatchBuilder::new().target_rows(4), vec![block(&[], 1.0)]);
+        assert_outputs(outputs, vec![(vec![], 1.0)]);
+    }
+}
@@ -1,3 +1,23 @@
+use crate::data::Schema;
+
+/// The schema of the join of `left` and `right` as Polars builds it: the columns of `left`,
+/// then those of `right` but its join columns `right_on`. A column of `right` named like a
+/// column of `left` gets the suffix `_right`.
+pub(crate) fn join_schema(left: &Schema, right: &Schema, right_on: &[String]) -> Schema {
+    let mut columns = left.columns.clone();
+    for column in right.columns.iter() {
+        if right_on.contains(&column.name) {
+            continue;
+        }
+        let mut column = column.clone();
+        if left.contains(&column.name) {
+            column.name = format!("{}_right", column.name);
+        }
+        columns.push(column);
+    }
+    Schema::new(left.table.clone(), columns)
+}
+
 #[cfg(test)]
 pub mod tests {
     use polars::prelude::*;
@@ -1,14 +1,14 @@
-use std::collections::HashMap;
-
 use crate::channel::MultiChannelReader;
 use crate::channel::MultiChannelBroadcaster;
 use crate::data::{
-    DATABLOCK_CARDINALITY,
+    BlockMetadata,
     DataBlock,
     DataMessage,
-    MetaCell,
     Payload,
+    Schema,
+    Signal,
 };
+use crate::graph::OperatorState;
 use crate::utils::log_event;
 
 use super::StreamProcessor;
@@ -20,16 +20,30 @@ pub trait MessageProcessor<T> {
     fn post_process_msg(&self) -> Option<T> {
         None
     }
+
+    /// See [StreamProcessor::checkpoint].
+    fn checkpoint_msg(&self, _state: &mut OperatorState) {}
+
+    /// See [StreamProcessor::restore].
+    fn restore_msg(&mut self, _state: &OperatorState) {}
 }
 
 /// Implements [StreamProcessor] for a type R of trait [MessageProcessor]
 impl<T: Send, R: MessageProcessor<T> + Send> StreamProcessor<T> for R {
+    fn checkpoint(&self, _channel: usize, state: &mut OperatorState) {
+        self.checkpoint_msg(state)
+    }
+
+    fn restore(&mut self, state: &OperatorState) {
+        self.restore_msg(state)
+    }
+
     fn process_stream(
         &self,
         input_stream: crate::channel::MultiChannelReader<T>,
         output_stream: crate::channel::MultiChannelBroadcaster<T>,
     ) {
-        let mut last_metadata: Option<HashMap<String, MetaCell>> = None;
+        let mut last_metadata: Option<BlockMetadata> = None;
         loop {
             let channel_seq = 0;
             let message = input_stream.read(channel_seq);
@@ -37,11 +51,11 @@ impl<T: Send, R: MessageProcessor<T> + Send> StreamProcessor<T> for R {
             match message.payload() {
                 Payload::EOF => {
                     if let Some(df_acc) = self.post_process_msg() {
-                        let mut eof_metadata = last_metadata.as_ref()
-                            .expect("No metadata to clone at post process")
-                            .clone();
-                        if let Some(cardinality) = eof_metadata.get_mut(DATABLOCK_CARDINALITY) {
-                            *cardinality = MetaCell::from(1.0);
+                        // Without any input, e.g., when resuming after all the input was
+                        // processed, there is no metadata to clone.
+                        let mut eof_metadata = last_metadata.clone().unwrap_or_default();
+                        if eof_metadata.progress().is_some() {
+                            eof_metadata.set_progress(1.0);
                         }
                         let post_process_dblock = DataBlock::new(df_acc, eof_metadata);
                         let post_process_msg = DataMessage::from(post_process_dblock);
@@ -62,10 +76,28 @@ impl<T: Send, R: MessageProcessor<T> + Send> StreamProcessor<T> for R {
                     }
                     log_event("process-message", "end");
                 }
-                Payload::Signal(_) => {
+                Payload::Signal(Signal::STOP) => {
+                    // Stopped before EOF; flush what has been accumulated thus far.
+                    if let Some(metadata) = last_metadata.as_ref() {
+                        if let Some(df_acc) = self.post_process_msg() {
+                            let post_process_dblock = DataBlock::new(df_acc, metadata.clone());
+                            output_stream.write(DataMessage::from(post_process_dblock));
+                        }
+                    }
+                    output_stream.write(message);
                     log_event("process-message", "end");
                     break;
                 }
+                Payload::Error(_) => {
+                    // The input is incomplete, so nothing accumulated is flushed.
+                    output_stream.write(message);
+                    log_event("process-message", "end");
+                    break;
+                }
+                Payload::Signal(_) => {
+                    // Pausing and resuming only concern source readers.
+                    log_event("process-message", "end");
+                }
             }
         }
     }
@@ -117,6 +149,13 @@ impl<T: Send> MessageProcessor<T> for SimpleMapper<T> {
 pub trait MessageFractionProcessor<T: Clone + Send>: Send {
     fn process(&self, df: &T, fraction: f64) -> T;
 
+    /// The schema of the output of [Self::process] given the schema of its input, or None if
+    /// unknown. Returns an error if the input lacks a column the processor reads, like
+    /// [StreamProcessor::output_schema].
+    fn output_schema(&self, _input_schema: &Schema) -> Result<Option<Schema>, String> {
+        Ok(None)
+    }
+
     fn process_stream_inner(
         &self,
         input_stream: MultiChannelReader<T>,
@@ -133,19 +172,23 @@ pub trait MessageFractionProcessor<T: Clone + Send>: Send {
                     break;
                 }
                 Payload::Some(dblock) => {
-                    let fraction = f64::from(dblock.metadata()
-                        .get(DATABLOCK_CARDINALITY)
-                        .expect("MessageFractionProcessor requires cardinality fraction"));
+                    let fraction = dblock.metadata()
+                        .progress()
+                        .expect("MessageFractionProcessor requires cardinality fraction");
                     let output_df = self.process(dblock.data(), fraction);
                     let output_dblock = DataBlock::new(output_df, dblock.metadata().clone());
                     let output_message = DataMessage::from(output_dblock);
                     log_event("process-message", "end");
                     output_stream.write(output_message);
                 }
-                Payload::Signal(_) => {
+                Payload::Signal(Signal::STOP) | Payload::Error(_) => {
+                    output_stream.write(message);
                     log_event("process-message", "end");
                     break;
                 }
+                Payload::Signal(_) => {
+                    log_event("process-message", "end");
+                }
             }
         }
     }
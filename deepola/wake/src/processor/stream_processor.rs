@@ -1,4 +1,6 @@
 use crate::channel::{MultiChannelBroadcaster, MultiChannelReader};
+use crate::data::Schema;
+use crate::graph::OperatorState;
 
 /// The interface for ExecutionNode.
 ///
@@ -15,4 +17,35 @@ pub trait StreamProcessor<T: Send>: Send {
         input_stream: MultiChannelReader<T>,
         output_stream: MultiChannelBroadcaster<T>,
     );
+
+    /// Saves the state built from the `channel`-th input so far into a checkpoint; see
+    /// [crate::graph::ExecutionService::checkpoint]. Called while the node runs, in between
+    /// two messages read from that channel, so the state must reflect every message read from
+    /// it before. Operators that keep no state across messages need not implement this.
+    fn checkpoint(&self, _channel: usize, _state: &mut OperatorState) {}
+
+    /// Restores the state saved by [Self::checkpoint] when a query resumes from a checkpoint.
+    /// Called before [Self::pre_process].
+    fn restore(&mut self, _state: &OperatorState) {}
+
+    /// The schema of the output, given the schemas of the inputs, one per input channel (None
+    /// where unknown). Returns None if the output schema cannot be told from the inputs, and
+    /// a description of the problem if an input lacks a column the operator reads; see
+    /// [crate::graph::ExecutionService::validate]. Operators whose output is unknown until
+    /// they run, e.g., ones that apply a closure, need not implement this.
+    fn output_schema(&self, _input_schemas: &[Option<Schema>]) -> Result<Option<Schema>, String> {
+        Ok(None)
+    }
+
+    /// The operator kind shown in exported plans, e.g., `HashJoinNode`. Defaults to the name
+    /// of the implementing type without its module path and generic arguments.
+    fn operator_name(&self) -> String {
+        let type_name = std::any::type_name::<Self>();
+        let without_generics = type_name.split('<').next().unwrap_or(type_name);
+        without_generics
+            .rsplit("::")
+            .next()
+            .unwrap_or(without_generics)
+            .to_string()
+    }
 }